@@ -38,6 +38,19 @@ fn main() {
         build.flag("-std=c++11").cpp_link_stdlib("stdc++").cpp(true);
     }
 
+    // keep floating point results identical across compilers and platforms, so optimized
+    // meshes are byte-identical wherever they are built; GCC and Clang may otherwise fuse
+    // multiplies and adds into FMA instructions on targets that have them, and MSVC only
+    // keeps them separate under /fp:precise
+    if target.contains("msvc") {
+        build.flag("/fp:precise");
+    } else {
+        build.flag_if_supported("-ffp-contract=off");
+    }
+
+    let simd = configure_simd(&mut build, &target);
+    println!("cargo:rustc-env=MESHOPT_CODEC_SIMD={}", simd);
+
     if target.starts_with("wasm32") {
         // In webassembly there's no stdlib, so we use
         // our own stripped down headers to provide the few
@@ -57,6 +70,61 @@ fn main() {
     generate_bindings("gen/bindings.rs");
 }
 
+/// Selects the SIMD path of the vertex codec from the `simd_*` features and the target
+/// features, returning its name for `codec_features`.
+///
+/// Without flags, meshoptimizer uses NEON on aarch64 and SSSE3 on x86 (checking for it at
+/// runtime with GCC and Clang); the features enable the other paths, which need the
+/// corresponding instructions at compile time.
+fn configure_simd(build: &mut cc::Build, target: &str) -> &'static str {
+    let enabled = |feature: &str| env::var_os(format!("CARGO_FEATURE_{}", feature)).is_some();
+    let target_features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+    let has_target_feature = |feature: &str| target_features.split(',').any(|f| f == feature);
+    let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let msvc = target.contains("msvc");
+
+    if enabled("NO_SIMD") {
+        build.define("MESHOPTIMIZER_NO_SIMD", None);
+        return "none";
+    }
+
+    match arch.as_str() {
+        "x86" | "x86_64" => {
+            if enabled("SIMD_AVX") {
+                if msvc {
+                    build.flag("/arch:AVX512");
+                } else {
+                    build
+                        .flag("-mavx512vbmi")
+                        .flag("-mavx512vbmi2")
+                        .flag("-mavx512vl")
+                        .flag("-mpopcnt");
+                }
+                "avx512"
+            } else if enabled("SIMD_SSE") || has_target_feature("ssse3") {
+                if !msvc {
+                    build.flag("-mssse3");
+                }
+                "sse"
+            } else if msvc {
+                "sse"
+            } else {
+                "sse_dispatch"
+            }
+        }
+        "aarch64" | "arm64ec" => "neon",
+        "arm" if enabled("SIMD_NEON") || has_target_feature("neon") => {
+            build.flag("-mfpu=neon");
+            "neon"
+        }
+        "wasm32" if enabled("SIMD_WASM") || has_target_feature("simd128") => {
+            build.flag("-msimd128");
+            "wasm_simd128"
+        }
+        _ => "none",
+    }
+}
+
 #[cfg(feature = "generate_bindings")]
 fn generate_bindings(output_file: &str) {
     let bindings = bindgen::Builder::default()
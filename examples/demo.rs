@@ -1,13 +1,11 @@
 #![allow(clippy::identity_op)]
 
 use memoffset::offset_of;
+use meshopt::io::obj::ObjMesh;
 use meshopt::*;
 use rand::{seq::SliceRandom, thread_rng};
 use std::{
-    fmt,
-    fs::File,
-    io::Write,
-    mem,
+    fmt, mem,
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
@@ -96,7 +94,7 @@ impl fmt::Debug for Mesh {
 
 impl Mesh {
     fn is_valid(&self) -> bool {
-        if self.indices.len() % 3 != 0 {
+        if !self.indices.len().is_multiple_of(3) {
             return false;
         } else {
             for i in 0..self.indices.len() {
@@ -109,86 +107,11 @@ impl Mesh {
     }
 
     fn load_obj(path: &Path) -> Self {
-        let obj = tobj::load_obj(
-            path,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        );
-        assert!(obj.is_ok());
-        let (models, _materials) = obj.unwrap();
-
-        assert!(models.len() == 1);
-
-        let mut merged_vertices: Vec<Vertex> = Vec::new();
-
-        let mut total_indices = 0;
-
-        for m in models.iter() {
-            let mut vertices: Vec<Vertex> = Vec::new();
-            let mesh = &m.mesh;
-            total_indices += mesh.indices.len();
-
-            for i in 0..mesh.indices.len() {
-                let index = mesh.indices[i] as usize;
-
-                // pos = [x, y, z]
-                let p = [
-                    mesh.positions[index * 3],
-                    mesh.positions[index * 3 + 1],
-                    mesh.positions[index * 3 + 2],
-                ];
-
-                let n = if !mesh.normals.is_empty() {
-                    // normal = [x, y, z]
-                    [
-                        mesh.normals[index * 3],
-                        mesh.normals[index * 3 + 1],
-                        mesh.normals[index * 3 + 2],
-                    ]
-                } else {
-                    [0f32, 0f32, 0f32]
-                };
-
-                let t = if !mesh.texcoords.is_empty() {
-                    // tex coord = [u, v];
-                    [mesh.texcoords[index * 2], mesh.texcoords[index * 2 + 1]]
-                } else {
-                    [0f32, 0f32]
-                };
-
-                vertices.push(Vertex { p, n, t });
-            }
-
-            merged_vertices.append(&mut vertices);
-        }
-
-        let (total_vertices, vertex_remap) = meshopt::generate_vertex_remap(&merged_vertices, None);
-
-        let mut mesh = Self::default();
-
-        mesh.indices.resize(total_indices, 0u32);
-        unsafe {
-            meshopt::ffi::meshopt_remapIndexBuffer(
-                mesh.indices.as_ptr() as *mut ::std::os::raw::c_uint,
-                ::std::ptr::null(),
-                total_indices,
-                vertex_remap.as_ptr() as *const ::std::os::raw::c_uint,
-            );
-        }
-
-        mesh.vertices.resize(total_vertices, Vertex::default());
-        unsafe {
-            meshopt::ffi::meshopt_remapVertexBuffer(
-                mesh.vertices.as_ptr() as *mut ::std::os::raw::c_void,
-                merged_vertices.as_ptr() as *const ::std::os::raw::c_void,
-                total_indices,
-                mem::size_of::<Vertex>(),
-                vertex_remap.as_ptr() as *const ::std::os::raw::c_uint,
-            );
-        }
+        let obj = ObjMesh::load(path).unwrap();
+        let mesh = Self {
+            vertices: obj.vertices,
+            indices: obj.indices,
+        };
 
         println!(
             "# {:?}: {} vertices, {} triangles",
@@ -201,39 +124,12 @@ impl Mesh {
     }
 
     #[allow(dead_code)]
-    fn save_obj(&self, path: &Path) -> std::io::Result<()> {
-        let mut buffer = File::create(path)?;
-
-        for i in 0..self.vertices.len() {
-            writeln!(
-                buffer,
-                "v {} {} {}",
-                self.vertices[i].p[0], self.vertices[i].p[1], self.vertices[i].p[2]
-            )?;
-            writeln!(
-                buffer,
-                "vn {} {} {}",
-                self.vertices[i].n[0], self.vertices[i].n[1], self.vertices[i].n[2]
-            )?;
-            writeln!(
-                buffer,
-                "vt {} {} {}",
-                self.vertices[i].t[0], self.vertices[i].t[1], 0f32
-            )?;
-        }
-
-        for i in (0..self.indices.len()).step_by(3) {
-            let i0 = self.indices[i + 0] + 1;
-            let i1 = self.indices[i + 1] + 1;
-            let i2 = self.indices[i + 2] + 1;
-            writeln!(
-                buffer,
-                "f {}/{}/{} {}/{}/{} {}/{}/{}",
-                i0, i0, i0, i1, i1, i1, i2, i2, i2
-            )?;
+    fn save_obj(&self, path: &Path) -> meshopt::Result<()> {
+        ObjMesh {
+            vertices: self.vertices.clone(),
+            indices: self.indices.clone(),
         }
-
-        Ok(())
+        .save(path)
     }
 
     fn create_plane(size: u32) -> Self {
@@ -298,22 +194,15 @@ impl Mesh {
     }
 
     fn split(&mut self) -> (VertexDataAdapter, &mut [u32]) {
-        let position_offset = offset_of!(Vertex, p);
-        let vertex_stride = std::mem::size_of::<Vertex>();
-        let vertex_data = typed_to_bytes(&self.vertices);
         (
-            VertexDataAdapter::new(vertex_data, vertex_stride, position_offset)
+            VertexDataAdapter::from_slice(&self.vertices, offset_of!(Vertex, p))
                 .expect("failed to create vertex data reader"),
             &mut self.indices,
         )
     }
 
     fn vertex_adapter(&self) -> VertexDataAdapter {
-        let position_offset = offset_of!(Vertex, p);
-        let vertex_stride = std::mem::size_of::<Vertex>();
-        let vertex_data = typed_to_bytes(&self.vertices);
-
-        VertexDataAdapter::new(vertex_data, vertex_stride, position_offset)
+        VertexDataAdapter::from_slice(&self.vertices, offset_of!(Vertex, p))
             .expect("failed to create vertex data reader")
     }
 }
@@ -338,11 +227,23 @@ fn optimize_mesh(mesh: &Mesh, name: &str, opt: fn(mesh: &mut Mesh)) {
 
     let os = meshopt::analyze_overdraw(&copy.indices, &vertex_adapter);
 
-    let vcs_nv = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 32, 32, 32);
+    let vcs_nv = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Nvidia,
+    );
 
-    let vcs_amd = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 14, 64, 128);
+    let vcs_amd = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Amd,
+    );
 
-    let vcs_intel = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 128, 0, 0);
+    let vcs_intel = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Intel,
+    );
 
     println!(
         "{:9}: ACMR {:.6} ATVR {:.6} (NV {:.6} AMD {:.6} Intel {:.6}) Overfetch {:.6} Overdraw {:.6} in {:.2} msec",
@@ -410,40 +311,49 @@ fn opt_fetch_remap(mesh: &mut Mesh) {
 }
 
 fn opt_complete(mesh: &mut Mesh) {
-    {
-        let (vertex_adapter, indices) = mesh.split();
-
-        // vertex cache optimization should go first as it provides starting order for overdraw
-        meshopt::optimize_vertex_cache_in_place(indices, vertex_adapter.vertex_count);
-
-        // reorder indices for overdraw, balancing overdraw and vertex cache efficiency
-        let threshold = 1.05f32; // allow up to 5% worse ACMR to get more reordering opportunities for overdraw
-        meshopt::optimize_overdraw_in_place(indices, &vertex_adapter, threshold);
-    }
-
-    // vertex fetch optimization should go last as it depends on the final index order
-    let final_size = meshopt::optimize_vertex_fetch_in_place(&mut mesh.indices, &mut mesh.vertices);
+    let final_size = meshopt::optimize_complete(
+        &mut mesh.indices,
+        &mut mesh.vertices,
+        &meshopt::OptimizeConfig::default(),
+    );
     mesh.vertices.resize(final_size, Default::default());
 }
 
 fn stripify(mesh: &Mesh, use_restart: bool) {
-    let restart_index = if use_restart { 0xffffffff } else { 0x00000000 };
+    let restart_mode = if use_restart {
+        meshopt::RestartMode::Index(0xffffffff)
+    } else {
+        meshopt::RestartMode::Degenerate
+    };
 
     let process_start = Instant::now();
-    let strip = meshopt::stripify(&mesh.indices, mesh.vertices.len(), restart_index).unwrap();
+    let strip =
+        meshopt::stripify_with_restart(&mesh.indices, mesh.vertices.len(), restart_mode).unwrap();
     let process_elapsed = process_start.elapsed();
 
     let mut copy = mesh.clone();
-    copy.indices = meshopt::unstripify(&strip, restart_index).unwrap();
+    copy.indices = meshopt::unstripify_with_restart(&strip, restart_mode).unwrap();
 
     assert!(copy.is_valid());
     assert_eq!(mesh, &copy);
 
     let vcs =
         meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), CACHE_SIZE as u32, 0, 0);
-    let vcs_nv = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 32, 32, 32);
-    let vcs_amd = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 14, 64, 128);
-    let vcs_intel = meshopt::analyze_vertex_cache(&copy.indices, copy.vertices.len(), 128, 0, 0);
+    let vcs_nv = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Nvidia,
+    );
+    let vcs_amd = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Amd,
+    );
+    let vcs_intel = meshopt::analyze_vertex_cache_for(
+        &copy.indices,
+        copy.vertices.len(),
+        meshopt::VertexCacheModel::Intel,
+    );
 
     println!("Stripify{}: ACMR {:.6} ATVR {:.6} (NV {:.6} AMD {:.6} Intel {:.6}); {} strip indices ({:.1}%) in {:.2} msec",
         if use_restart { "R" } else { " " },
@@ -508,7 +418,8 @@ fn meshlets(mesh: &Mesh) {
         max_vertices,
         max_triangles,
         0.5, // cone weight
-    );
+    )
+    .unwrap();
     let process_elapsed = process_start.elapsed();
 
     let mut avg_vertices = 0f64;
@@ -546,65 +457,34 @@ fn meshlets(mesh: &Mesh) {
 
     let test_start = Instant::now();
     for meshlet in meshlets.iter() {
-        let bounds = meshopt::compute_meshlet_bounds(meshlet, &vertex_adapter);
+        let bounds: meshopt::ClusterBounds =
+            meshopt::compute_meshlet_bounds(meshlet, &vertex_adapter).into();
 
         // trivial accept: we can't ever backface cull this meshlet
-        if bounds.cone_cutoff >= 1f32 {
+        if bounds.is_cone_degenerate() {
             accepted += 1;
         }
 
-        if bounds.cone_cutoff_s8 == 127 {
+        if bounds.is_cone_degenerate_s8() {
             accepted_s8 += 1;
         }
 
         // perspective projection: dot(normalize(cone_apex - camera_position), cone_axis) > cone_cutoff
-        let mview: [f32; 3] = [
-            bounds.cone_apex[0] - camera[0],
-            bounds.cone_apex[1] - camera[1],
-            bounds.cone_apex[2] - camera[2],
-        ];
-
-        let mviewlength = (mview[0] * mview[0] + mview[1] * mview[1] + mview[2] * mview[2]).sqrt();
-
-        if mview[0] * bounds.cone_axis[0]
-            + mview[1] * bounds.cone_axis[1]
-            + mview[2] * bounds.cone_axis[2]
-            >= bounds.cone_cutoff * mviewlength
-        {
+        if bounds.is_backface_culled(camera) {
             rejected += 1;
         }
 
-        if mview[0] * (bounds.cone_axis_s8[0] as f32 / 127.0)
-            + mview[1] * (bounds.cone_axis_s8[1] as f32 / 127.0)
-            + mview[2] * (bounds.cone_axis_s8[2] as f32 / 127.0)
-            >= (bounds.cone_cutoff_s8 as f32 / 127.0) * mviewlength
-        {
+        if bounds.is_backface_culled_s8(camera) {
             rejected_s8 += 1;
         }
 
         // alternative formulation for perspective projection that doesn't use apex (and uses cluster bounding sphere instead):
         // dot(normalize(center - camera_position), cone_axis) > cone_cutoff + radius / length(center - camera_position)
-        let cview: [f32; 3] = [
-            bounds.center[0] - camera[0],
-            bounds.center[1] - camera[1],
-            bounds.center[2] - camera[2],
-        ];
-
-        let cviewlength = (cview[0] * cview[0] + cview[1] * cview[1] + cview[2] * cview[2]).sqrt();
-
-        if cview[0] * bounds.cone_axis[0]
-            + cview[1] * bounds.cone_axis[1]
-            + cview[2] * bounds.cone_axis[2]
-            >= bounds.cone_cutoff * cviewlength + bounds.radius
-        {
+        if bounds.is_backface_culled_sphere(camera) {
             rejected_alt += 1;
         }
 
-        if cview[0] * (bounds.cone_axis_s8[0] as f32 / 127.0)
-            + cview[1] * (bounds.cone_axis_s8[1] as f32 / 127.0)
-            + cview[2] * (bounds.cone_axis_s8[2] as f32 / 127.0)
-            >= (bounds.cone_cutoff_s8 as f32 / 127.0) * cviewlength + bounds.radius
-        {
+        if bounds.is_backface_culled_sphere_s8(camera) {
             rejected_alt_s8 += 1;
         }
     }
@@ -769,19 +649,7 @@ fn encode_index(mesh: &Mesh) {
     let decode_elapsed = decode_start.elapsed();
 
     let compressed = compress(&encoded);
-    for i in (0..mesh.indices.len()).step_by(3) {
-        assert!(
-            (decoded[i + 0] == mesh.indices[i + 0]
-                && decoded[i + 1] == mesh.indices[i + 1]
-                && decoded[i + 2] == mesh.indices[i + 2])
-                || (decoded[i + 1] == mesh.indices[i + 0]
-                    && decoded[i + 2] == mesh.indices[i + 1]
-                    && decoded[i + 0] == mesh.indices[i + 2])
-                || (decoded[i + 2] == mesh.indices[i + 0]
-                    && decoded[i + 0] == mesh.indices[i + 1]
-                    && decoded[i + 1] == mesh.indices[i + 2])
-        );
-    }
+    meshopt::verify_index_roundtrip(&mesh.indices, &decoded).unwrap();
 
     if mesh.vertices.len() <= 65536 {
         let decoded2 = meshopt::decode_index_buffer::<u16>(&encoded, mesh.indices.len()).unwrap();
@@ -804,18 +672,16 @@ fn encode_index(mesh: &Mesh) {
     );
 }
 
-fn encode_vertex<T: FromVertex + Clone + Default + Eq>(mesh: &Mesh, name: &str) {
-    let packed = pack_vertices::<T>(&mesh.vertices);
-
+fn encode_vertex<T: Clone + Default + Eq>(mesh: &Mesh, packed: &[T], name: &str) {
     let encode_start = Instant::now();
-    let encoded = meshopt::encode_vertex_buffer(&packed).unwrap();
+    let encoded = meshopt::encode_vertex_buffer(packed).unwrap();
     let encode_elapsed = encode_start.elapsed();
 
     let decode_start = Instant::now();
     let decoded = meshopt::decode_vertex_buffer(&encoded, mesh.vertices.len()).unwrap();
     let decode_elapsed = decode_start.elapsed();
 
-    assert!(packed == decoded);
+    meshopt::verify_vertex_roundtrip(packed, &decoded).unwrap();
 
     let compressed = compress(&encoded);
 
@@ -843,9 +709,10 @@ fn pack_mesh<T: FromVertex + Clone + Default>(mesh: &Mesh, name: &str) {
 }
 
 fn compress<T: Clone + Default>(data: &[T]) -> Vec<u8> {
-    use miniz_oxide::deflate::compress_to_vec;
     let bytes: &[u8] = typed_to_bytes(data);
-    compress_to_vec(bytes, 6 /* 0-10 compression level */)
+    meshopt::Codec::Deflate(6 /* 0-10 compression level */)
+        .compress(bytes)
+        .unwrap()
 }
 
 fn process(path: Option<PathBuf>, export: bool) {
@@ -894,8 +761,12 @@ fn process(path: Option<PathBuf>, export: bool) {
 
     encode_index(&copy);
     pack_mesh::<PackedVertex>(&copy, "");
-    encode_vertex::<PackedVertex>(&copy, "");
-    encode_vertex::<PackedVertexOct>(&copy, "0");
+    let packed = pack_vertices::<PackedVertex>(&copy.vertices);
+    encode_vertex(&copy, &packed, "");
+    let oct = pack_vertices::<PackedVertexOct>(&copy.vertices);
+    encode_vertex(&copy, &oct, "0");
+    let (snorm10, _) = PackedVertexSnorm10::pack_vertices_in_grid(&copy.vertices);
+    encode_vertex(&copy, &snorm10, "1");
 
     simplify(&mesh);
 }
@@ -0,0 +1,165 @@
+use crate::ffi;
+use std::alloc::{self, Layout};
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+
+// Every block is prefixed with a header storing the total allocation size, since
+// meshoptimizer only passes the pointer back on deallocation. The header also keeps
+// the returned pointer aligned for any type the native code may place in the block.
+const HEADER_SIZE: usize = 16;
+
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// meshoptimizer frees its temporary allocations on the thread that made them before
+// returning, so per-thread counters attribute allocations to the operation running on it
+thread_local! {
+    static THREAD_CURRENT_BYTES: Cell<usize> = const { Cell::new(0) };
+    static THREAD_PEAK_BYTES: Cell<usize> = const { Cell::new(0) };
+    static THREAD_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn track_allocation(size: usize) {
+    let current = CURRENT_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_BYTES.fetch_max(current, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+    // `try_with` fails while the thread is being torn down, where stats no longer matter
+    let _ = THREAD_CURRENT_BYTES.try_with(|thread_current| {
+        let current = thread_current.get() + size;
+        thread_current.set(current);
+        THREAD_PEAK_BYTES.with(|peak| peak.set(peak.get().max(current)));
+        THREAD_ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+    });
+}
+
+fn track_deallocation(size: usize) {
+    CURRENT_BYTES.fetch_sub(size, Ordering::Relaxed);
+    let _ =
+        THREAD_CURRENT_BYTES.try_with(|current| current.set(current.get().saturating_sub(size)));
+}
+
+unsafe extern "C" fn allocate(size: usize) -> *mut c_void {
+    let layout = match size
+        .checked_add(HEADER_SIZE)
+        .and_then(|total| Layout::from_size_align(total, HEADER_SIZE).ok())
+    {
+        Some(layout) => layout,
+        // there is no way to report failure to the native side, and unwinding across
+        // the FFI boundary is not allowed
+        None => std::process::abort(),
+    };
+
+    let base = alloc::alloc(layout);
+    if base.is_null() {
+        alloc::handle_alloc_error(layout);
+    }
+    base.cast::<usize>().write(layout.size());
+    track_allocation(size);
+    base.add(HEADER_SIZE).cast()
+}
+
+unsafe extern "C" fn deallocate(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    let base = ptr.cast::<u8>().sub(HEADER_SIZE);
+    let size = base.cast::<usize>().read();
+    track_deallocation(size - HEADER_SIZE);
+    alloc::dealloc(base, Layout::from_size_align_unchecked(size, HEADER_SIZE));
+}
+
+/// Routes all temporary allocations made by meshoptimizer through Rust's global allocator,
+/// instead of the default C++ `operator new`/`operator delete`.
+///
+/// This makes native memory usage visible to custom global allocators (jemalloc, mimalloc,
+/// tracking allocators, etc.).
+///
+/// The call is idempotent.
+///
+/// # Safety
+///
+/// No other function of this crate may be running on another thread during the call, as a
+/// block allocated with `operator new` before the switch would then be freed through the
+/// Rust allocator. Calling it at startup, before spawning threads, satisfies this.
+pub unsafe fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe {
+        ffi::meshopt_setAllocator(Some(allocate), Some(deallocate));
+    });
+}
+
+/// Memory allocated by meshoptimizer, as reported by `allocation_stats` and
+/// `measure_allocations`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AllocationStats {
+    /// Bytes currently allocated.
+    pub current_bytes: usize,
+    /// Highest number of bytes allocated at once.
+    pub peak_bytes: usize,
+    /// Number of allocations made.
+    pub allocations: usize,
+}
+
+/// Returns the memory allocated by meshoptimizer on all threads: the bytes currently
+/// allocated, and the peak and number of allocations since startup or the last call to
+/// `reset_allocation_stats`.
+///
+/// Allocations are only tracked once `init` has installed the allocator hook; before that,
+/// all stats are 0.
+pub fn allocation_stats() -> AllocationStats {
+    AllocationStats {
+        current_bytes: CURRENT_BYTES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// Resets the peak reported by `allocation_stats` to the bytes currently allocated, and the
+/// number of allocations to 0.
+pub fn reset_allocation_stats() {
+    PEAK_BYTES.store(CURRENT_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+}
+
+/// Runs `f`, returning its result together with the memory meshoptimizer allocated on the
+/// current thread while it ran, e.g. the peak temporary memory of a single simplification.
+///
+/// Since allocations are tracked per thread, operations running concurrently on other
+/// threads don't affect the result; work `f` hands off to other threads (e.g. the `par_`
+/// functions) isn't included, though. Requires `init`, like `allocation_stats`.
+pub fn measure_allocations<R>(f: impl FnOnce() -> R) -> (R, AllocationStats) {
+    let start_bytes = THREAD_CURRENT_BYTES.with(Cell::get);
+    let start_allocations = THREAD_ALLOCATIONS.with(Cell::get);
+    let guard = PeakGuard {
+        outer_peak: THREAD_PEAK_BYTES.with(|peak| peak.replace(start_bytes)),
+    };
+
+    let result = f();
+
+    let end_bytes = THREAD_CURRENT_BYTES.with(Cell::get);
+    let peak = THREAD_PEAK_BYTES.with(Cell::get);
+    drop(guard);
+    let stats = AllocationStats {
+        current_bytes: end_bytes.saturating_sub(start_bytes),
+        peak_bytes: peak - start_bytes,
+        allocations: THREAD_ALLOCATIONS.with(Cell::get) - start_allocations,
+    };
+    (result, stats)
+}
+
+/// Restores the thread peak of an enclosing measurement when `measure_allocations` returns,
+/// or when `f` panics.
+struct PeakGuard {
+    outer_peak: usize,
+}
+
+impl Drop for PeakGuard {
+    fn drop(&mut self) {
+        // keep the peak of an enclosing measurement intact
+        THREAD_PEAK_BYTES.with(|peak| peak.set(self.outer_peak.max(peak.get())));
+    }
+}
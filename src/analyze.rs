@@ -1,5 +1,8 @@
-use crate::{ffi, DecodePosition, VertexDataAdapter};
-use std::mem;
+use crate::{
+    ffi, utilities::check_indices, DecodePosition, Index, Meshlets, PositionBuffer,
+    VertexDataAdapter,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 pub type VertexCacheStatistics = ffi::meshopt_VertexCacheStatistics;
 pub type VertexFetchStatistics = ffi::meshopt_VertexFetchStatistics;
@@ -7,13 +10,17 @@ pub type OverdrawStatistics = ffi::meshopt_OverdrawStatistics;
 
 /// Returns cache hit statistics using a simplified FIFO model.
 /// Results may not match actual GPU performance.
-pub fn analyze_vertex_cache(
-    indices: &[u32],
+///
+/// Accepts both `u16` and `u32` indices, as do the other analyzers.
+pub fn analyze_vertex_cache<T: Index>(
+    indices: &[T],
     vertex_count: usize,
     cache_size: u32,
     warp_size: u32,
     prim_group_size: u32,
 ) -> VertexCacheStatistics {
+    check_indices(indices, vertex_count);
+    let indices = T::to_u32_slice(indices);
     unsafe {
         ffi::meshopt_analyzeVertexCache(
             indices.as_ptr(),
@@ -26,13 +33,215 @@ pub fn analyze_vertex_cache(
     }
 }
 
+/// Vertex cache configuration used by `analyze_vertex_cache_for`, with presets approximating
+/// common GPU hardware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VertexCacheModel {
+    /// Approximates Nvidia hardware: 32 entry cache, 32 vertex warps, 32 primitive groups.
+    Nvidia,
+    /// Approximates AMD hardware: 14 entry cache, 64 vertex warps, 128 primitive groups.
+    Amd,
+    /// Approximates Intel hardware: 128 entry FIFO cache.
+    Intel,
+    /// Explicit configuration; warp and primitive group sizes of 0 disable those limits.
+    Custom {
+        cache_size: u32,
+        warp_size: u32,
+        prim_group_size: u32,
+    },
+}
+
+impl VertexCacheModel {
+    /// Plain FIFO cache with `cache_size` entries.
+    pub fn fifo(cache_size: u32) -> Self {
+        VertexCacheModel::Custom {
+            cache_size,
+            warp_size: 0,
+            prim_group_size: 0,
+        }
+    }
+
+    /// Returns `(cache_size, warp_size, prim_group_size)` of the model.
+    pub fn parameters(self) -> (u32, u32, u32) {
+        match self {
+            VertexCacheModel::Nvidia => (32, 32, 32),
+            VertexCacheModel::Amd => (14, 64, 128),
+            VertexCacheModel::Intel => (128, 0, 0),
+            VertexCacheModel::Custom {
+                cache_size,
+                warp_size,
+                prim_group_size,
+            } => (cache_size, warp_size, prim_group_size),
+        }
+    }
+}
+
+/// Returns cache hit statistics using a simplified FIFO model configured by `model`.
+/// Results may not match actual GPU performance.
+pub fn analyze_vertex_cache_for<T: Index>(
+    indices: &[T],
+    vertex_count: usize,
+    model: VertexCacheModel,
+) -> VertexCacheStatistics {
+    let (cache_size, warp_size, prim_group_size) = model.parameters();
+    analyze_vertex_cache(
+        indices,
+        vertex_count,
+        cache_size,
+        warp_size,
+        prim_group_size,
+    )
+}
+
+/// Model of a post-transform vertex cache, used by `simulate_vertex_cache` to evaluate index
+/// orderings against hardware that `analyze_vertex_cache` doesn't model well.
+pub trait CacheSimulator {
+    /// Clears the cache before a new index buffer is simulated.
+    fn reset(&mut self);
+
+    /// Processes the vertices of the next triangle, returning how many of them had to be
+    /// transformed (0 to 3).
+    fn process_triangle(&mut self, triangle: [u32; 3]) -> u32;
+
+    /// Number of shader warps (or waves) executed since the last `reset`, if the model
+    /// tracks them.
+    fn warps_executed(&self) -> u32 {
+        0
+    }
+}
+
+/// First-in first-out vertex cache; hits don't change the order of the cached vertices.
+#[derive(Debug, Clone)]
+pub struct FifoCache {
+    capacity: usize,
+    entries: VecDeque<u32>,
+}
+
+impl FifoCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl CacheSimulator for FifoCache {
+    fn reset(&mut self) {
+        self.entries.clear();
+    }
+
+    fn process_triangle(&mut self, triangle: [u32; 3]) -> u32 {
+        let mut misses = 0;
+        for vertex in triangle {
+            if !self.entries.contains(&vertex) {
+                misses += 1;
+                if self.capacity > 0 {
+                    if self.entries.len() == self.capacity {
+                        self.entries.pop_front();
+                    }
+                    self.entries.push_back(vertex);
+                }
+            }
+        }
+        misses
+    }
+}
+
+/// Least recently used vertex cache; hits move the vertex to the front of the cache.
+#[derive(Debug, Clone)]
+pub struct LruCache {
+    capacity: usize,
+    entries: VecDeque<u32>,
+}
+
+impl LruCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl CacheSimulator for LruCache {
+    fn reset(&mut self) {
+        self.entries.clear();
+    }
+
+    fn process_triangle(&mut self, triangle: [u32; 3]) -> u32 {
+        let mut misses = 0;
+        for vertex in triangle {
+            if let Some(position) = self.entries.iter().position(|&entry| entry == vertex) {
+                self.entries.remove(position);
+            } else {
+                misses += 1;
+                if self.entries.len() == self.capacity {
+                    self.entries.pop_back();
+                }
+            }
+            if self.capacity > 0 {
+                self.entries.push_front(vertex);
+            }
+        }
+        misses
+    }
+}
+
+/// Returns cache hit statistics of `indices` as modeled by `simulator`, which is reset first.
+pub fn simulate_vertex_cache<T: Index, S: CacheSimulator + ?Sized>(
+    indices: &[T],
+    vertex_count: usize,
+    simulator: &mut S,
+) -> VertexCacheStatistics {
+    check_indices(indices, vertex_count);
+    simulator.reset();
+
+    let mut referenced = vec![false; vertex_count];
+    let mut unique_vertex_count = 0u32;
+    let mut vertices_transformed = 0u32;
+    for triangle in indices.chunks_exact(3) {
+        let triangle = [
+            triangle[0].to_u32(),
+            triangle[1].to_u32(),
+            triangle[2].to_u32(),
+        ];
+        for vertex in triangle {
+            assert!((vertex as usize) < vertex_count);
+            if !referenced[vertex as usize] {
+                referenced[vertex as usize] = true;
+                unique_vertex_count += 1;
+            }
+        }
+        vertices_transformed += simulator.process_triangle(triangle);
+    }
+
+    let triangle_count = (indices.len() / 3) as f32;
+    VertexCacheStatistics {
+        vertices_transformed,
+        warps_executed: simulator.warps_executed(),
+        acmr: if triangle_count == 0f32 {
+            0f32
+        } else {
+            vertices_transformed as f32 / triangle_count
+        },
+        atvr: if unique_vertex_count == 0 {
+            0f32
+        } else {
+            vertices_transformed as f32 / unique_vertex_count as f32
+        },
+    }
+}
+
 /// Returns cache hit statistics using a simplified direct mapped model.
 /// Results may not match actual GPU performance.
-pub fn analyze_vertex_fetch(
-    indices: &[u32],
+pub fn analyze_vertex_fetch<T: Index>(
+    indices: &[T],
     vertex_count: usize,
     vertex_size: usize,
 ) -> VertexFetchStatistics {
+    check_indices(indices, vertex_count);
+    let indices = T::to_u32_slice(indices);
     unsafe {
         ffi::meshopt_analyzeVertexFetch(indices.as_ptr(), indices.len(), vertex_count, vertex_size)
     }
@@ -40,28 +249,21 @@ pub fn analyze_vertex_fetch(
 
 /// Returns overdraw statistics using a software rasterizer.
 /// Results may not match actual GPU performance.
-pub fn analyze_overdraw_decoder<T: DecodePosition>(
-    indices: &[u32],
+pub fn analyze_overdraw_decoder<I: Index, T: DecodePosition>(
+    indices: &[I],
     vertices: &[T],
 ) -> OverdrawStatistics {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    unsafe {
-        ffi::meshopt_analyzeOverdraw(
-            indices.as_ptr(),
-            indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
-        )
-    }
+    vertices.with_positions(|positions| analyze_overdraw(indices, positions))
 }
 
 /// Returns overdraw statistics using a software rasterizer.
 /// Results may not match actual GPU performance.
-pub fn analyze_overdraw(indices: &[u32], vertices: &VertexDataAdapter<'_>) -> OverdrawStatistics {
+pub fn analyze_overdraw<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+) -> OverdrawStatistics {
+    check_indices(indices, vertices.vertex_count);
+    let indices = T::to_u32_slice(indices);
     unsafe {
         ffi::meshopt_analyzeOverdraw(
             indices.as_ptr(),
@@ -72,3 +274,405 @@ pub fn analyze_overdraw(indices: &[u32], vertices: &VertexDataAdapter<'_>) -> Ov
         )
     }
 }
+
+/// Topology problems of a mesh, as returned by `analyze_topology`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopologyStatistics {
+    /// Triangles that reference the same vertex more than once.
+    pub degenerate_triangles: usize,
+    /// Triangles with distinct vertices whose positions are collinear or coincident.
+    pub zero_area_triangles: usize,
+    /// Triangles that reference the same vertices as an earlier triangle, in any order.
+    pub duplicate_triangles: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges used by only one triangle.
+    pub border_edges: usize,
+    /// Vertices that aren't referenced by any triangle.
+    pub unreferenced_vertices: usize,
+}
+
+/// Returns counts of degenerate and duplicate triangles, non-manifold and border edges and
+/// unreferenced vertices, which affect the results of the simplifier and other algorithms.
+pub fn analyze_topology<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+) -> TopologyStatistics {
+    check_indices(indices, vertices.vertex_count);
+    let indices = T::to_u32_slice(indices);
+    let mut statistics = TopologyStatistics::default();
+
+    let mut referenced = vec![false; vertices.vertex_count];
+    let mut triangles: HashSet<[u32; 3]> = HashSet::new();
+    let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for &index in triangle {
+            referenced[index as usize] = true;
+        }
+
+        let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+        if a == b || b == c || c == a {
+            statistics.degenerate_triangles += 1;
+            continue;
+        }
+
+        let (pa, pb, pc) = (
+            vertices.position(a as usize),
+            vertices.position(b as usize),
+            vertices.position(c as usize),
+        );
+        let ab = [pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]];
+        let ac = [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]];
+        let normal = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+        if normal == [0f32; 3] {
+            statistics.zero_area_triangles += 1;
+        }
+
+        let mut key = [a, b, c];
+        key.sort_unstable();
+        if !triangles.insert(key) {
+            statistics.duplicate_triangles += 1;
+        }
+
+        for (v0, v1) in [(a, b), (b, c), (c, a)] {
+            *edges.entry((v0.min(v1), v0.max(v1))).or_default() += 1;
+        }
+    }
+
+    for &count in edges.values() {
+        match count {
+            1 => statistics.border_edges += 1,
+            2 => {}
+            _ => statistics.non_manifold_edges += 1,
+        }
+    }
+    statistics.unreferenced_vertices = referenced.iter().filter(|&&used| !used).count();
+    statistics
+}
+
+/// Configuration for `analyze_all`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzeConfig {
+    /// Size of a vertex in bytes, used for vertex fetch analysis.
+    pub vertex_size: usize,
+    /// Vertex cache models to analyze.
+    pub vertex_caches: Vec<VertexCacheModel>,
+}
+
+impl AnalyzeConfig {
+    /// Creates a configuration for vertices of `vertex_size` bytes, analyzing a plain 16 entry
+    /// FIFO cache as well as configurations approximating Nvidia, AMD and Intel hardware.
+    pub fn new(vertex_size: usize) -> Self {
+        Self {
+            vertex_size,
+            vertex_caches: vec![
+                VertexCacheModel::fifo(16),
+                VertexCacheModel::Nvidia,
+                VertexCacheModel::Amd,
+                VertexCacheModel::Intel,
+            ],
+        }
+    }
+}
+
+/// Statistics of a mesh, as returned by `analyze_all`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshReport {
+    pub index_count: usize,
+    pub vertex_count: usize,
+    /// Vertex cache statistics, one entry per `AnalyzeConfig::vertex_caches` entry.
+    pub vertex_cache: Vec<VertexCacheStatistics>,
+    pub vertex_fetch: VertexFetchStatistics,
+    pub overdraw: OverdrawStatistics,
+}
+
+impl MeshReport {
+    #[inline]
+    pub fn triangle_count(&self) -> usize {
+        self.index_count / 3
+    }
+}
+
+impl std::fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} triangles, {} vertices; ACMR",
+            self.triangle_count(),
+            self.vertex_count
+        )?;
+        for stats in &self.vertex_cache {
+            write!(f, " {:.6}", stats.acmr)?;
+        }
+        write!(
+            f,
+            " Overfetch {:.6} Overdraw {:.6} Coverage {}",
+            self.vertex_fetch.overfetch, self.overdraw.overdraw, self.overdraw.pixels_covered
+        )
+    }
+}
+
+/// Collects vertex cache, vertex fetch and overdraw statistics of a mesh in one call.
+/// Results may not match actual GPU performance.
+pub fn analyze_all<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    config: &AnalyzeConfig,
+) -> MeshReport {
+    check_indices(indices, vertices.vertex_count);
+    // convert 16-bit indices once instead of in every analyzer
+    let indices: &[u32] = &T::to_u32_slice(indices);
+    MeshReport {
+        index_count: indices.len(),
+        vertex_count: vertices.vertex_count,
+        vertex_cache: config
+            .vertex_caches
+            .iter()
+            .map(|&model| analyze_vertex_cache_for(indices, vertices.vertex_count, model))
+            .collect(),
+        vertex_fetch: analyze_vertex_fetch(indices, vertices.vertex_count, config.vertex_size),
+        overdraw: analyze_overdraw(indices, vertices),
+    }
+}
+
+/// Relative improvements from one `MeshReport` to another, as returned by `compare_reports`.
+///
+/// Lower is better for all of these statistics, so positive values are improvements and
+/// negative values are regressions, e.g. `0.25` means the value dropped by 25%.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshDelta {
+    pub triangle_count: f32,
+    pub vertex_count: f32,
+    /// One entry per vertex cache model, for the models present in both reports.
+    pub acmr: Vec<f32>,
+    /// One entry per vertex cache model, for the models present in both reports.
+    pub atvr: Vec<f32>,
+    pub overfetch: f32,
+    pub overdraw: f32,
+}
+
+impl MeshDelta {
+    /// Returns true if any statistic got worse by more than `tolerance`, e.g. `0.01` for 1%.
+    pub fn is_regression(&self, tolerance: f32) -> bool {
+        [
+            self.triangle_count,
+            self.vertex_count,
+            self.overfetch,
+            self.overdraw,
+        ]
+        .iter()
+        .chain(&self.acmr)
+        .chain(&self.atvr)
+        .any(|&delta| delta < -tolerance)
+    }
+}
+
+impl std::fmt::Display for MeshDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ACMR")?;
+        for delta in &self.acmr {
+            write!(f, " {:+.1}%", delta * 100.0)?;
+        }
+        write!(f, " ATVR")?;
+        for delta in &self.atvr {
+            write!(f, " {:+.1}%", delta * 100.0)?;
+        }
+        write!(
+            f,
+            " Overfetch {:+.1}% Overdraw {:+.1}%",
+            self.overfetch * 100.0,
+            self.overdraw * 100.0
+        )
+    }
+}
+
+fn improvement(before: f32, after: f32) -> f32 {
+    if before > 0f32 {
+        (before - after) / before
+    } else {
+        0f32
+    }
+}
+
+/// Compares the statistics of a mesh before and after optimization, e.g. to log the
+/// improvements in an asset pipeline or fail a build on regressions.
+///
+/// Both reports should have been created with the same `AnalyzeConfig`.
+pub fn compare_reports(before: &MeshReport, after: &MeshReport) -> MeshDelta {
+    let caches = before.vertex_cache.iter().zip(&after.vertex_cache);
+    MeshDelta {
+        triangle_count: improvement(
+            before.triangle_count() as f32,
+            after.triangle_count() as f32,
+        ),
+        vertex_count: improvement(before.vertex_count as f32, after.vertex_count as f32),
+        acmr: caches
+            .clone()
+            .map(|(before, after)| improvement(before.acmr, after.acmr))
+            .collect(),
+        atvr: caches
+            .map(|(before, after)| improvement(before.atvr, after.atvr))
+            .collect(),
+        overfetch: improvement(before.vertex_fetch.overfetch, after.vertex_fetch.overfetch),
+        overdraw: improvement(before.overdraw.overdraw, after.overdraw.overdraw),
+    }
+}
+
+/// Distribution of a per-meshlet fill ratio, see `MeshletStatistics`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FillStatistics {
+    pub average: f32,
+    pub min: f32,
+    /// 10th percentile; 10% of the meshlets are filled less than this.
+    pub p10: f32,
+    pub median: f32,
+}
+
+impl FillStatistics {
+    fn new(mut fills: Vec<f32>) -> Self {
+        if fills.is_empty() {
+            return Self::default();
+        }
+        fills.sort_unstable_by(f32::total_cmp);
+        let percentile = |p: usize| fills[(fills.len() - 1) * p / 100];
+        Self {
+            average: fills.iter().sum::<f32>() / fills.len() as f32,
+            min: fills[0],
+            p10: percentile(10),
+            median: percentile(50),
+        }
+    }
+}
+
+/// Occupancy statistics of a set of meshlets, as returned by `analyze_meshlets`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshletStatistics {
+    pub meshlet_count: usize,
+    /// Fill ratio of the meshlet vertices, relative to `max_vertices`.
+    pub vertex_fill: FillStatistics,
+    /// Fill ratio of the meshlet triangles, relative to `max_triangles`.
+    pub triangle_fill: FillStatistics,
+    /// Number of meshlets using less than half of `max_vertices` and less than half of `max_triangles`.
+    pub under_filled: usize,
+    /// Average span between the smallest and largest micro-index of a triangle, relative to the
+    /// meshlet vertex count; lower values mean better locality.
+    pub triangle_span: f32,
+}
+
+/// Computes occupancy statistics of meshlets built with the given `max_vertices` and
+/// `max_triangles` limits, which helps tuning these limits for a given mesh.
+pub fn analyze_meshlets(
+    meshlets: &Meshlets,
+    max_vertices: usize,
+    max_triangles: usize,
+) -> MeshletStatistics {
+    let mut vertex_fills: Vec<f32> = Vec::with_capacity(meshlets.len());
+    let mut triangle_fills: Vec<f32> = Vec::with_capacity(meshlets.len());
+    let mut under_filled = 0;
+    let mut span_sum = 0f64;
+    let mut triangle_count = 0usize;
+
+    for meshlet in meshlets.iter() {
+        let vertex_fill = meshlet.vertices.len() as f32 / max_vertices as f32;
+        let triangle_fill = (meshlet.triangles.len() / 3) as f32 / max_triangles as f32;
+        if vertex_fill < 0.5 && triangle_fill < 0.5 {
+            under_filled += 1;
+        }
+        vertex_fills.push(vertex_fill);
+        triangle_fills.push(triangle_fill);
+
+        for triangle in meshlet.triangles.chunks_exact(3) {
+            let min = triangle.iter().min().copied().unwrap_or(0);
+            let max = triangle.iter().max().copied().unwrap_or(0);
+            span_sum += f64::from(max - min) / meshlet.vertices.len() as f64;
+            triangle_count += 1;
+        }
+    }
+
+    MeshletStatistics {
+        meshlet_count: meshlets.len(),
+        vertex_fill: FillStatistics::new(vertex_fills),
+        triangle_fill: FillStatistics::new(triangle_fills),
+        under_filled,
+        triangle_span: if triangle_count == 0 {
+            0f32
+        } else {
+            (span_sum / triangle_count as f64) as f32
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analyze_all, analyze_overdraw, analyze_vertex_cache, analyze_vertex_cache_for,
+        analyze_vertex_fetch, simulate_vertex_cache, AnalyzeConfig, FifoCache, LruCache,
+        VertexDataAdapter,
+    };
+
+    // three triangles sharing vertex 0, which a 3 entry FIFO cache evicts before the last one
+    const FAN: [u32; 9] = [0, 1, 2, 0, 3, 4, 0, 5, 6];
+
+    #[test]
+    fn test_simulate_vertex_cache() {
+        let lru = simulate_vertex_cache(&FAN, 7, &mut LruCache::new(3));
+        assert_eq!(lru.vertices_transformed, 7);
+        let fan: Vec<u16> = FAN.iter().map(|&index| index as u16).collect();
+        let fifo = simulate_vertex_cache(&fan, 7, &mut FifoCache::new(3));
+        assert_eq!(fifo.vertices_transformed, 8);
+        assert_eq!(fifo.acmr, 8f32 / 3f32);
+        assert_eq!(fifo.atvr, 8f32 / 7f32);
+
+        let native = analyze_vertex_cache(&FAN, 7, 3, 0, 0);
+        assert_eq!(fifo.vertices_transformed, native.vertices_transformed);
+        assert_eq!(fifo.acmr, native.acmr);
+        assert_eq!(fifo.atvr, native.atvr);
+    }
+
+    #[test]
+    fn test_analyze_all() {
+        let mut positions = Vec::new();
+        for z in [0f32, 1f32] {
+            positions.extend_from_slice(&[
+                [0f32, 0f32, z],
+                [1f32, 0f32, z],
+                [1f32, 1f32, z],
+                [0f32, 1f32, z],
+            ]);
+        }
+        let indices = [0u32, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+        let vertices = VertexDataAdapter::from_slice(&positions, 0).unwrap();
+        let config = AnalyzeConfig::new(12);
+        let report = analyze_all(&indices, &vertices, &config);
+        assert_eq!(report.triangle_count(), 4);
+        assert_eq!(report.vertex_count, 8);
+
+        assert_eq!(report.vertex_cache.len(), config.vertex_caches.len());
+        for (statistics, &model) in report.vertex_cache.iter().zip(&config.vertex_caches) {
+            let native = analyze_vertex_cache_for(&indices, 8, model);
+            // every cache model fits the whole mesh
+            assert_eq!(statistics.vertices_transformed, 8);
+            assert_eq!(statistics.vertices_transformed, native.vertices_transformed);
+            assert_eq!(statistics.acmr, native.acmr);
+        }
+
+        let native = analyze_vertex_fetch(&indices, 8, 12);
+        assert_eq!(report.vertex_fetch.bytes_fetched, native.bytes_fetched);
+        assert_eq!(report.vertex_fetch.overfetch, native.overfetch);
+
+        let native = analyze_overdraw(&indices, &vertices);
+        assert_eq!(report.overdraw.pixels_covered, native.pixels_covered);
+        assert_eq!(report.overdraw.pixels_shaded, native.pixels_shaded);
+        // the nearer square is drawn last, so every covered pixel is shaded twice
+        assert_eq!(native.pixels_shaded, native.pixels_covered * 2);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    encode_index_buffer, encode_vertex_buffer_raw, ffi, optimize_overdraw_in_place,
+    optimize_vertex_cache_in_place, simplify_in_place, typed_to_bytes, validate_indices, Error,
+    Result, SimplifyOptions, VertexDataAdapter,
+};
+use rayon::prelude::*;
+use std::mem;
+
+/// A mesh processed by `process_batch`, owning its indices and interleaved vertex data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchMesh {
+    pub indices: Vec<u32>,
+    /// Interleaved vertex data, `vertex_stride` bytes per vertex.
+    pub vertices: Vec<u8>,
+    pub vertex_stride: usize,
+    /// Offset of the position (3 x `f32`) within a vertex, in bytes.
+    pub position_offset: usize,
+}
+
+impl BatchMesh {
+    pub fn new(
+        indices: Vec<u32>,
+        vertices: Vec<u8>,
+        vertex_stride: usize,
+        position_offset: usize,
+    ) -> Self {
+        Self {
+            indices,
+            vertices,
+            vertex_stride,
+            position_offset,
+        }
+    }
+
+    /// Creates a mesh from vertices of type `T`, with the position located at byte
+    /// `position_offset` within `T`.
+    pub fn from_vertices<T>(indices: Vec<u32>, vertices: &[T], position_offset: usize) -> Self {
+        Self::new(
+            indices,
+            typed_to_bytes(vertices).to_vec(),
+            mem::size_of::<T>(),
+            position_offset,
+        )
+    }
+
+    /// Number of vertices in the mesh.
+    pub fn vertex_count(&self) -> usize {
+        self.vertices
+            .len()
+            .checked_div(self.vertex_stride)
+            .unwrap_or(0)
+    }
+
+    /// Returns an adapter reading the positions of the mesh.
+    pub fn adapter(&self) -> Result<VertexDataAdapter<'_>> {
+        VertexDataAdapter::new(&self.vertices, self.vertex_stride, self.position_offset)
+    }
+}
+
+/// A single step of a `BatchPipeline`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchStep {
+    Simplify {
+        target_ratio: f32,
+        target_error: f32,
+        options: SimplifyOptions,
+    },
+    OptimizeVertexCache,
+    OptimizeOverdraw {
+        threshold: f32,
+    },
+    OptimizeVertexFetch,
+}
+
+/// Sequence of operations applied to every mesh by `process_batch`, in the order they
+/// were added.
+///
+/// A typical pipeline simplifies (optionally), then optimizes for the vertex cache, overdraw
+/// and vertex fetch, and finally encodes the result:
+///
+/// ```ignore
+/// let pipeline = BatchPipeline::new()
+///     .optimize_vertex_cache()
+///     .optimize_overdraw(1.05)
+///     .optimize_vertex_fetch()
+///     .encode(true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchPipeline {
+    steps: Vec<BatchStep>,
+    encode: bool,
+}
+
+impl BatchPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simplifies the mesh to `target_ratio` of its triangles, see `simplify`.
+    pub fn simplify(
+        mut self,
+        target_ratio: f32,
+        target_error: f32,
+        options: SimplifyOptions,
+    ) -> Self {
+        self.steps.push(BatchStep::Simplify {
+            target_ratio,
+            target_error,
+            options,
+        });
+        self
+    }
+
+    /// Reorders the indices for the vertex cache, see `optimize_vertex_cache`.
+    pub fn optimize_vertex_cache(mut self) -> Self {
+        self.steps.push(BatchStep::OptimizeVertexCache);
+        self
+    }
+
+    /// Reorders the indices to reduce overdraw, see `optimize_overdraw_in_place`.
+    pub fn optimize_overdraw(mut self, threshold: f32) -> Self {
+        self.steps.push(BatchStep::OptimizeOverdraw { threshold });
+        self
+    }
+
+    /// Reorders the vertices for vertex fetch, dropping unreferenced vertices, see
+    /// `optimize_vertex_fetch`.
+    pub fn optimize_vertex_fetch(mut self) -> Self {
+        self.steps.push(BatchStep::OptimizeVertexFetch);
+        self
+    }
+
+    /// Whether to encode the resulting index and vertex buffers with `encode_index_buffer`
+    /// and `encode_vertex_buffer_raw`.
+    pub fn encode(mut self, encode: bool) -> Self {
+        self.encode = encode;
+        self
+    }
+
+    /// Runs the pipeline on a single mesh.
+    pub fn run(&self, mut mesh: BatchMesh) -> Result<BatchOutput> {
+        if mesh.vertex_stride == 0 {
+            return Err(Error::Config("vertex_stride must not be 0".into()));
+        }
+        mesh.adapter()?;
+        validate_indices(&mesh.indices, mesh.vertex_count())?;
+
+        for step in &self.steps {
+            match *step {
+                BatchStep::Simplify {
+                    target_ratio,
+                    target_error,
+                    options,
+                } => {
+                    let target_count =
+                        (mesh.indices.len() as f32 / 3.0 * target_ratio) as usize * 3;
+                    // borrows the vertices only, so the indices can be modified in place
+                    let adapter = VertexDataAdapter::new(
+                        &mesh.vertices,
+                        mesh.vertex_stride,
+                        mesh.position_offset,
+                    )?;
+                    simplify_in_place(
+                        &mut mesh.indices,
+                        &adapter,
+                        target_count,
+                        target_error,
+                        options,
+                        None,
+                    );
+                }
+                BatchStep::OptimizeVertexCache => {
+                    let vertex_count = mesh.vertex_count();
+                    optimize_vertex_cache_in_place(&mut mesh.indices, vertex_count);
+                }
+                BatchStep::OptimizeOverdraw { threshold } => {
+                    let adapter = VertexDataAdapter::new(
+                        &mesh.vertices,
+                        mesh.vertex_stride,
+                        mesh.position_offset,
+                    )?;
+                    optimize_overdraw_in_place(&mut mesh.indices, &adapter, threshold);
+                }
+                BatchStep::OptimizeVertexFetch => optimize_vertex_fetch_raw(&mut mesh),
+            }
+        }
+
+        let encoded = if self.encode {
+            Some(EncodedMesh {
+                indices: encode_index_buffer(&mesh.indices, mesh.vertex_count())?,
+                vertices: encode_vertex_buffer_raw(
+                    &mesh.vertices,
+                    mesh.vertex_count(),
+                    mesh.vertex_stride,
+                )?,
+            })
+        } else {
+            None
+        };
+        Ok(BatchOutput { mesh, encoded })
+    }
+}
+
+/// Index and vertex buffers encoded by a `BatchPipeline`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedMesh {
+    pub indices: Vec<u8>,
+    pub vertices: Vec<u8>,
+}
+
+/// Result of running a `BatchPipeline` on a mesh.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchOutput {
+    /// The processed mesh.
+    pub mesh: BatchMesh,
+    /// The encoded buffers, if the pipeline encodes its output.
+    pub encoded: Option<EncodedMesh>,
+}
+
+fn optimize_vertex_fetch_raw(mesh: &mut BatchMesh) {
+    let vertex_count = mesh.vertex_count();
+    let mut result: Vec<u8> = vec![0; mesh.vertices.len()];
+    let next_vertex = unsafe {
+        ffi::meshopt_optimizeVertexFetch(
+            result.as_mut_ptr().cast(),
+            mesh.indices.as_mut_ptr(),
+            mesh.indices.len(),
+            mesh.vertices.as_ptr().cast(),
+            vertex_count,
+            mesh.vertex_stride,
+        )
+    };
+    result.truncate(next_vertex * mesh.vertex_stride);
+    mesh.vertices = result;
+}
+
+/// Runs `pipeline` on every mesh on the rayon thread pool, returning the results in the
+/// order of `meshes`.
+///
+/// Meshes are processed independently, so an error only affects the result of its mesh.
+pub fn process_batch(meshes: Vec<BatchMesh>, pipeline: &BatchPipeline) -> Vec<Result<BatchOutput>> {
+    process_batch_with(meshes, |mesh| pipeline.run(mesh))
+}
+
+/// Runs `process` on every item (e.g. a mesh or submesh in a user-defined format) on the
+/// rayon thread pool, returning the results in the order of `items`.
+pub fn process_batch_with<T, R, F>(items: Vec<T>, process: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    items.into_par_iter().map(process).collect()
+}
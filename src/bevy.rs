@@ -0,0 +1,182 @@
+//! Conversions between Bevy meshes (`bevy_mesh::Mesh`, re-exported as
+//! `bevy::render::mesh::Mesh`) and the index and attribute buffers of this crate.
+
+use crate::{
+    generate_vertex_remap_multi, optimize_overdraw_in_place, optimize_vertex_cache_in_place,
+    optimize_vertex_fetch_remap, remap_index_buffer, remap_vertex_buffer, typed_to_bytes, Error,
+    OptimizeConfig, Result, Vertex, VertexDataAdapter, VertexStream,
+};
+use bevy_mesh::{Indices, Mesh, PrimitiveTopology, VertexAttributeValues};
+use std::mem;
+
+/// Returns the indices of a triangle list mesh as `u32` values, generating them if the mesh
+/// isn't indexed.
+pub fn mesh_indices(mesh: &Mesh) -> Result<Vec<u32>> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Err(Error::Config(format!(
+            "mesh topology must be a triangle list, got {:?}",
+            mesh.primitive_topology()
+        )));
+    }
+
+    let vertex_count = mesh_vertex_count(mesh)?;
+    let indices: Vec<u32> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|&index| u32::from(index)).collect(),
+        Some(Indices::U32(indices)) => indices.clone(),
+        None => (0..vertex_count as u32).collect(),
+    };
+    if !indices.len().is_multiple_of(3) {
+        return Err(Error::Parse(format!(
+            "index count ({}) must be a multiple of 3",
+            indices.len()
+        )));
+    }
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(Error::Parse(format!(
+            "index {} is out of range for {} vertices",
+            index, vertex_count
+        )));
+    }
+    Ok(indices)
+}
+
+/// Sets the indices of `mesh`, storing them as `u16` values if they all fit.
+pub fn set_mesh_indices(mesh: &mut Mesh, indices: &[u32]) {
+    let indices = if indices.iter().all(|&index| u16::try_from(index).is_ok()) {
+        Indices::U16(indices.iter().map(|&index| index as u16).collect())
+    } else {
+        Indices::U32(indices.to_vec())
+    };
+    mesh.insert_indices(indices);
+}
+
+/// Returns the number of vertices of `mesh`, failing if its attributes have different
+/// lengths.
+pub fn mesh_vertex_count(mesh: &Mesh) -> Result<usize> {
+    let mut vertex_count = None;
+    for (attribute, values) in mesh.attributes() {
+        match vertex_count {
+            None => vertex_count = Some(values.len()),
+            Some(count) if count != values.len() => {
+                return Err(Error::Parse(format!(
+                    "attribute {} has {} values, expected {}",
+                    attribute.name,
+                    values.len(),
+                    count
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(vertex_count.unwrap_or(0))
+}
+
+/// Returns the positions of `mesh`, which must be stored as `Float32x3`.
+pub fn mesh_positions(mesh: &Mesh) -> Result<&[[f32; 3]]> {
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => Ok(positions),
+        Some(_) => Err(Error::Config("mesh positions must be Float32x3".into())),
+        None => Err(Error::Config("mesh has no positions".into())),
+    }
+}
+
+/// Returns an adapter reading the positions of `mesh`, for the simplify, optimize and
+/// clusterize functions.
+pub fn mesh_positions_adapter(mesh: &Mesh) -> Result<VertexDataAdapter<'_>> {
+    VertexDataAdapter::new(
+        typed_to_bytes(mesh_positions(mesh)?),
+        mem::size_of::<[f32; 3]>(),
+        0,
+    )
+}
+
+/// Returns a stream for every attribute of `mesh`, e.g. for `generate_vertex_remap_multi`
+/// or `generate_shadow_index_buffer_multi`.
+pub fn mesh_attribute_streams(mesh: &Mesh) -> Vec<VertexStream<'_>> {
+    mesh.attributes()
+        .map(|(_, values)| {
+            let bytes = values.get_bytes();
+            let size = (bytes.len() / values.len().max(1)).max(1);
+            VertexStream::from_bytes(bytes, size, size)
+        })
+        .collect()
+}
+
+/// Applies a remap table to every attribute of `mesh`, leaving `vertex_count` vertices, see
+/// `remap_vertex_buffer`.
+pub fn remap_mesh_attributes(mesh: &mut Mesh, remap: &[u32], vertex_count: usize) {
+    for (_, values) in mesh.attributes_mut() {
+        remap_attribute_values(values, remap, vertex_count);
+    }
+}
+
+/// Inserts the positions, normals and texture coordinates of `vertices` as the
+/// `ATTRIBUTE_POSITION`, `ATTRIBUTE_NORMAL` and `ATTRIBUTE_UV_0` attributes of `mesh`.
+pub fn insert_mesh_vertices(mesh: &mut Mesh, vertices: &[Vertex]) {
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vertices.iter().map(|vertex| vertex.p).collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vertices.iter().map(|vertex| vertex.n).collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vertices.iter().map(|vertex| vertex.t).collect::<Vec<_>>(),
+    );
+}
+
+/// Runs the complete optimization pipeline on a triangle list mesh: removes duplicate and
+/// unreferenced vertices (comparing all attributes), then optimizes for vertex cache,
+/// overdraw and vertex fetch, like `optimize_complete`.
+///
+/// Indices are stored as `u16` values if they all fit, see `set_mesh_indices`.
+pub fn optimize_bevy_mesh(mesh: &mut Mesh, config: &OptimizeConfig) -> Result<()> {
+    let indices = mesh_indices(mesh)?;
+    let vertex_count = mesh_vertex_count(mesh)?;
+    // fail before modifying the mesh
+    mesh_positions(mesh)?;
+
+    let (unique_count, remap) =
+        generate_vertex_remap_multi(vertex_count, &mesh_attribute_streams(mesh), Some(&indices));
+    let mut indices = remap_index_buffer(Some(&indices), vertex_count, &remap);
+    remap_mesh_attributes(mesh, &remap, unique_count);
+
+    // vertex cache optimization should go first as it provides starting order for overdraw
+    optimize_vertex_cache_in_place(&mut indices, unique_count);
+    optimize_overdraw_in_place(
+        &mut indices,
+        &mesh_positions_adapter(mesh)?,
+        config.overdraw_threshold,
+    );
+
+    // vertex fetch optimization should go last as it depends on the final index order
+    let remap = optimize_vertex_fetch_remap(&indices, unique_count);
+    let indices = remap_index_buffer(Some(&indices), unique_count, &remap);
+    remap_mesh_attributes(mesh, &remap, unique_count);
+
+    set_mesh_indices(mesh, &indices);
+    Ok(())
+}
+
+fn remap_attribute_values(values: &mut VertexAttributeValues, remap: &[u32], vertex_count: usize) {
+    macro_rules! remap_variants {
+        ($($variant:ident),*) => {
+            match values {
+                $(VertexAttributeValues::$variant(values) => {
+                    *values = remap_vertex_buffer(values, vertex_count, remap);
+                })*
+            }
+        };
+    }
+    remap_variants!(
+        Float32, Sint32, Uint32, Float32x2, Sint32x2, Uint32x2, Float32x3, Sint32x3, Uint32x3,
+        Float32x4, Sint32x4, Uint32x4, Sint16x2, Snorm16x2, Uint16x2, Unorm16x2, Sint16x4,
+        Snorm16x4, Uint16x4, Unorm16x4, Sint8x2, Snorm8x2, Uint8x2, Unorm8x2, Sint8x4, Snorm8x4,
+        Uint8x4, Unorm8x4
+    );
+}
@@ -1,21 +1,171 @@
 use crate::ffi;
-use crate::{DecodePosition, VertexDataAdapter};
+use crate::utilities::{check_indices, try_reserve};
+use crate::{DecodePosition, Error, Index, PositionBuffer, Result, VertexDataAdapter};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::HashMap;
 
 pub type Bounds = ffi::meshopt_Bounds;
 
+/// Bounding volumes of a cluster that can be used for frustum, backface and occlusion culling.
+///
+/// This is a plain Rust version of `Bounds` with culling helpers; see `compute_cluster_bounds`
+/// for the formulas they implement.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct ClusterBounds {
+    /// Center of the bounding sphere.
+    pub center: [f32; 3],
+    /// Radius of the bounding sphere.
+    pub radius: f32,
+    /// Apex of the normal cone.
+    pub cone_apex: [f32; 3],
+    /// Axis of the normal cone.
+    pub cone_axis: [f32; 3],
+    /// Cutoff of the normal cone, `cos(angle/2)`.
+    pub cone_cutoff: f32,
+    /// Axis of the normal cone, quantized to 8-bit snorm.
+    pub cone_axis_s8: [i8; 3],
+    /// Cutoff of the normal cone, quantized to 8-bit snorm.
+    pub cone_cutoff_s8: i8,
+}
+
+impl From<Bounds> for ClusterBounds {
+    fn from(bounds: Bounds) -> Self {
+        Self {
+            center: bounds.center,
+            radius: bounds.radius,
+            cone_apex: bounds.cone_apex,
+            cone_axis: bounds.cone_axis,
+            cone_cutoff: bounds.cone_cutoff,
+            cone_axis_s8: bounds.cone_axis_s8,
+            cone_cutoff_s8: bounds.cone_cutoff_s8,
+        }
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+impl ClusterBounds {
+    /// Returns `true` if the normal cone is too wide for the cluster to ever be backface culled.
+    pub fn is_cone_degenerate(&self) -> bool {
+        self.cone_cutoff >= 1f32
+    }
+
+    /// Returns `true` if the quantized normal cone is too wide for the cluster to ever be backface culled.
+    pub fn is_cone_degenerate_s8(&self) -> bool {
+        self.cone_cutoff_s8 == 127
+    }
+
+    fn cone_axis_s8_f32(&self) -> [f32; 3] {
+        self.cone_axis_s8.map(|v| f32::from(v) / 127f32)
+    }
+
+    fn cone_cutoff_s8_f32(&self) -> f32 {
+        f32::from(self.cone_cutoff_s8) / 127f32
+    }
+
+    /// Backface culling for orthographic projection, where `view` is the view direction:
+    /// `dot(view, cone_axis) >= cone_cutoff`.
+    pub fn is_backface_culled_orthographic(&self, view: [f32; 3]) -> bool {
+        dot(view, self.cone_axis) >= self.cone_cutoff
+    }
+
+    /// Backface culling for perspective projection using the cone apex:
+    /// `dot(normalize(cone_apex - camera_position), cone_axis) >= cone_cutoff`.
+    pub fn is_backface_culled(&self, camera_position: [f32; 3]) -> bool {
+        let view = sub(self.cone_apex, camera_position);
+        dot(view, self.cone_axis) >= self.cone_cutoff * dot(view, view).sqrt()
+    }
+
+    /// Same as `is_backface_culled`, using the quantized cone axis and cutoff.
+    pub fn is_backface_culled_s8(&self, camera_position: [f32; 3]) -> bool {
+        let view = sub(self.cone_apex, camera_position);
+        dot(view, self.cone_axis_s8_f32()) >= self.cone_cutoff_s8_f32() * dot(view, view).sqrt()
+    }
+
+    /// Backface culling for perspective projection using the bounding sphere instead of the apex:
+    /// `dot(center - camera_position, cone_axis) >= cone_cutoff * length(center - camera_position) + radius`.
+    pub fn is_backface_culled_sphere(&self, camera_position: [f32; 3]) -> bool {
+        let view = sub(self.center, camera_position);
+        dot(view, self.cone_axis) >= self.cone_cutoff * dot(view, view).sqrt() + self.radius
+    }
+
+    /// Same as `is_backface_culled_sphere`, using the quantized cone axis and cutoff.
+    pub fn is_backface_culled_sphere_s8(&self, camera_position: [f32; 3]) -> bool {
+        let view = sub(self.center, camera_position);
+        dot(view, self.cone_axis_s8_f32())
+            >= self.cone_cutoff_s8_f32() * dot(view, view).sqrt() + self.radius
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Meshlet<'data> {
     pub vertices: &'data [u32],
     pub triangles: &'data [u8],
 }
 
+impl Meshlet<'_> {
+    /// Expands the micro-indices of the meshlet into regular triangle indices referencing
+    /// the original vertex buffer.
+    pub fn global_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        self.triangles
+            .iter()
+            .map(move |&index| self.vertices[index as usize])
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "crate::serde_impls::MeshletsParts")
+)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(bytecheck(verify))
+)]
 pub struct Meshlets {
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Map<MeshletDef>))]
     pub meshlets: Vec<ffi::meshopt_Meshlet>,
     pub vertices: Vec<u32>,
     pub triangles: Vec<u8>,
 }
 
+/// `rkyv` definition of `ffi::meshopt_Meshlet`, archived as `ArchivedMeshlet`.
+#[cfg(feature = "rkyv")]
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[rkyv(remote = ffi::meshopt_Meshlet, archived = ArchivedMeshlet)]
+pub struct MeshletDef {
+    pub vertex_offset: u32,
+    pub triangle_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+}
+
 impl Meshlets {
+    /// Accesses meshlets archived with `rkyv` in place, e.g. in a memory mapped file,
+    /// without deserializing them.
+    ///
+    /// Besides the layout of the archive, the ranges of every meshlet are validated like
+    /// by `validate`, so that indexing the archived buffers with them can't panic.
+    #[cfg(feature = "rkyv")]
+    pub fn access_archived(bytes: &[u8]) -> Result<&ArchivedMeshlets> {
+        rkyv::access::<ArchivedMeshlets, rkyv::rancor::Error>(bytes)
+            .map_err(|err| Error::Parse(err.to_string()))
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.meshlets.len()
@@ -25,6 +175,40 @@ impl Meshlets {
         self.meshlets.is_empty()
     }
 
+    /// Creates meshlets from buffers produced elsewhere, e.g. deserialized from a file,
+    /// checking them with `validate` so that the accessors can't panic.
+    pub fn from_parts(
+        meshlets: Vec<ffi::meshopt_Meshlet>,
+        vertices: Vec<u32>,
+        triangles: Vec<u8>,
+    ) -> Result<Self> {
+        let meshlets = Self {
+            meshlets,
+            vertices,
+            triangles,
+        };
+        meshlets.validate()?;
+        Ok(meshlets)
+    }
+
+    /// Checks that the ranges of every meshlet lie within the vertex and micro-index
+    /// buffers, and that its micro-indices reference its own vertices.
+    pub fn validate(&self) -> Result<()> {
+        for (idx, meshlet) in self.meshlets.iter().enumerate() {
+            let valid = matches!(
+                self.checked_meshlet_from_ffi(meshlet),
+                Some(m) if m.triangles.iter().all(|&index| (index as usize) < m.vertices.len())
+            );
+            if !valid {
+                return Err(Error::Parse(format!(
+                    "meshlet {} references vertices or micro-indices out of range",
+                    idx
+                )));
+            }
+        }
+        Ok(())
+    }
+
     fn meshlet_from_ffi(&self, meshlet: &ffi::meshopt_Meshlet) -> Meshlet<'_> {
         Meshlet {
             vertices: &self.vertices[meshlet.vertex_offset as usize
@@ -34,16 +218,228 @@ impl Meshlets {
         }
     }
 
+    fn checked_meshlet_from_ffi(&self, meshlet: &ffi::meshopt_Meshlet) -> Option<Meshlet<'_>> {
+        let vertex_offset = meshlet.vertex_offset as usize;
+        let triangle_offset = meshlet.triangle_offset as usize;
+        Some(Meshlet {
+            vertices: self
+                .vertices
+                .get(vertex_offset..vertex_offset.checked_add(meshlet.vertex_count as usize)?)?,
+            triangles: self.triangles.get(
+                triangle_offset
+                    ..triangle_offset
+                        .checked_add((meshlet.triangle_count as usize).checked_mul(3)?)?,
+            )?,
+        })
+    }
+
     #[inline]
     pub fn get(&self, idx: usize) -> Meshlet<'_> {
         self.meshlet_from_ffi(&self.meshlets[idx])
     }
 
+    /// Returns the meshlet at `idx`, or `None` if `idx` is out of range or the meshlet
+    /// references data outside of the vertex and micro-index buffers.
+    #[inline]
+    pub fn try_get(&self, idx: usize) -> Option<Meshlet<'_>> {
+        self.checked_meshlet_from_ffi(self.meshlets.get(idx)?)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = Meshlet<'_>> {
         self.meshlets
             .iter()
             .map(|meshlet| self.meshlet_from_ffi(meshlet))
     }
+
+    /// Reorders the meshlets along a space-filling curve through their centers, which improves
+    /// cache locality and culling coherence when meshlets are processed in order.
+    ///
+    /// The vertex and micro-index buffers are repacked to match the new order. Returns the
+    /// previous index of each meshlet in the new order, which can be used to reorder
+    /// per-meshlet data such as bounds.
+    pub fn sort_spatially(&mut self, vertices: &VertexDataAdapter<'_>) -> Vec<u32> {
+        let centers: Vec<[f32; 3]> = self
+            .iter()
+            .map(|meshlet| aabb_center(&compute_meshlet_aabb(meshlet, vertices)))
+            .collect();
+        self.sort_by_centers(&centers)
+    }
+
+    /// Reorders the meshlets along a space-filling curve through their centers, which improves
+    /// cache locality and culling coherence when meshlets are processed in order.
+    ///
+    /// The vertex and micro-index buffers are repacked to match the new order. Returns the
+    /// previous index of each meshlet in the new order, which can be used to reorder
+    /// per-meshlet data such as bounds.
+    pub fn sort_spatially_decoder<T: DecodePosition>(&mut self, vertices: &[T]) -> Vec<u32> {
+        let centers: Vec<[f32; 3]> = self
+            .iter()
+            .map(|meshlet| aabb_center(&compute_meshlet_aabb_decoder(meshlet, vertices)))
+            .collect();
+        self.sort_by_centers(&centers)
+    }
+
+    fn sort_by_centers(&mut self, centers: &[[f32; 3]]) -> Vec<u32> {
+        let mut remap: Vec<u32> = vec![0; centers.len()];
+        unsafe {
+            ffi::meshopt_spatialSortRemap(
+                remap.as_mut_ptr(),
+                centers.as_ptr().cast(),
+                centers.len(),
+                std::mem::size_of::<f32>() * 3,
+            );
+        }
+        let mut order: Vec<u32> = vec![0; centers.len()];
+        for (meshlet, &position) in remap.iter().enumerate() {
+            order[position as usize] = meshlet as u32;
+        }
+
+        let mut meshlets: Vec<ffi::meshopt_Meshlet> = Vec::with_capacity(self.meshlets.len());
+        let mut meshlet_vertices: Vec<u32> = Vec::with_capacity(self.vertices.len());
+        let mut meshlet_triangles: Vec<u8> = Vec::with_capacity(self.triangles.len());
+        for &meshlet in &order {
+            let source = self.meshlets[meshlet as usize];
+            let data = self.meshlet_from_ffi(&source);
+            meshlets.push(ffi::meshopt_Meshlet {
+                vertex_offset: meshlet_vertices.len() as u32,
+                triangle_offset: meshlet_triangles.len() as u32,
+                ..source
+            });
+            meshlet_vertices.extend_from_slice(data.vertices);
+            meshlet_triangles.extend_from_slice(data.triangles);
+            // keep micro-index offsets 4-byte aligned
+            meshlet_triangles.resize((meshlet_triangles.len() + 3) & !3, 0);
+        }
+
+        self.meshlets = meshlets;
+        self.vertices = meshlet_vertices;
+        self.triangles = meshlet_triangles;
+        order
+    }
+
+    /// Expands all meshlets into a single index buffer referencing the original vertex buffer,
+    /// e.g. for draw paths without mesh shader support.
+    pub fn to_index_buffer(&self) -> Vec<u32> {
+        let index_count = self
+            .meshlets
+            .iter()
+            .map(|meshlet| meshlet.triangle_count as usize * 3)
+            .sum();
+        let mut indices: Vec<u32> = Vec::with_capacity(index_count);
+        for meshlet in self.iter() {
+            indices.extend(meshlet.global_indices());
+        }
+        indices
+    }
+
+    /// Packs the meshlets into flat, GPU-ready buffers, see `GpuMeshlets`.
+    pub fn pack_for_gpu(&self) -> GpuMeshlets {
+        let mut descriptors: Vec<GpuMeshlet> = Vec::with_capacity(self.meshlets.len());
+        let mut vertices: Vec<u32> = Vec::with_capacity(self.vertices.len());
+        let mut triangles: Vec<u32> =
+            Vec::with_capacity(self.triangles.len() / 4 + self.meshlets.len());
+        for meshlet in self.iter() {
+            descriptors.push(GpuMeshlet {
+                vertex_offset: vertices.len() as u32,
+                triangle_offset: triangles.len() as u32,
+                vertex_count: meshlet.vertices.len() as u32,
+                triangle_count: (meshlet.triangles.len() / 3) as u32,
+            });
+            vertices.extend_from_slice(meshlet.vertices);
+            triangles.extend(pack_micro_indices(meshlet.triangles));
+        }
+        GpuMeshlets {
+            meshlets: descriptors,
+            vertices,
+            triangles,
+        }
+    }
+}
+
+/// Packs micro-indices four to a little-endian word, padding the last word with zeros.
+fn pack_micro_indices(triangles: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    triangles.chunks(4).map(|chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        u32::from_le_bytes(word)
+    })
+}
+
+#[cfg(feature = "rayon")]
+impl Meshlets {
+    /// Returns a parallel iterator over the meshlets, e.g. to compute per-meshlet culling
+    /// data with `compute_meshlet_bounds` on all threads.
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = Meshlet<'_>> + '_ {
+        self.meshlets
+            .par_iter()
+            .map(move |meshlet| self.meshlet_from_ffi(meshlet))
+    }
+
+    /// Same as `pack_for_gpu`, but packs the micro-indices of the meshlets in parallel.
+    pub fn par_pack_for_gpu(&self) -> GpuMeshlets {
+        let words: Vec<Vec<u32>> = self
+            .par_iter()
+            .map(|meshlet| pack_micro_indices(meshlet.triangles).collect())
+            .collect();
+
+        let mut descriptors: Vec<GpuMeshlet> = Vec::with_capacity(self.meshlets.len());
+        let mut vertices: Vec<u32> = Vec::with_capacity(self.vertices.len());
+        let mut triangles: Vec<u32> = Vec::with_capacity(words.iter().map(Vec::len).sum());
+        for (meshlet, words) in self.iter().zip(&words) {
+            descriptors.push(GpuMeshlet {
+                vertex_offset: vertices.len() as u32,
+                triangle_offset: triangles.len() as u32,
+                vertex_count: meshlet.vertices.len() as u32,
+                triangle_count: (meshlet.triangles.len() / 3) as u32,
+            });
+            vertices.extend_from_slice(meshlet.vertices);
+            triangles.extend_from_slice(words);
+        }
+        GpuMeshlets {
+            meshlets: descriptors,
+            vertices,
+            triangles,
+        }
+    }
+}
+
+/// Meshlet descriptor with a layout suitable for uploading to GPU buffers (std430 compatible).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct GpuMeshlet {
+    /// Offset of the first vertex in `GpuMeshlets::vertices`.
+    pub vertex_offset: u32,
+    /// Offset of the first micro-index word in `GpuMeshlets::triangles`; always 4-byte aligned.
+    pub triangle_offset: u32,
+    /// Number of vertices of the meshlet.
+    pub vertex_count: u32,
+    /// Number of triangles of the meshlet.
+    pub triangle_count: u32,
+}
+
+/// Meshlets packed into flat buffers, ready to be uploaded as storage buffers for mesh
+/// shader pipelines, as produced by `Meshlets::pack_for_gpu`.
+///
+/// Micro-index `k` of a meshlet (with `k < triangle_count * 3`) is stored in byte `k % 4` of
+/// `triangles[triangle_offset + k / 4]`, i.e. `(word >> (8 * (k % 4))) & 0xff`, and indexes
+/// into `vertices[vertex_offset..vertex_offset + vertex_count]`. The micro-indices of every
+/// meshlet start on a new word.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct GpuMeshlets {
+    pub meshlets: Vec<GpuMeshlet>,
+    pub vertices: Vec<u32>,
+    pub triangles: Vec<u32>,
 }
 
 /// Splits the mesh into a set of meshlets where each meshlet has a micro index buffer
@@ -52,27 +448,199 @@ impl Meshlets {
 /// The resulting data can be used to render meshes using `NVidia programmable mesh shading`
 /// pipeline, or in other cluster-based renderers.
 ///
-/// Note: `max_vertices` must be <= 255 and `max_triangles` must be <= 512 and divisible by 4.
-pub fn build_meshlets(
-    indices: &[u32],
+/// Accepts both `u16` and `u32` indices.
+///
+/// Note: `max_vertices` must be between 3 and 256, `max_triangles` must be between 4 and 512
+/// and divisible by 4, and `cone_weight` must be between 0 and 1; other values return
+/// `Error::Config`.
+pub fn build_meshlets<T: Index>(
+    indices: &[T],
     vertices: &VertexDataAdapter<'_>,
     max_vertices: usize,
     max_triangles: usize,
     cone_weight: f32,
-) -> Meshlets {
-    let meshlet_count =
-        unsafe { ffi::meshopt_buildMeshletsBound(indices.len(), max_vertices, max_triangles) };
-    let mut meshlets: Vec<ffi::meshopt_Meshlet> =
-        vec![unsafe { ::std::mem::zeroed() }; meshlet_count];
+) -> Result<Meshlets> {
+    let mut meshlets = Meshlets::default();
+    build_meshlets_into(
+        indices,
+        vertices,
+        max_vertices,
+        max_triangles,
+        cone_weight,
+        &mut meshlets,
+    )?;
+    Ok(meshlets)
+}
 
-    let mut meshlet_verts: Vec<u32> = vec![0; meshlet_count * max_vertices];
-    let mut meshlet_tris: Vec<u8> = vec![0; meshlet_count * max_triangles * 3];
+/// Same as `build_meshlets`, but returns `Error::Memory` instead of aborting if the
+/// worst-case sized meshlet buffers can't be allocated.
+pub fn try_build_meshlets<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    max_vertices: usize,
+    max_triangles: usize,
+    cone_weight: f32,
+) -> Result<Meshlets> {
+    validate_meshlet_parameters(max_vertices, max_triangles, cone_weight)?;
+    let (meshlet_count, vertex_count, triangle_count) =
+        meshlet_buffer_sizes(indices.len(), max_vertices, max_triangles)?;
+
+    // build_meshlets_into resizes the buffers to these sizes, which doesn't reallocate
+    let mut meshlets = Meshlets::default();
+    try_reserve(&mut meshlets.meshlets, meshlet_count)?;
+    try_reserve(&mut meshlets.vertices, vertex_count)?;
+    try_reserve(&mut meshlets.triangles, triangle_count)?;
+    build_meshlets_into(
+        indices,
+        vertices,
+        max_vertices,
+        max_triangles,
+        cone_weight,
+        &mut meshlets,
+    )?;
+    Ok(meshlets)
+}
+
+/// Returns the number of meshlets, meshlet vertices and micro-indices `build_meshlets` needs
+/// room for, or an error if they don't fit in `usize` (e.g. for large meshes on 32-bit targets).
+fn meshlet_buffer_sizes(
+    index_count: usize,
+    max_vertices: usize,
+    max_triangles: usize,
+) -> Result<(usize, usize, usize)> {
+    let meshlet_count = build_meshlets_bound(index_count, max_vertices, max_triangles);
+    let vertex_count = meshlet_count.checked_mul(max_vertices);
+    let triangle_count = meshlet_count.checked_mul(max_triangles * 3);
+    match (vertex_count, triangle_count) {
+        (Some(vertex_count), Some(triangle_count)) => {
+            Ok((meshlet_count, vertex_count, triangle_count))
+        }
+        _ => Err(Error::memory_dynamic(format!(
+            "meshlet buffers for {} meshlets of up to {} vertices and {} triangles overflow usize",
+            meshlet_count, max_vertices, max_triangles
+        ))),
+    }
+}
+
+/// Checks the meshlet limits accepted by `build_meshlets`.
+fn validate_meshlet_parameters(
+    max_vertices: usize,
+    max_triangles: usize,
+    cone_weight: f32,
+) -> Result<()> {
+    if !(3..=256).contains(&max_vertices) {
+        Err(Error::Config(format!(
+            "max_vertices ({}) must be between 3 and 256",
+            max_vertices
+        )))
+    } else if !(4..=512).contains(&max_triangles) || !max_triangles.is_multiple_of(4) {
+        Err(Error::Config(format!(
+            "max_triangles ({}) must be between 4 and 512 and divisible by 4",
+            max_triangles
+        )))
+    } else if !(0f32..=1f32).contains(&cone_weight) {
+        Err(Error::Config(format!(
+            "cone_weight ({}) must be between 0 and 1",
+            cone_weight
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the maximum number of meshlets `build_meshlets` can produce for `index_count`
+/// indices; the slices passed to `build_meshlets_into_slices` need to be sized accordingly.
+pub fn build_meshlets_bound(
+    index_count: usize,
+    max_vertices: usize,
+    max_triangles: usize,
+) -> usize {
+    unsafe { ffi::meshopt_buildMeshletsBound(index_count, max_vertices, max_triangles) }
+}
+
+/// Same as `build_meshlets`, but reuses the buffers of `meshlets`, so that repeated calls
+/// (e.g. for many meshes) don't need to allocate worst-case sized buffers every time.
+///
+/// Any previous contents of `meshlets` are replaced.
+pub fn build_meshlets_into<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    max_vertices: usize,
+    max_triangles: usize,
+    cone_weight: f32,
+    meshlets: &mut Meshlets,
+) -> Result<()> {
+    validate_meshlet_parameters(max_vertices, max_triangles, cone_weight)?;
+    let (meshlet_count, vertex_count, triangle_count) =
+        meshlet_buffer_sizes(indices.len(), max_vertices, max_triangles)?;
+    meshlets
+        .meshlets
+        .resize(meshlet_count, unsafe { ::std::mem::zeroed() });
+    meshlets.vertices.resize(vertex_count, 0);
+    meshlets.triangles.resize(triangle_count, 0);
+
+    let counts = build_meshlets_into_slices(
+        indices,
+        vertices,
+        max_vertices,
+        max_triangles,
+        cone_weight,
+        &mut meshlets.meshlets,
+        &mut meshlets.vertices,
+        &mut meshlets.triangles,
+    )?;
+
+    meshlets.meshlets.truncate(counts.meshlet_count);
+    meshlets.vertices.truncate(counts.vertex_count);
+    meshlets.triangles.truncate(counts.triangle_count);
+    Ok(())
+}
+
+/// Number of elements of each buffer used by `build_meshlets_into_slices`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshletCounts {
+    /// Number of meshlets written.
+    pub meshlet_count: usize,
+    /// Number of meshlet vertices used.
+    pub vertex_count: usize,
+    /// Number of micro-index bytes used, including the padding of the last meshlet to 4 bytes.
+    pub triangle_count: usize,
+}
+
+/// Same as `build_meshlets`, but writes into caller-provided buffers and returns the number
+/// of elements used in each.
+///
+/// `meshlets` must have room for `build_meshlets_bound(indices.len(), max_vertices, max_triangles)`
+/// meshlets, `meshlet_vertices` for that many times `max_vertices` elements and
+/// `meshlet_triangles` for that many times `max_triangles * 3` elements.
+#[allow(clippy::too_many_arguments)]
+pub fn build_meshlets_into_slices<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    max_vertices: usize,
+    max_triangles: usize,
+    cone_weight: f32,
+    meshlets: &mut [ffi::meshopt_Meshlet],
+    meshlet_vertices: &mut [u32],
+    meshlet_triangles: &mut [u8],
+) -> Result<MeshletCounts> {
+    check_indices(indices, vertices.vertex_count);
+    validate_meshlet_parameters(max_vertices, max_triangles, cone_weight)?;
+    let (meshlet_count, vertex_count, triangle_count) =
+        meshlet_buffer_sizes(indices.len(), max_vertices, max_triangles)?;
+    assert!(
+        meshlets.len() >= meshlet_count
+            && meshlet_vertices.len() >= vertex_count
+            && meshlet_triangles.len() >= triangle_count,
+        "meshlet buffers must be sized according to build_meshlets_bound"
+    );
+    let indices = T::to_u32_slice(indices);
 
     let count = unsafe {
         ffi::meshopt_buildMeshlets(
             meshlets.as_mut_ptr(),
-            meshlet_verts.as_mut_ptr(),
-            meshlet_tris.as_mut_ptr(),
+            meshlet_vertices.as_mut_ptr(),
+            meshlet_triangles.as_mut_ptr(),
             indices.as_ptr(),
             indices.len(),
             vertices.pos_ptr(),
@@ -83,32 +651,28 @@ pub fn build_meshlets(
             cone_weight,
         )
     };
+    if count == 0 {
+        return Ok(MeshletCounts::default());
+    }
 
-    let last_meshlet = meshlets[count - 1];
-    meshlet_verts
-        .truncate(last_meshlet.vertex_offset as usize + last_meshlet.vertex_count as usize);
-    meshlet_tris.truncate(
-        last_meshlet.triangle_offset as usize
-            + ((last_meshlet.triangle_count as usize * 3 + 3) & !3),
-    );
-    meshlets.truncate(count);
-
-    for meshlet in meshlets.iter_mut().take(count) {
+    for meshlet in meshlets.iter().take(count) {
         unsafe {
             ffi::meshopt_optimizeMeshlet(
-                &mut meshlet_verts[meshlet.vertex_offset as usize],
-                &mut meshlet_tris[meshlet.triangle_offset as usize],
+                &mut meshlet_vertices[meshlet.vertex_offset as usize],
+                &mut meshlet_triangles[meshlet.triangle_offset as usize],
                 meshlet.triangle_count as usize,
                 meshlet.vertex_count as usize,
             );
         };
     }
 
-    Meshlets {
-        meshlets,
-        vertices: meshlet_verts,
-        triangles: meshlet_tris,
-    }
+    let last_meshlet = meshlets[count - 1];
+    Ok(MeshletCounts {
+        meshlet_count: count,
+        vertex_count: last_meshlet.vertex_offset as usize + last_meshlet.vertex_count as usize,
+        triangle_count: last_meshlet.triangle_offset as usize
+            + ((last_meshlet.triangle_count as usize * 3 + 3) & !3),
+    })
 }
 
 /// Creates bounding volumes that can be used for frustum, backface and occlusion culling.
@@ -130,6 +694,7 @@ pub fn build_meshlets(
 ///
 /// `index_count` should be <= 256*3 (the function assumes clusters of limited size)
 pub fn compute_cluster_bounds(indices: &[u32], vertices: &VertexDataAdapter<'_>) -> Bounds {
+    check_indices(indices, vertices.vertex_count);
     unsafe {
         ffi::meshopt_computeClusterBounds(
             indices.as_ptr(),
@@ -163,19 +728,7 @@ pub fn compute_cluster_bounds_decoder<T: DecodePosition>(
     indices: &[u32],
     vertices: &[T],
 ) -> Bounds {
-    let vertices = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    unsafe {
-        ffi::meshopt_computeClusterBounds(
-            indices.as_ptr(),
-            indices.len(),
-            vertices.as_ptr().cast(),
-            vertices.len() * 3,
-            ::std::mem::size_of::<f32>() * 3,
-        )
-    }
+    vertices.with_positions(|positions| compute_cluster_bounds(indices, positions))
 }
 
 pub fn compute_meshlet_bounds(meshlet: Meshlet<'_>, vertices: &VertexDataAdapter<'_>) -> Bounds {
@@ -195,18 +748,445 @@ pub fn compute_meshlet_bounds_decoder<T: DecodePosition>(
     meshlet: Meshlet<'_>,
     vertices: &[T],
 ) -> Bounds {
-    let vertices = vertices
+    vertices.with_positions(|positions| compute_meshlet_bounds(meshlet, positions))
+}
+
+/// Axis-aligned bounding box.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Default for Aabb {
+    /// Returns an empty box, which grows to the first point or box added to it.
+    fn default() -> Self {
+        Self {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+        }
+    }
+}
+
+impl Aabb {
+    /// Returns `true` if no point has been added to the box.
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    /// Grows the box to contain `point`.
+    pub fn add_point(&mut self, point: [f32; 3]) {
+        for (i, &value) in point.iter().enumerate() {
+            self.min[i] = self.min[i].min(value);
+            self.max[i] = self.max[i].max(value);
+        }
+    }
+
+    /// Grows the box to contain `other`.
+    pub fn add_aabb(&mut self, other: &Aabb) {
+        if !other.is_empty() {
+            self.add_point(other.min);
+            self.add_point(other.max);
+        }
+    }
+
+    /// Quantizes the box to 8 bits per component relative to `parent`, which must contain it.
+    ///
+    /// The minimum is rounded down and the maximum up, so the dequantized box is
+    /// conservative; see `QuantizedAabb::dequantize`.
+    pub fn quantize(&self, parent: &Aabb) -> QuantizedAabb {
+        let mut result = QuantizedAabb::default();
+        for i in 0..3 {
+            let extent = parent.max[i] - parent.min[i];
+            let scale = if extent > 0f32 { 255f32 / extent } else { 0f32 };
+            let min = ((self.min[i] - parent.min[i]) * scale).floor();
+            let max = ((self.max[i] - parent.min[i]) * scale).ceil();
+            result.min[i] = min.clamp(0f32, 255f32) as u8;
+            result.max[i] = max.clamp(0f32, 255f32) as u8;
+        }
+        result
+    }
+}
+
+/// Axis-aligned bounding box quantized to 8 bits per component relative to a parent box,
+/// as produced by `Aabb::quantize`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct QuantizedAabb {
+    pub min: [u8; 3],
+    pub max: [u8; 3],
+}
+
+impl QuantizedAabb {
+    /// Reconstructs the (conservative) box relative to the `parent` used for quantization.
+    pub fn dequantize(&self, parent: &Aabb) -> Aabb {
+        let mut result = Aabb::default();
+        for i in 0..3 {
+            let step = (parent.max[i] - parent.min[i]) / 255f32;
+            result.min[i] = parent.min[i] + f32::from(self.min[i]) * step;
+            result.max[i] = parent.min[i] + f32::from(self.max[i]) * step;
+        }
+        result
+    }
+}
+
+fn aabb_center(aabb: &Aabb) -> [f32; 3] {
+    [
+        (aabb.min[0] + aabb.max[0]) * 0.5,
+        (aabb.min[1] + aabb.max[1]) * 0.5,
+        (aabb.min[2] + aabb.max[2]) * 0.5,
+    ]
+}
+
+/// Computes the axis-aligned bounding box of the meshlet vertices.
+pub fn compute_meshlet_aabb(meshlet: Meshlet<'_>, vertices: &VertexDataAdapter<'_>) -> Aabb {
+    let mut aabb = Aabb::default();
+    for &vertex in meshlet.vertices {
+        aabb.add_point(vertices.position(vertex as usize));
+    }
+    aabb
+}
+
+/// Computes the axis-aligned bounding box of the meshlet vertices.
+pub fn compute_meshlet_aabb_decoder<T: DecodePosition>(
+    meshlet: Meshlet<'_>,
+    vertices: &[T],
+) -> Aabb {
+    let mut aabb = Aabb::default();
+    for &vertex in meshlet.vertices {
+        aabb.add_point(vertices[vertex as usize].decode_position());
+    }
+    aabb
+}
+
+/// Computes the axis-aligned bounding box of every meshlet, together with the box of the
+/// whole set of meshlets.
+///
+/// The parent box can be used with `Aabb::quantize` to store the meshlet boxes in 6 bytes each.
+pub fn compute_meshlet_aabbs(
+    meshlets: &Meshlets,
+    vertices: &VertexDataAdapter<'_>,
+) -> (Vec<Aabb>, Aabb) {
+    let aabbs: Vec<Aabb> = meshlets
         .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    unsafe {
-        ffi::meshopt_computeMeshletBounds(
-            meshlet.vertices.as_ptr(),
-            meshlet.triangles.as_ptr(),
-            meshlet.triangles.len() / 3,
-            vertices.as_ptr().cast(),
-            vertices.len() * 3,
-            std::mem::size_of::<f32>() * 3,
-        )
+        .map(|meshlet| compute_meshlet_aabb(meshlet, vertices))
+        .collect();
+    let mut parent = Aabb::default();
+    for aabb in &aabbs {
+        parent.add_aabb(aabb);
+    }
+    (aabbs, parent)
+}
+
+/// Same as `compute_meshlet_aabbs`, but computes the boxes of the meshlets in parallel.
+#[cfg(feature = "rayon")]
+pub fn par_compute_meshlet_aabbs(
+    meshlets: &Meshlets,
+    vertices: &VertexDataAdapter<'_>,
+) -> (Vec<Aabb>, Aabb) {
+    let aabbs: Vec<Aabb> = meshlets
+        .par_iter()
+        .map(|meshlet| compute_meshlet_aabb(meshlet, vertices))
+        .collect();
+    let mut parent = Aabb::default();
+    for aabb in &aabbs {
+        parent.add_aabb(aabb);
+    }
+    (aabbs, parent)
+}
+
+/// Computes the bounds of every meshlet in parallel, see `compute_meshlet_bounds`.
+#[cfg(feature = "rayon")]
+pub fn par_compute_meshlet_bounds(
+    meshlets: &Meshlets,
+    vertices: &VertexDataAdapter<'_>,
+) -> Vec<Bounds> {
+    meshlets
+        .par_iter()
+        .map(|meshlet| compute_meshlet_bounds(meshlet, vertices))
+        .collect()
+}
+
+/// Computes the axis-aligned bounding box of every meshlet, together with the box of the
+/// whole set of meshlets.
+///
+/// The parent box can be used with `Aabb::quantize` to store the meshlet boxes in 6 bytes each.
+pub fn compute_meshlet_aabbs_decoder<T: DecodePosition>(
+    meshlets: &Meshlets,
+    vertices: &[T],
+) -> (Vec<Aabb>, Aabb) {
+    let aabbs: Vec<Aabb> = meshlets
+        .iter()
+        .map(|meshlet| compute_meshlet_aabb_decoder(meshlet, vertices))
+        .collect();
+    let mut parent = Aabb::default();
+    for aabb in &aabbs {
+        parent.add_aabb(aabb);
+    }
+    (aabbs, parent)
+}
+
+/// Partitions clusters into groups of roughly `target_partition_size` clusters, where
+/// clusters in the same group share as many vertices as possible.
+///
+/// `cluster_indices` contains the index buffers of all clusters back to back, with
+/// `cluster_index_counts` specifying how many indices belong to each cluster.
+///
+/// Returns the number of partitions and, for each cluster, the index of the partition
+/// it was assigned to. The result can be used to group clusters for hierarchical
+/// simplification.
+///
+/// This variant only considers connectivity; use `partition_clusters_with_positions`
+/// to also keep partitions spatially coherent.
+///
+/// The bundled meshoptimizer has no `meshopt_partitionClusters`, so this is a greedy
+/// partitioner written in Rust: each partition grows from a seed by repeatedly adding the
+/// unassigned cluster sharing the most vertices with it. Its results differ from those of
+/// `meshopt_partitionClusters` in newer meshoptimizer versions.
+pub fn partition_clusters(
+    cluster_indices: &[u32],
+    cluster_index_counts: &[u32],
+    vertex_count: usize,
+    target_partition_size: usize,
+) -> (usize, Vec<u32>) {
+    partition_clusters_impl(
+        cluster_indices,
+        cluster_index_counts,
+        vertex_count,
+        None,
+        target_partition_size,
+    )
+}
+
+/// Partitions clusters into groups of roughly `target_partition_size` clusters, where
+/// clusters in the same group share as many vertices as possible and are close to
+/// each other in space.
+///
+/// Vertex positions are used to order partition seeds along a space-filling curve, to
+/// break ties between equally connected candidates and to keep growing a partition
+/// through disconnected geometry. This produces more spatially coherent cluster groups,
+/// which is important for hierarchical LOD.
+///
+/// See `partition_clusters` for the meaning of the other parameters and the result.
+pub fn partition_clusters_with_positions(
+    cluster_indices: &[u32],
+    cluster_index_counts: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_partition_size: usize,
+) -> (usize, Vec<u32>) {
+    let centers = cluster_centers(cluster_indices, cluster_index_counts, |vertex| {
+        vertices.position(vertex)
+    });
+    partition_clusters_impl(
+        cluster_indices,
+        cluster_index_counts,
+        vertices.vertex_count,
+        Some(&centers),
+        target_partition_size,
+    )
+}
+
+/// Partitions clusters into groups of roughly `target_partition_size` clusters, where
+/// clusters in the same group share as many vertices as possible and are close to
+/// each other in space.
+///
+/// See `partition_clusters_with_positions` for details.
+pub fn partition_clusters_decoder<T: DecodePosition>(
+    cluster_indices: &[u32],
+    cluster_index_counts: &[u32],
+    vertices: &[T],
+    target_partition_size: usize,
+) -> (usize, Vec<u32>) {
+    let centers = cluster_centers(cluster_indices, cluster_index_counts, |vertex| {
+        vertices[vertex].decode_position()
+    });
+    partition_clusters_impl(
+        cluster_indices,
+        cluster_index_counts,
+        vertices.len(),
+        Some(&centers),
+        target_partition_size,
+    )
+}
+
+fn cluster_centers(
+    cluster_indices: &[u32],
+    cluster_index_counts: &[u32],
+    position: impl Fn(usize) -> [f32; 3],
+) -> Vec<[f32; 3]> {
+    let mut offset = 0;
+    cluster_index_counts
+        .iter()
+        .map(|&count| {
+            let indices = &cluster_indices[offset..offset + count as usize];
+            offset += count as usize;
+
+            let mut center = [0f32; 3];
+            for &index in indices {
+                let p = position(index as usize);
+                center[0] += p[0];
+                center[1] += p[1];
+                center[2] += p[2];
+            }
+            let scale = if indices.is_empty() {
+                0f32
+            } else {
+                1f32 / indices.len() as f32
+            };
+            [center[0] * scale, center[1] * scale, center[2] * scale]
+        })
+        .collect()
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1]) + (a[2] - b[2]) * (a[2] - b[2])
+}
+
+fn partition_clusters_impl(
+    cluster_indices: &[u32],
+    cluster_index_counts: &[u32],
+    vertex_count: usize,
+    centers: Option<&[[f32; 3]]>,
+    target_partition_size: usize,
+) -> (usize, Vec<u32>) {
+    const UNASSIGNED: u32 = u32::MAX;
+
+    let cluster_count = cluster_index_counts.len();
+    let total_index_count: usize = cluster_index_counts.iter().map(|&c| c as usize).sum();
+    assert_eq!(
+        total_index_count,
+        cluster_indices.len(),
+        "cluster index counts must add up to the number of cluster indices"
+    );
+    let target_partition_size = target_partition_size.max(1);
+
+    // unique vertices of each cluster, and the clusters referencing each vertex
+    let mut cluster_vertices: Vec<Vec<u32>> = Vec::with_capacity(cluster_count);
+    let mut vertex_clusters: Vec<Vec<u32>> = vec![Vec::new(); vertex_count];
+    let mut offset = 0;
+    for (cluster, &count) in cluster_index_counts.iter().enumerate() {
+        let mut unique = cluster_indices[offset..offset + count as usize].to_vec();
+        offset += count as usize;
+        unique.sort_unstable();
+        unique.dedup();
+        for &vertex in &unique {
+            vertex_clusters[vertex as usize].push(cluster as u32);
+        }
+        cluster_vertices.push(unique);
     }
+
+    // seeds are visited in input order, or along a space-filling curve when positions are known
+    let order: Vec<u32> = match centers {
+        Some(centers) => {
+            let mut remap: Vec<u32> = vec![0; cluster_count];
+            unsafe {
+                ffi::meshopt_spatialSortRemap(
+                    remap.as_mut_ptr(),
+                    centers.as_ptr().cast(),
+                    centers.len(),
+                    std::mem::size_of::<f32>() * 3,
+                );
+            }
+            let mut order: Vec<u32> = vec![0; cluster_count];
+            for (cluster, &position) in remap.iter().enumerate() {
+                order[position as usize] = cluster as u32;
+            }
+            order
+        }
+        None => (0..cluster_count as u32).collect(),
+    };
+
+    let mut partitions: Vec<u32> = vec![UNASSIGNED; cluster_count];
+    let mut partition_count = 0usize;
+    // cluster -> number of vertices shared with the partition being grown
+    let mut candidates: HashMap<u32, u32> = HashMap::new();
+    let mut cursor = 0;
+    // first position along the curve that may still be unassigned, for disconnected geometry
+    let mut curve_cursor = 0;
+
+    while cursor < order.len() {
+        let seed = order[cursor];
+        if partitions[seed as usize] != UNASSIGNED {
+            cursor += 1;
+            continue;
+        }
+
+        let partition = partition_count as u32;
+        partition_count += 1;
+        candidates.clear();
+
+        let mut size = 0;
+        let mut center_sum = [0f32; 3];
+        let mut next = Some(seed);
+
+        while let Some(cluster) = next {
+            partitions[cluster as usize] = partition;
+            size += 1;
+            if let Some(centers) = centers {
+                let c = centers[cluster as usize];
+                center_sum = [
+                    center_sum[0] + c[0],
+                    center_sum[1] + c[1],
+                    center_sum[2] + c[2],
+                ];
+            }
+            if size >= target_partition_size {
+                break;
+            }
+
+            candidates.remove(&cluster);
+            for &vertex in &cluster_vertices[cluster as usize] {
+                for &other in &vertex_clusters[vertex as usize] {
+                    if partitions[other as usize] == UNASSIGNED {
+                        *candidates.entry(other).or_insert(0) += 1;
+                    }
+                }
+            }
+
+            let partition_center = [
+                center_sum[0] / size as f32,
+                center_sum[1] / size as f32,
+                center_sum[2] / size as f32,
+            ];
+            let distance = |cluster: u32| {
+                centers.map_or(0f32, |centers| {
+                    distance_squared(centers[cluster as usize], partition_center)
+                })
+            };
+
+            next = candidates
+                .iter()
+                .map(|(&candidate, &shared)| (candidate, shared))
+                .min_by(|&(a, shared_a), &(b, shared_b)| {
+                    shared_b
+                        .cmp(&shared_a)
+                        .then_with(|| distance(a).total_cmp(&distance(b)))
+                        .then_with(|| a.cmp(&b))
+                })
+                .map(|(candidate, _)| candidate);
+
+            // disconnected geometry: keep growing with the next cluster along the curve
+            if next.is_none() && centers.is_some() {
+                // clusters never become unassigned again, so the scan position only moves forward
+                curve_cursor = curve_cursor.max(cursor);
+                while curve_cursor < order.len()
+                    && partitions[order[curve_cursor] as usize] != UNASSIGNED
+                {
+                    curve_cursor += 1;
+                }
+                next = order.get(curve_cursor).copied();
+            }
+        }
+    }
+
+    (partition_count, partitions)
 }
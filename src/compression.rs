@@ -0,0 +1,769 @@
+use crate::{
+    decode_filter_exp, decode_filter_oct, decode_index_buffer_checked, decode_vertex_buffer_raw,
+    encode_filter_exp, encode_filter_oct, encode_index_buffer, encode_vertex_buffer_raw,
+    typed_to_bytes, utilities::try_reserve, AttributeFormat, AttributeSemantic, EncodeExpMode,
+    Error, LayoutAttribute, Result, VertexLayout,
+};
+
+const MAGIC: &[u8; 4] = b"MOPC";
+const VERSION: u32 = 1;
+/// Largest supported vertex size, matching the limit of the vertex codec.
+const MAX_VERTEX_SIZE: usize = 256;
+
+/// An attribute of interleaved vertex data, describing how `compress_mesh` encodes it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CompressedAttribute {
+    /// `size` bytes at `offset`, stored losslessly.
+    Raw { offset: usize, size: usize },
+    /// Unit vector of 3 or 4 `f32` components, stored with the octahedral filter using `bits`
+    /// (1..=16) bits per component; the fourth component (e.g. a tangent sign) is quantized
+    /// to `bits` bits as well.
+    Normal {
+        offset: usize,
+        components: usize,
+        bits: u32,
+    },
+    /// `f32` components stored with the exponential filter using `bits` (1..=24) bits of
+    /// mantissa per component.
+    Float {
+        offset: usize,
+        components: usize,
+        bits: u32,
+    },
+}
+
+impl CompressedAttribute {
+    /// Describes how to encode `attribute`: `Float32` normals and tangents with 3 or 4
+    /// components use `Normal` with `normal_bits`, other `Float32` attributes use `Float`
+    /// with `float_bits`, and everything else is stored as `Raw`.
+    pub fn from_layout_attribute(
+        attribute: &LayoutAttribute,
+        normal_bits: u32,
+        float_bits: u32,
+    ) -> Self {
+        let offset = attribute.offset;
+        match (attribute.semantic, attribute.format) {
+            (
+                AttributeSemantic::Normal | AttributeSemantic::Tangent,
+                AttributeFormat::Float32(components @ (3 | 4)),
+            ) => CompressedAttribute::Normal {
+                offset,
+                components,
+                bits: normal_bits,
+            },
+            (_, AttributeFormat::Float32(components)) => CompressedAttribute::Float {
+                offset,
+                components,
+                bits: float_bits,
+            },
+            (_, format) => CompressedAttribute::Raw {
+                offset,
+                size: format.size(),
+            },
+        }
+    }
+
+    /// Describes every attribute of `layout`, see `from_layout_attribute`; pass `layout.stride`
+    /// as the vertex size to `compress_mesh`.
+    pub fn from_vertex_layout(
+        layout: &VertexLayout,
+        normal_bits: u32,
+        float_bits: u32,
+    ) -> Vec<Self> {
+        layout
+            .attributes
+            .iter()
+            .map(|attribute| Self::from_layout_attribute(attribute, normal_bits, float_bits))
+            .collect()
+    }
+
+    fn kind(&self) -> u32 {
+        match self {
+            CompressedAttribute::Raw { .. } => 0,
+            CompressedAttribute::Normal { .. } => 1,
+            CompressedAttribute::Float { .. } => 2,
+        }
+    }
+
+    /// Returns `(offset, size in bytes, components or size, bits)`.
+    fn fields(&self) -> (usize, usize, usize, u32) {
+        match *self {
+            CompressedAttribute::Raw { offset, size } => (offset, size, size, 0),
+            CompressedAttribute::Normal {
+                offset,
+                components,
+                bits,
+            }
+            | CompressedAttribute::Float {
+                offset,
+                components,
+                bits,
+            } => (offset, components.saturating_mul(4), components, bits),
+        }
+    }
+
+    /// Returns the size in bytes of a vertex in the encoded stream of this attribute.
+    fn stream_stride(&self) -> usize {
+        match *self {
+            CompressedAttribute::Raw { size, .. } => padded(size),
+            CompressedAttribute::Normal { bits, .. } => {
+                if bits <= 8 {
+                    4
+                } else {
+                    8
+                }
+            }
+            CompressedAttribute::Float { components, .. } => components * 4,
+        }
+    }
+
+    fn from_fields(kind: u32, offset: usize, count: usize, bits: u32) -> Result<Self> {
+        Ok(match kind {
+            0 => CompressedAttribute::Raw {
+                offset,
+                size: count,
+            },
+            1 => CompressedAttribute::Normal {
+                offset,
+                components: count,
+                bits,
+            },
+            2 => CompressedAttribute::Float {
+                offset,
+                components: count,
+                bits,
+            },
+            _ => return Err(Error::Parse(format!("unknown attribute kind ({})", kind))),
+        })
+    }
+
+    fn validate(&self, vertex_size: usize) -> Result<()> {
+        let (offset, size, count, bits) = self.fields();
+        let fits = matches!(offset.checked_add(size), Some(end) if end <= vertex_size);
+        if count == 0 || !fits {
+            return Err(Error::Config(format!(
+                "attribute at offset {} with size {} must be non-empty and fit within vertex_size ({})",
+                offset, size, vertex_size
+            )));
+        }
+        if let CompressedAttribute::Normal { components, .. } = self {
+            if *components != 3 && *components != 4 {
+                return Err(Error::Config(format!(
+                    "normal attributes must have 3 or 4 components, got {}",
+                    components
+                )));
+            }
+        }
+        let max_bits = match self {
+            CompressedAttribute::Raw { .. } => 0,
+            CompressedAttribute::Normal { .. } => 16,
+            CompressedAttribute::Float { .. } => 24,
+        };
+        if max_bits != 0 && (bits == 0 || bits > max_bits) {
+            return Err(Error::Config(format!(
+                "attribute bits ({}) must be in 1..={}",
+                bits, max_bits
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A mesh decompressed by `decompress_mesh`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecompressedMesh {
+    pub indices: Vec<u32>,
+    /// Interleaved vertex data; bytes not covered by any attribute are zero.
+    pub vertices: Vec<u8>,
+    pub vertex_count: usize,
+    pub vertex_size: usize,
+    pub attributes: Vec<CompressedAttribute>,
+}
+
+fn read_f32(vertices: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([
+        vertices[offset],
+        vertices[offset + 1],
+        vertices[offset + 2],
+        vertices[offset + 3],
+    ])
+}
+
+fn unit_vector(v: [f32; 4], xyz_scale: f32, w_scale: f32) -> [f32; 4] {
+    [
+        v[0] / xyz_scale,
+        v[1] / xyz_scale,
+        v[2] / xyz_scale,
+        v[3] / w_scale,
+    ]
+}
+
+fn padded(size: usize) -> usize {
+    (size + 3) & !3
+}
+
+fn encode_attribute(
+    vertices: &[u8],
+    vertex_count: usize,
+    vertex_size: usize,
+    attribute: &CompressedAttribute,
+) -> Result<Vec<u8>> {
+    let vertex_offsets = (0..vertex_count).map(|vertex| vertex * vertex_size);
+    match *attribute {
+        CompressedAttribute::Raw { offset, size } => {
+            let stride = padded(size);
+            let mut stream: Vec<u8> = vec![0; vertex_count * stride];
+            for (vertex, base) in vertex_offsets.enumerate() {
+                stream[vertex * stride..vertex * stride + size]
+                    .copy_from_slice(&vertices[base + offset..base + offset + size]);
+            }
+            encode_vertex_buffer_raw(&stream, vertex_count, stride)
+        }
+        CompressedAttribute::Normal {
+            offset,
+            components,
+            bits,
+        } => {
+            let vectors: Vec<[f32; 4]> = vertex_offsets
+                .map(|base| {
+                    let mut vector = [0f32; 4];
+                    for (i, value) in vector.iter_mut().enumerate().take(components) {
+                        *value = read_f32(vertices, base + offset + i * 4);
+                    }
+                    vector
+                })
+                .collect();
+            if bits <= 8 {
+                let stream = encode_filter_oct::<[i8; 4]>(&vectors, bits)?;
+                encode_vertex_buffer_raw(typed_to_bytes(&stream), vertex_count, 4)
+            } else {
+                let stream: Vec<u8> = encode_filter_oct::<[i16; 4]>(&vectors, bits)?
+                    .iter()
+                    .flatten()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect();
+                encode_vertex_buffer_raw(&stream, vertex_count, 8)
+            }
+        }
+        CompressedAttribute::Float {
+            offset,
+            components,
+            bits,
+        } => {
+            let values: Vec<f32> = vertex_offsets
+                .flat_map(|base| {
+                    (0..components).map(move |i| read_f32(vertices, base + offset + i * 4))
+                })
+                .collect();
+            let stream: Vec<u8> = encode_filter_exp::<u32>(&values, bits, EncodeExpMode::Separate)?
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            encode_vertex_buffer_raw(&stream, vertex_count, components * 4)
+        }
+    }
+}
+
+fn decode_attribute(
+    encoded: &[u8],
+    vertices: &mut [u8],
+    vertex_count: usize,
+    vertex_size: usize,
+    attribute: &CompressedAttribute,
+) -> Result<()> {
+    match *attribute {
+        CompressedAttribute::Raw { offset, size } => {
+            let stride = attribute.stream_stride();
+            let stream = decode_vertex_buffer_raw(encoded, vertex_count, stride)?;
+            for vertex in 0..vertex_count {
+                let base = vertex * vertex_size + offset;
+                vertices[base..base + size]
+                    .copy_from_slice(&stream[vertex * stride..vertex * stride + size]);
+            }
+        }
+        CompressedAttribute::Normal {
+            offset,
+            components,
+            bits,
+        } => {
+            let stream =
+                decode_vertex_buffer_raw(encoded, vertex_count, attribute.stream_stride())?;
+            // the filter rescales xyz to the full component range, but leaves w at `bits` bits
+            let w_scale = ((1 << (bits - 1)) - 1).max(1) as f32;
+            let values: Vec<f32> = if bits <= 8 {
+                let mut data: Vec<[i8; 4]> = stream
+                    .chunks_exact(4)
+                    .map(|c| [c[0] as i8, c[1] as i8, c[2] as i8, c[3] as i8])
+                    .collect();
+                decode_filter_oct(&mut data)?;
+                data.iter()
+                    .flat_map(|v| unit_vector(v.map(f32::from), 127f32, w_scale))
+                    .collect()
+            } else {
+                let mut data: Vec<[i16; 4]> = stream
+                    .chunks_exact(8)
+                    .map(|c| {
+                        [
+                            i16::from_le_bytes([c[0], c[1]]),
+                            i16::from_le_bytes([c[2], c[3]]),
+                            i16::from_le_bytes([c[4], c[5]]),
+                            i16::from_le_bytes([c[6], c[7]]),
+                        ]
+                    })
+                    .collect();
+                decode_filter_oct(&mut data)?;
+                data.iter()
+                    .flat_map(|v| unit_vector(v.map(f32::from), 32767f32, w_scale))
+                    .collect()
+            };
+            for vertex in 0..vertex_count {
+                for i in 0..components {
+                    let base = vertex * vertex_size + offset + i * 4;
+                    vertices[base..base + 4].copy_from_slice(&values[vertex * 4 + i].to_le_bytes());
+                }
+            }
+        }
+        CompressedAttribute::Float {
+            offset, components, ..
+        } => {
+            let stream = decode_vertex_buffer_raw(encoded, vertex_count, components * 4)?;
+            let mut data: Vec<u32> = stream
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect();
+            decode_filter_exp(&mut data)?;
+            for vertex in 0..vertex_count {
+                let base = vertex * vertex_size + offset;
+                for i in 0..components {
+                    vertices[base + i * 4..base + i * 4 + 4]
+                        .copy_from_slice(&data[vertex * components + i].to_le_bytes());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compresses a mesh with interleaved vertices into a self-describing blob that can be
+/// restored with `decompress_mesh`.
+///
+/// Each attribute in `attributes` is stored as a separate stream: normals use the octahedral
+/// filter, floats the exponential filter and raw attributes are stored losslessly; all
+/// streams and the index buffer are then encoded with the vertex and index codecs. Bytes of
+/// the vertex not covered by any attribute are not stored.
+///
+/// Normal and float attributes are read as little-endian `f32` values, and all streams are
+/// stored little-endian regardless of the platform.
+///
+/// For best results, the mesh should be optimized for vertex cache and vertex fetch first.
+pub fn compress_mesh(
+    indices: &[u32],
+    vertices: &[u8],
+    vertex_size: usize,
+    attributes: &[CompressedAttribute],
+) -> Result<Vec<u8>> {
+    if vertex_size == 0 || vertex_size > MAX_VERTEX_SIZE {
+        return Err(Error::Config(format!(
+            "vertex_size ({}) must be in 1..={}",
+            vertex_size, MAX_VERTEX_SIZE
+        )));
+    }
+    if !vertices.len().is_multiple_of(vertex_size) {
+        return Err(Error::Config(format!(
+            "vertex data length ({}) must be a multiple of vertex_size ({})",
+            vertices.len(),
+            vertex_size
+        )));
+    }
+    if attributes.is_empty() {
+        return Err(Error::Config(
+            "at least one attribute is required to store the vertex data".into(),
+        ));
+    }
+    let vertex_count = vertices.len() / vertex_size;
+    for attribute in attributes {
+        attribute.validate(vertex_size)?;
+    }
+
+    let streams = attributes
+        .iter()
+        .map(|attribute| encode_attribute(vertices, vertex_count, vertex_size, attribute))
+        .collect::<Result<Vec<Vec<u8>>>>()?;
+    let encoded_indices = encode_index_buffer(indices, vertex_count)?;
+
+    let mut result: Vec<u8> = Vec::new();
+    let mut write = |value: usize, what: &str| -> Result<()> {
+        let value = u32::try_from(value).map_err(|err| {
+            Error::Config(format!(
+                "{} ({}) doesn't fit in 32 bits: {}",
+                what, value, err
+            ))
+        })?;
+        result.extend_from_slice(&value.to_le_bytes());
+        Ok(())
+    };
+    write(u32::from_le_bytes(*MAGIC) as usize, "magic")?;
+    write(VERSION as usize, "version")?;
+    write(indices.len(), "index count")?;
+    write(vertex_count, "vertex count")?;
+    write(vertex_size, "vertex size")?;
+    write(attributes.len(), "attribute count")?;
+    for (attribute, stream) in attributes.iter().zip(&streams) {
+        let (offset, _, count, bits) = attribute.fields();
+        write(attribute.kind() as usize, "attribute kind")?;
+        write(offset, "attribute offset")?;
+        write(count, "attribute size")?;
+        write(bits as usize, "attribute bits")?;
+        write(stream.len(), "attribute stream size")?;
+    }
+    write(encoded_indices.len(), "index data size")?;
+    for stream in &streams {
+        result.extend_from_slice(stream);
+    }
+    result.extend_from_slice(&encoded_indices);
+    Ok(result)
+}
+
+/// Restores a mesh compressed with `compress_mesh`.
+///
+/// The blob is validated, so this is safe to use for untrusted input.
+pub fn decompress_mesh(data: &[u8]) -> Result<DecompressedMesh> {
+    let mut offset = 0usize;
+    let mut read = |what: &str| -> Result<usize> {
+        let bytes = data
+            .get(offset..offset + 4)
+            .ok_or_else(|| Error::Parse(format!("unexpected end of data reading {}", what)))?;
+        offset += 4;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+
+    if read("magic")? != u32::from_le_bytes(*MAGIC) as usize {
+        return Err(Error::Parse("invalid magic, expected \"MOPC\"".into()));
+    }
+    let version = read("version")?;
+    if version != VERSION as usize {
+        return Err(Error::Parse(format!("unsupported version ({})", version)));
+    }
+    let index_count = read("index count")?;
+    let vertex_count = read("vertex count")?;
+    let vertex_size = read("vertex size")?;
+    let attribute_count = read("attribute count")?;
+    if vertex_size == 0 || vertex_size > MAX_VERTEX_SIZE {
+        return Err(Error::Parse(format!(
+            "vertex size ({}) must be in 1..={}",
+            vertex_size, MAX_VERTEX_SIZE
+        )));
+    }
+    if attribute_count == 0 {
+        return Err(Error::Parse("mesh has no attributes".into()));
+    }
+
+    let mut attributes: Vec<CompressedAttribute> = Vec::new();
+    let mut stream_sizes: Vec<usize> = Vec::new();
+    for _ in 0..attribute_count {
+        let kind = read("attribute kind")? as u32;
+        let attribute_offset = read("attribute offset")?;
+        let count = read("attribute size")?;
+        let bits = read("attribute bits")? as u32;
+        let attribute = CompressedAttribute::from_fields(kind, attribute_offset, count, bits)?;
+        attribute.validate(vertex_size)?;
+        let stream_size = read("attribute stream size")?;
+
+        // the vertex codec compresses data by at most a factor of 64, so check the vertex
+        // count against every stream before allocating, like `decode_vertex_buffer_checked`
+        let fits = match vertex_count.checked_mul(attribute.stream_stride()) {
+            Some(decoded_size) => decoded_size / 64 <= stream_size,
+            None => false,
+        };
+        if !fits {
+            return Err(Error::Parse(format!(
+                "vertex count ({}) is too large for {} bytes of encoded attribute data",
+                vertex_count, stream_size
+            )));
+        }
+        attributes.push(attribute);
+        stream_sizes.push(stream_size);
+    }
+    let index_data_size = read("index data size")?;
+
+    let payload = &data[offset..];
+    let total = stream_sizes
+        .iter()
+        .try_fold(index_data_size, |total, &size| total.checked_add(size));
+    if total != Some(payload.len()) {
+        return Err(Error::Parse(format!(
+            "payload size ({}) doesn't match the stream sizes in the header",
+            payload.len()
+        )));
+    }
+
+    // bounded by the checks above, as vertex_size is at most `MAX_VERTEX_SIZE`
+    let mut vertices: Vec<u8> = Vec::new();
+    try_reserve(&mut vertices, vertex_count * vertex_size)?;
+    vertices.resize(vertex_count * vertex_size, 0);
+    let mut stream_offset = 0;
+    for (attribute, &size) in attributes.iter().zip(&stream_sizes) {
+        decode_attribute(
+            &payload[stream_offset..stream_offset + size],
+            &mut vertices,
+            vertex_count,
+            vertex_size,
+            attribute,
+        )?;
+        stream_offset += size;
+    }
+
+    let indices = decode_index_buffer_checked::<u32>(&payload[stream_offset..], index_count)?;
+    if let Some(index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(Error::Parse(format!(
+            "index ({}) must be less than vertex count ({})",
+            index, vertex_count
+        )));
+    }
+
+    Ok(DecompressedMesh {
+        indices,
+        vertices,
+        vertex_count,
+        vertex_size,
+        attributes,
+    })
+}
+
+const CODEC_MAGIC: &[u8; 4] = b"MOPZ";
+const CODEC_VERSION: u32 = 1;
+
+/// A general-purpose compressor applied after the mesh codecs by `encode_and_compress`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Codec {
+    /// Stores the data as is.
+    None,
+    /// Deflate with the given level (0..=10), using `miniz_oxide`.
+    #[cfg(feature = "deflate")]
+    Deflate(u8),
+    /// Zstandard with the given level (1..=22, or negative for faster modes).
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Codec {
+    fn id(&self) -> u32 {
+        match self {
+            Codec::None => 0,
+            #[cfg(feature = "deflate")]
+            Codec::Deflate(_) => 1,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(_) => 2,
+        }
+    }
+
+    /// Compresses `data`.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match *self {
+            Codec::None => Ok(data.to_vec()),
+            #[cfg(feature = "deflate")]
+            Codec::Deflate(level) => Ok(miniz_oxide::deflate::compress_to_vec(data, level)),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(level) => Ok(zstd::bulk::compress(data, level)?),
+        }
+    }
+
+    /// Decompresses `data`, which must decompress to exactly `size` bytes.
+    pub fn decompress(&self, data: &[u8], size: usize) -> Result<Vec<u8>> {
+        let result = match *self {
+            Codec::None => data.to_vec(),
+            #[cfg(feature = "deflate")]
+            Codec::Deflate(_) => miniz_oxide::inflate::decompress_to_vec_with_limit(data, size)
+                .map_err(|err| Error::Parse(format!("invalid deflate data: {}", err)))?,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(_) => {
+                use std::io::Read;
+                // stream instead of preallocating `size` bytes, which comes from the header;
+                // reading one byte more than expected detects oversized data
+                let mut result = Vec::new();
+                zstd::stream::read::Decoder::new(data)
+                    .and_then(|decoder| decoder.take(size as u64 + 1).read_to_end(&mut result))
+                    .map_err(|err| Error::Parse(format!("invalid zstd data: {}", err)))?;
+                result
+            }
+        };
+        if result.len() != size {
+            return Err(Error::Parse(format!(
+                "decompressed size ({}) doesn't match the expected size ({})",
+                result.len(),
+                size
+            )));
+        }
+        Ok(result)
+    }
+}
+
+/// Compresses a mesh with `compress_mesh`, then compresses the result with `codec`, recording
+/// the codec so that `decompress_and_decode` can restore the mesh.
+pub fn encode_and_compress(
+    indices: &[u32],
+    vertices: &[u8],
+    vertex_size: usize,
+    attributes: &[CompressedAttribute],
+    codec: Codec,
+) -> Result<Vec<u8>> {
+    let encoded = compress_mesh(indices, vertices, vertex_size, attributes)?;
+    if u32::try_from(encoded.len()).is_err() {
+        return Err(Error::memory("encoded mesh must be smaller than 4 GiB"));
+    }
+    let compressed = codec.compress(&encoded)?;
+
+    let mut result: Vec<u8> = Vec::with_capacity(16 + compressed.len());
+    result.extend_from_slice(CODEC_MAGIC);
+    result.extend_from_slice(&CODEC_VERSION.to_le_bytes());
+    result.extend_from_slice(&codec.id().to_le_bytes());
+    result.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    result.extend_from_slice(&compressed);
+    Ok(result)
+}
+
+/// Restores a mesh compressed with `encode_and_compress`.
+///
+/// Fails with `Error::Config` if the data was compressed with a codec whose feature isn't
+/// enabled. The blob is validated, so this is safe to use for untrusted input.
+pub fn decompress_and_decode(data: &[u8]) -> Result<DecompressedMesh> {
+    if data.len() < 16 {
+        return Err(Error::Parse("unexpected end of data reading header".into()));
+    }
+    let read = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+
+    if &data[..4] != CODEC_MAGIC {
+        return Err(Error::Parse("invalid magic, expected \"MOPZ\"".into()));
+    }
+    let version = read(4);
+    if version != CODEC_VERSION {
+        return Err(Error::Parse(format!("unsupported version ({})", version)));
+    }
+    let codec = match read(8) {
+        0 => Codec::None,
+        #[cfg(feature = "deflate")]
+        1 => Codec::Deflate(0),
+        #[cfg(feature = "zstd")]
+        2 => Codec::Zstd(0),
+        #[cfg(not(feature = "deflate"))]
+        1 => {
+            return Err(Error::Config(
+                "deflate data requires the `deflate` feature".into(),
+            ))
+        }
+        #[cfg(not(feature = "zstd"))]
+        2 => {
+            return Err(Error::Config(
+                "zstd data requires the `zstd` feature".into(),
+            ))
+        }
+        id => return Err(Error::Parse(format!("unknown codec ({})", id))),
+    };
+    let size = read(12) as usize;
+
+    decompress_mesh(&codec.decompress(&data[16..], size)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compress_mesh, decompress_mesh, typed_to_bytes, AttributeFormat, AttributeSemantic,
+        CompressedAttribute, VertexLayout,
+    };
+
+    #[test]
+    fn test_from_vertex_layout() {
+        let layout = VertexLayout::new(36)
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float32(3))
+            .with_attribute(AttributeSemantic::Normal, 12, AttributeFormat::Float32(3))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                24,
+                AttributeFormat::Float32(2),
+            )
+            .with_attribute(AttributeSemantic::Color(0), 32, AttributeFormat::Unorm8(4));
+
+        let attributes = CompressedAttribute::from_vertex_layout(&layout, 10, 16);
+        assert_eq!(
+            attributes,
+            [
+                CompressedAttribute::Float {
+                    offset: 0,
+                    components: 3,
+                    bits: 16
+                },
+                CompressedAttribute::Normal {
+                    offset: 12,
+                    components: 3,
+                    bits: 10
+                },
+                CompressedAttribute::Float {
+                    offset: 24,
+                    components: 2,
+                    bits: 16
+                },
+                CompressedAttribute::Raw {
+                    offset: 32,
+                    size: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normal_round_trip_keeps_w() {
+        // tangents with a handedness sign in w
+        let tangents: Vec<[f32; 4]> = (0..64)
+            .map(|i| {
+                let angle = i as f32 * 0.1;
+                let sign = if i % 2 == 0 { 1f32 } else { -1f32 };
+                [angle.cos(), angle.sin(), 0f32, sign]
+            })
+            .collect();
+        let indices: Vec<u32> = (0..63).flat_map(|i| [0, i, i + 1]).collect();
+
+        for bits in [10, 12] {
+            let attribute = CompressedAttribute::Normal {
+                offset: 0,
+                components: 4,
+                bits,
+            };
+            let compressed =
+                compress_mesh(&indices, typed_to_bytes(&tangents), 16, &[attribute]).unwrap();
+            let mesh = decompress_mesh(&compressed).unwrap();
+            assert_eq!(mesh.indices, indices);
+
+            let tolerance = 2f32 / ((1 << (bits - 1)) - 1) as f32;
+            for (vertex, tangent) in mesh.vertices.chunks_exact(16).zip(&tangents) {
+                for (i, &expected) in tangent.iter().enumerate() {
+                    let bytes = [
+                        vertex[i * 4],
+                        vertex[i * 4 + 1],
+                        vertex[i * 4 + 2],
+                        vertex[i * 4 + 3],
+                    ];
+                    let value = f32::from_le_bytes(bytes);
+                    assert!(
+                        (value - expected).abs() <= tolerance,
+                        "component {} is {}, expected {} at {} bits",
+                        i,
+                        value,
+                        expected,
+                        bits
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    build_meshlets_into, optimize_vertex_cache_in_place, simplify_into, simplify_sloppy_into,
+    simplify_with_locks_into, typed_to_bytes, DecodePosition, Index, Meshlets, Result,
+    SimplifyOptions, VertexDataAdapter,
+};
+use std::mem;
+
+/// Scratch buffers reused across calls, for processing many meshes (or many LODs of a
+/// mesh) without allocating for every operation.
+///
+/// Results are returned as slices borrowing the context and stay valid until the next call.
+/// The buffers grow to the largest mesh processed and are kept until the context is dropped
+/// or `shrink` is called.
+#[derive(Default)]
+pub struct MeshoptContext {
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    meshlets: Meshlets,
+}
+
+impl MeshoptContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Releases the scratch buffers.
+    pub fn shrink(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Decodes the positions of `vertices` into the position scratch buffer.
+    pub fn decode_positions<T: DecodePosition>(&mut self, vertices: &[T]) -> &[[f32; 3]] {
+        self.positions.clear();
+        self.positions
+            .extend(vertices.iter().map(DecodePosition::decode_position));
+        &self.positions
+    }
+
+    /// Same as `optimize_vertex_cache`, returning the reordered indices.
+    pub fn optimize_vertex_cache<T: Index>(
+        &mut self,
+        indices: &[T],
+        vertex_count: usize,
+    ) -> &[u32] {
+        self.indices.clear();
+        self.indices
+            .extend(indices.iter().map(|index| index.to_u32()));
+        optimize_vertex_cache_in_place(&mut self.indices, vertex_count);
+        &self.indices
+    }
+
+    /// Same as `simplify`, returning the simplified indices.
+    pub fn simplify(
+        &mut self,
+        indices: &[u32],
+        vertices: &VertexDataAdapter<'_>,
+        target_count: usize,
+        target_error: f32,
+        options: SimplifyOptions,
+        result_error: Option<&mut f32>,
+    ) -> &[u32] {
+        self.indices.resize(indices.len(), 0);
+        let index_count = simplify_into(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            options,
+            &mut self.indices,
+            result_error,
+        );
+        self.indices.truncate(index_count);
+        &self.indices
+    }
+
+    /// Same as `simplify_decoder`, decoding the positions into the position scratch buffer.
+    pub fn simplify_decoder<T: DecodePosition>(
+        &mut self,
+        indices: &[u32],
+        vertices: &[T],
+        target_count: usize,
+        target_error: f32,
+        options: SimplifyOptions,
+        result_error: Option<&mut f32>,
+    ) -> &[u32] {
+        self.decode_positions(vertices);
+        let positions = positions_adapter(&self.positions);
+        self.indices.resize(indices.len(), 0);
+        let index_count = simplify_into(
+            indices,
+            &positions,
+            target_count,
+            target_error,
+            options,
+            &mut self.indices,
+            result_error,
+        );
+        self.indices.truncate(index_count);
+        &self.indices
+    }
+
+    /// Same as `simplify_with_locks`, returning the simplified indices.
+    #[allow(clippy::too_many_arguments)]
+    pub fn simplify_with_locks(
+        &mut self,
+        indices: &[u32],
+        vertices: &VertexDataAdapter<'_>,
+        vertex_lock: &[bool],
+        target_count: usize,
+        target_error: f32,
+        options: SimplifyOptions,
+        result_error: Option<&mut f32>,
+    ) -> &[u32] {
+        self.indices.resize(indices.len(), 0);
+        let index_count = simplify_with_locks_into(
+            indices,
+            vertices,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            &mut self.indices,
+            result_error,
+        );
+        self.indices.truncate(index_count);
+        &self.indices
+    }
+
+    /// Same as `simplify_sloppy`, returning the simplified indices.
+    pub fn simplify_sloppy(
+        &mut self,
+        indices: &[u32],
+        vertices: &VertexDataAdapter<'_>,
+        target_count: usize,
+        target_error: f32,
+        result_error: Option<&mut f32>,
+    ) -> &[u32] {
+        self.indices.resize(indices.len(), 0);
+        let index_count = simplify_sloppy_into(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            &mut self.indices,
+            result_error,
+        );
+        self.indices.truncate(index_count);
+        &self.indices
+    }
+
+    /// Same as `simplify_sloppy_decoder`, decoding the positions into the position scratch
+    /// buffer.
+    pub fn simplify_sloppy_decoder<T: DecodePosition>(
+        &mut self,
+        indices: &[u32],
+        vertices: &[T],
+        target_count: usize,
+        target_error: f32,
+        result_error: Option<&mut f32>,
+    ) -> &[u32] {
+        self.decode_positions(vertices);
+        let positions = positions_adapter(&self.positions);
+        self.indices.resize(indices.len(), 0);
+        let index_count = simplify_sloppy_into(
+            indices,
+            &positions,
+            target_count,
+            target_error,
+            &mut self.indices,
+            result_error,
+        );
+        self.indices.truncate(index_count);
+        &self.indices
+    }
+
+    /// Same as `build_meshlets`, reusing the worst-case sized meshlet buffers of previous
+    /// calls.
+    pub fn build_meshlets<T: Index>(
+        &mut self,
+        indices: &[T],
+        vertices: &VertexDataAdapter<'_>,
+        max_vertices: usize,
+        max_triangles: usize,
+        cone_weight: f32,
+    ) -> Result<&Meshlets> {
+        build_meshlets_into(
+            indices,
+            vertices,
+            max_vertices,
+            max_triangles,
+            cone_weight,
+            &mut self.meshlets,
+        )?;
+        Ok(&self.meshlets)
+    }
+
+    /// Same as `build_meshlets`, decoding the positions into the position scratch buffer.
+    pub fn build_meshlets_decoder<I: Index, T: DecodePosition>(
+        &mut self,
+        indices: &[I],
+        vertices: &[T],
+        max_vertices: usize,
+        max_triangles: usize,
+        cone_weight: f32,
+    ) -> Result<&Meshlets> {
+        self.decode_positions(vertices);
+        let positions = positions_adapter(&self.positions);
+        build_meshlets_into(
+            indices,
+            &positions,
+            max_vertices,
+            max_triangles,
+            cone_weight,
+            &mut self.meshlets,
+        )?;
+        Ok(&self.meshlets)
+    }
+}
+
+fn positions_adapter(positions: &[[f32; 3]]) -> VertexDataAdapter<'_> {
+    VertexDataAdapter::new(typed_to_bytes(positions), mem::size_of::<[f32; 3]>(), 0)
+        .expect("positions are tightly packed")
+}
@@ -0,0 +1,587 @@
+use crate::{
+    encoding::validate_index_size,
+    error_or, ffi, try_decode_index_buffer, try_decode_vertex_buffer, typed_to_bytes,
+    utilities::{try_reserve, UninitBuffer},
+    EncodeHeader, EncodeObject, Error, Result,
+};
+use std::mem;
+use std::str::FromStr;
+
+/// An object (draw group) of a mesh decoded by `decode_mesh`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodedObject {
+    pub material: String,
+    pub index_offset: usize,
+    pub index_count: usize,
+}
+
+/// A mesh read from an `OPTM` container, as written with `EncodeHeader` and `EncodeObject`
+/// by the `encoder` example.
+#[derive(Debug, Clone)]
+pub struct DecodedMesh<V> {
+    pub header: EncodeHeader,
+    pub objects: Vec<DecodedObject>,
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+}
+
+impl<V> DecodedMesh<V> {
+    /// Reconstructs a position from its quantized representation, using the header's
+    /// position offset and scale.
+    pub fn dequantize_position(&self, position: [u16; 3]) -> [f32; 3] {
+        let h = &self.header;
+        [
+            h.pos_offset[0] + f32::from(position[0]) * h.pos_scale,
+            h.pos_offset[1] + f32::from(position[1]) * h.pos_scale,
+            h.pos_offset[2] + f32::from(position[2]) * h.pos_scale,
+        ]
+    }
+
+    /// Reconstructs a texture coordinate from its quantized representation, using the
+    /// header's texture coordinate offset and scale.
+    pub fn dequantize_uv(&self, uv: [u16; 2]) -> [f32; 2] {
+        let h = &self.header;
+        [
+            h.uv_offset[0] + f32::from(uv[0]) * h.uv_scale[0],
+            h.uv_offset[1] + f32::from(uv[1]) * h.uv_scale[1],
+        ]
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize, what: &str) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| {
+                Error::Parse(format!(
+                    "unexpected end of data reading {} ({} bytes at offset {}, {} available)",
+                    what,
+                    length,
+                    self.offset,
+                    self.data.len()
+                ))
+            })?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self, what: &str) -> Result<u32> {
+        let bytes = self.bytes(4, what)?;
+        Ok(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self, what: &str) -> Result<f32> {
+        self.u32(what).map(f32::from_bits)
+    }
+}
+
+/// Parses the header of an `OPTM` container.
+pub fn decode_header(data: &[u8]) -> Result<EncodeHeader> {
+    let mut reader = Reader { data, offset: 0 };
+    read_header(&mut reader)
+}
+
+fn read_header(reader: &mut Reader<'_>) -> Result<EncodeHeader> {
+    let magic = reader.bytes(4, "magic")?;
+    if magic != b"OPTM" {
+        return Err(Error::Parse(format!(
+            "invalid magic {:?}, expected \"OPTM\"",
+            magic
+        )));
+    }
+    let header = EncodeHeader {
+        magic: *b"OPTM",
+        group_count: reader.u32("group count")?,
+        vertex_count: reader.u32("vertex count")?,
+        index_count: reader.u32("index count")?,
+        vertex_data_size: reader.u32("vertex data size")?,
+        index_data_size: reader.u32("index data size")?,
+        pos_offset: [
+            reader.f32("position offset")?,
+            reader.f32("position offset")?,
+            reader.f32("position offset")?,
+        ],
+        pos_scale: reader.f32("position scale")?,
+        uv_offset: [reader.f32("uv offset")?, reader.f32("uv offset")?],
+        uv_scale: [reader.f32("uv scale")?, reader.f32("uv scale")?],
+        reserved: [reader.u32("reserved")?, reader.u32("reserved")?],
+    };
+    debug_assert_eq!(reader.offset, mem::size_of::<EncodeHeader>());
+    Ok(header)
+}
+
+/// Reads a mesh from an `OPTM` container: the header, the object table with material names,
+/// and the encoded vertex and index buffers, which are decoded into vertices of type `V`.
+///
+/// `V` must match the vertex type that was encoded (e.g. `PackedVertex`), since the container
+/// doesn't store the vertex size.
+pub fn decode_mesh<V: Clone + Default>(data: &[u8]) -> Result<DecodedMesh<V>> {
+    let mut reader = Reader { data, offset: 0 };
+    let header = read_header(&mut reader)?;
+
+    let mut objects: Vec<EncodeObject> = Vec::new();
+    for _ in 0..header.group_count {
+        objects.push(EncodeObject {
+            index_offset: reader.u32("object index offset")?,
+            index_count: reader.u32("object index count")?,
+            material_length: reader.u32("object material length")?,
+            reserved: reader.u32("object reserved")?,
+        });
+    }
+
+    let mut decoded_objects: Vec<DecodedObject> = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        let material = reader.bytes(object.material_length as usize, "material name")?;
+        let material = String::from_utf8(material.to_vec()).map_err(|err| {
+            Error::Parse(format!(
+                "material name of object {} is not UTF-8: {}",
+                i, err
+            ))
+        })?;
+        let index_offset = object.index_offset as usize;
+        let index_count = object.index_count as usize;
+        if index_offset + index_count > header.index_count as usize {
+            return Err(Error::Parse(format!(
+                "object {} index range {}..{} exceeds index count ({})",
+                i,
+                index_offset,
+                index_offset + index_count,
+                header.index_count
+            )));
+        }
+        decoded_objects.push(DecodedObject {
+            material,
+            index_offset,
+            index_count,
+        });
+    }
+
+    let vertex_data = reader.bytes(header.vertex_data_size as usize, "vertex data")?;
+    let index_data = reader.bytes(header.index_data_size as usize, "index data")?;
+
+    let vertices = try_decode_vertex_buffer::<V>(vertex_data, header.vertex_count as usize)?;
+    let indices = try_decode_index_buffer::<u32>(index_data, header.index_count as usize)?;
+    if let Some(index) = indices.iter().find(|&&index| index >= header.vertex_count) {
+        return Err(Error::Parse(format!(
+            "index ({}) must be less than vertex count ({})",
+            index, header.vertex_count
+        )));
+    }
+
+    Ok(DecodedMesh {
+        header,
+        objects: decoded_objects,
+        vertices,
+        indices,
+    })
+}
+
+/// Describes an error code returned by the native decoders.
+fn decode_failure(code: i32) -> &'static str {
+    match code {
+        -1 => "unsupported encoding version",
+        -2 => "encoded data is truncated or malformed",
+        -3 => "encoded data doesn't match the expected element count or size",
+        _ => "unknown decoder error",
+    }
+}
+
+/// Decodes index data like `decode_index_buffer`, validating the request against the size of
+/// the encoded data before allocating, which makes it suitable for untrusted input such as
+/// network-delivered assets.
+///
+/// The index codec needs at least one byte per triangle, so requests for more indices than
+/// the encoded data can possibly hold are rejected without allocating.
+pub fn decode_index_buffer_checked<T: Clone + Default>(
+    encoded: &[u8],
+    index_count: usize,
+) -> Result<Vec<T>> {
+    validate_index_size::<T>()?;
+    let index_size = mem::size_of::<T>();
+    if !index_count.is_multiple_of(3) {
+        return Err(Error::Parse(format!(
+            "index count ({}) must be a multiple of 3",
+            index_count
+        )));
+    }
+    if index_count / 3 > encoded.len() {
+        return Err(Error::Parse(format!(
+            "index count ({}) is too large for {} bytes of encoded data",
+            index_count,
+            encoded.len()
+        )));
+    }
+
+    let mut result = UninitBuffer::<T>::try_new(index_count)?;
+    let result_code = unsafe {
+        ffi::meshopt_decodeIndexBuffer(
+            result.as_mut_ptr().cast(),
+            index_count,
+            index_size,
+            encoded.as_ptr(),
+            encoded.len(),
+        )
+    };
+    error_or(result_code, (), "meshopt_decodeIndexBuffer", || {
+        format!(
+            "index_count: {}, index_size: {}, encoded_size: {}; {}",
+            index_count,
+            index_size,
+            encoded.len(),
+            decode_failure(result_code)
+        )
+    })?;
+    Ok(unsafe { result.assume_init(index_count) })
+}
+
+/// Decodes vertex data like `decode_vertex_buffer`, validating the request against the size
+/// of the encoded data before allocating, which makes it suitable for untrusted input such as
+/// network-delivered assets.
+///
+/// The vertex codec compresses data by at most a factor of 64, so requests for more vertex
+/// data than the encoded data can possibly hold are rejected without allocating.
+pub fn decode_vertex_buffer_checked<T: Clone + Default>(
+    encoded: &[u8],
+    vertex_count: usize,
+) -> Result<Vec<T>> {
+    let vertex_size = mem::size_of::<T>();
+    if vertex_size == 0 || vertex_size > 256 || !vertex_size.is_multiple_of(4) {
+        return Err(Error::Config(format!(
+            "vertex size ({}) must be a multiple of 4 in 4..=256",
+            vertex_size
+        )));
+    }
+    let fits = match vertex_count.checked_mul(vertex_size) {
+        Some(decoded_size) => decoded_size / 64 <= encoded.len(),
+        None => false,
+    };
+    if !fits {
+        return Err(Error::Parse(format!(
+            "vertex count ({}) is too large for {} bytes of encoded data",
+            vertex_count,
+            encoded.len()
+        )));
+    }
+
+    let mut result = UninitBuffer::<T>::try_new(vertex_count)?;
+    let result_code = unsafe {
+        ffi::meshopt_decodeVertexBuffer(
+            result.as_mut_ptr().cast(),
+            vertex_count,
+            vertex_size,
+            encoded.as_ptr(),
+            encoded.len(),
+        )
+    };
+    error_or(result_code, (), "meshopt_decodeVertexBuffer", || {
+        format!(
+            "vertex_count: {}, vertex_size: {}, encoded_size: {}; {}",
+            vertex_count,
+            vertex_size,
+            encoded.len(),
+            decode_failure(result_code)
+        )
+    })?;
+    Ok(unsafe { result.assume_init(vertex_count) })
+}
+
+/// Compression mode of a buffer view using the `EXT_meshopt_compression` glTF extension.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GltfCompressionMode {
+    /// Vertex attributes, decoded with the vertex codec.
+    Attributes,
+    /// Triangle list indices, decoded with the index codec.
+    Triangles,
+    /// Index sequences (e.g. for non-triangle primitives), decoded with the index sequence codec.
+    Indices,
+}
+
+impl FromStr for GltfCompressionMode {
+    type Err = Error;
+
+    /// Parses the `mode` property of the extension, e.g. `"ATTRIBUTES"`.
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "ATTRIBUTES" => Ok(Self::Attributes),
+            "TRIANGLES" => Ok(Self::Triangles),
+            "INDICES" => Ok(Self::Indices),
+            _ => Err(Error::Parse(format!(
+                "unknown compression mode \"{}\"",
+                mode
+            ))),
+        }
+    }
+}
+
+/// Filter applied to a buffer view using the `EXT_meshopt_compression` glTF extension.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum GltfCompressionFilter {
+    #[default]
+    None,
+    Octahedral,
+    Quaternion,
+    Exponential,
+}
+
+impl FromStr for GltfCompressionFilter {
+    type Err = Error;
+
+    /// Parses the `filter` property of the extension, e.g. `"OCTAHEDRAL"`.
+    fn from_str(filter: &str) -> Result<Self> {
+        match filter {
+            "NONE" => Ok(Self::None),
+            "OCTAHEDRAL" => Ok(Self::Octahedral),
+            "QUATERNION" => Ok(Self::Quaternion),
+            "EXPONENTIAL" => Ok(Self::Exponential),
+            _ => Err(Error::Parse(format!(
+                "unknown compression filter \"{}\"",
+                filter
+            ))),
+        }
+    }
+}
+
+/// Description of a buffer view compressed with the `EXT_meshopt_compression` glTF extension.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GltfCompressedBufferView {
+    pub mode: GltfCompressionMode,
+    pub filter: GltfCompressionFilter,
+    /// Size of each element in bytes.
+    pub byte_stride: usize,
+    /// Number of elements.
+    pub count: usize,
+}
+
+impl GltfCompressedBufferView {
+    fn validate(&self) -> Result<()> {
+        let stride = self.byte_stride;
+        let valid = match self.mode {
+            GltfCompressionMode::Attributes => {
+                stride.is_multiple_of(4) && (4..=256).contains(&stride)
+            }
+            GltfCompressionMode::Triangles => {
+                (stride == 2 || stride == 4) && self.count.is_multiple_of(3)
+            }
+            GltfCompressionMode::Indices => stride == 2 || stride == 4,
+        };
+        if !valid {
+            return Err(Error::Config(format!(
+                "invalid byte stride ({}) or count ({}) for {:?} mode",
+                stride, self.count, self.mode
+            )));
+        }
+
+        let valid = match self.filter {
+            GltfCompressionFilter::None => true,
+            _ if self.mode != GltfCompressionMode::Attributes => false,
+            GltfCompressionFilter::Octahedral => stride == 4 || stride == 8,
+            GltfCompressionFilter::Quaternion => stride == 8,
+            GltfCompressionFilter::Exponential => stride.is_multiple_of(4),
+        };
+        if !valid {
+            return Err(Error::Config(format!(
+                "{:?} filter can't be used with {:?} mode and byte stride {}",
+                self.filter, self.mode, stride
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a buffer view compressed with the `EXT_meshopt_compression` glTF extension,
+/// returning `count * byte_stride` bytes of uncompressed data.
+///
+/// `source` is the compressed data referenced by the extension (`buffer`, `byteOffset`
+/// and `byteLength`). The decoders are safe to use for untrusted input.
+pub fn decode_gltf_buffer_view(source: &[u8], view: &GltfCompressedBufferView) -> Result<Vec<u8>> {
+    view.validate()?;
+    let size = view
+        .count
+        .checked_mul(view.byte_stride)
+        .ok_or_else(|| Error::Config("buffer view size overflows".into()))?;
+
+    // reject requests the compressed data can't possibly hold before allocating, like
+    // `decode_index_buffer_checked` and `decode_vertex_buffer_checked`: the vertex codec
+    // compresses by at most a factor of 64, and the index codecs need at least one byte per
+    // triangle or index
+    let fits = match view.mode {
+        GltfCompressionMode::Attributes => size / 64 <= source.len(),
+        GltfCompressionMode::Triangles => view.count / 3 <= source.len(),
+        GltfCompressionMode::Indices => view.count <= source.len(),
+    };
+    if !fits {
+        return Err(Error::Parse(format!(
+            "count ({}) is too large for {} bytes of compressed data",
+            view.count,
+            source.len()
+        )));
+    }
+
+    // decode into 4-byte aligned storage, since the decoders and filters access the data
+    // as 16 and 32-bit values
+    let mut result: Vec<u32> = Vec::new();
+    try_reserve(&mut result, size / 4 + 1)?;
+    result.resize(size / 4 + 1, 0);
+    let result_code = unsafe {
+        match view.mode {
+            GltfCompressionMode::Attributes => ffi::meshopt_decodeVertexBuffer(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+                source.as_ptr(),
+                source.len(),
+            ),
+            GltfCompressionMode::Triangles => ffi::meshopt_decodeIndexBuffer(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+                source.as_ptr(),
+                source.len(),
+            ),
+            GltfCompressionMode::Indices => ffi::meshopt_decodeIndexSequence(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+                source.as_ptr(),
+                source.len(),
+            ),
+        }
+    };
+    let function = match view.mode {
+        GltfCompressionMode::Attributes => "meshopt_decodeVertexBuffer",
+        GltfCompressionMode::Triangles => "meshopt_decodeIndexBuffer",
+        GltfCompressionMode::Indices => "meshopt_decodeIndexSequence",
+    };
+    let mut result = error_or(result_code, result, function, || {
+        format!(
+            "count: {}, byte_stride: {}, source_size: {}; {}",
+            view.count,
+            view.byte_stride,
+            source.len(),
+            decode_failure(result_code)
+        )
+    })?;
+
+    unsafe {
+        match view.filter {
+            GltfCompressionFilter::None => {}
+            GltfCompressionFilter::Octahedral => ffi::meshopt_decodeFilterOct(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+            ),
+            GltfCompressionFilter::Quaternion => ffi::meshopt_decodeFilterQuat(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+            ),
+            GltfCompressionFilter::Exponential => ffi::meshopt_decodeFilterExp(
+                result.as_mut_ptr().cast(),
+                view.count,
+                view.byte_stride,
+            ),
+        }
+    }
+    Ok(typed_to_bytes(&result)[..size].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        any_as_u8_slice, decode_mesh, encode_index_buffer, encode_vertex_buffer, EncodeHeader,
+        EncodeObject,
+    };
+
+    fn encode_container(vertices: &[[u16; 4]], indices: &[u32], material: &str) -> Vec<u8> {
+        let encoded_vertices = encode_vertex_buffer(vertices).unwrap();
+        let encoded_indices = encode_index_buffer(indices, vertices.len()).unwrap();
+        let header = EncodeHeader {
+            magic: *b"OPTM",
+            group_count: 1,
+            vertex_count: vertices.len() as u32,
+            index_count: indices.len() as u32,
+            vertex_data_size: encoded_vertices.len() as u32,
+            index_data_size: encoded_indices.len() as u32,
+            pos_offset: [0f32; 3],
+            pos_scale: 1f32,
+            uv_offset: [0f32; 2],
+            uv_scale: [1f32; 2],
+            reserved: [0, 0],
+        };
+        let object = EncodeObject {
+            index_offset: 0,
+            index_count: indices.len() as u32,
+            material_length: material.len() as u32,
+            reserved: 0,
+        };
+
+        let mut data = Vec::new();
+        data.extend_from_slice(any_as_u8_slice(&header));
+        data.extend_from_slice(any_as_u8_slice(&object));
+        data.extend_from_slice(material.as_bytes());
+        data.extend_from_slice(&encoded_vertices);
+        data.extend_from_slice(&encoded_indices);
+        data
+    }
+
+    fn grid() -> (Vec<[u16; 4]>, Vec<u32>) {
+        let vertices: Vec<[u16; 4]> = (0..16u16).map(|i| [i % 4, i / 4, 0, 0]).collect();
+        let mut indices: Vec<u32> = Vec::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                let i = y * 4 + x;
+                indices.extend_from_slice(&[i, i + 1, i + 4, i + 4, i + 1, i + 5]);
+            }
+        }
+        (vertices, indices)
+    }
+
+    #[test]
+    fn test_decode_mesh_round_trip() {
+        let (vertices, indices) = grid();
+        let data = encode_container(&vertices, &indices, "stone");
+
+        let mesh = decode_mesh::<[u16; 4]>(&data).unwrap();
+        assert_eq!(mesh.vertices, vertices);
+        assert_eq!(mesh.indices, indices);
+        assert_eq!(mesh.objects.len(), 1);
+        assert_eq!(mesh.objects[0].material, "stone");
+        assert_eq!(mesh.objects[0].index_count, indices.len());
+    }
+
+    #[test]
+    fn test_decode_mesh_invalid() {
+        let (vertices, indices) = grid();
+        let data = encode_container(&vertices, &indices, "stone");
+
+        for length in 0..data.len() {
+            assert!(decode_mesh::<[u16; 4]>(&data[..length]).is_err());
+        }
+
+        let mut corrupt = data.clone();
+        corrupt[0] = b'X';
+        assert!(decode_mesh::<[u16; 4]>(&corrupt).is_err());
+
+        // object range past the end of the index buffer
+        let mut corrupt = data.clone();
+        let index_count_offset = std::mem::size_of::<EncodeHeader>() + 4;
+        corrupt[index_count_offset..index_count_offset + 4]
+            .copy_from_slice(&(indices.len() as u32 + 3).to_ne_bytes());
+        assert!(decode_mesh::<[u16; 4]>(&corrupt).is_err());
+
+        // garbage in the encoded buffers may decode, but must never panic
+        for offset in std::mem::size_of::<EncodeHeader>()..data.len() {
+            let mut corrupt = data.clone();
+            corrupt[offset] ^= 0xa5;
+            let _ = decode_mesh::<[u16; 4]>(&corrupt);
+        }
+    }
+}
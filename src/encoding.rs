@@ -1,5 +1,11 @@
-use crate::{error_or, ffi, utilities::rcp_safe, Result};
-use std::mem;
+use crate::{
+    error_or, ffi, typed_to_bytes,
+    utilities::{rcp_safe, UninitBuffer},
+    Error, Index, Result,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::mem::{self, MaybeUninit};
 
 /// Encodes index data into an array of bytes that is generally much smaller (<1.5 bytes/triangle)
 /// and compresses better (<1 bytes/triangle) compared to original.
@@ -7,37 +13,82 @@ use std::mem;
 /// For maximum efficiency the index buffer being encoded has to be optimized for vertex cache and
 /// vertex fetch first.
 pub fn encode_index_buffer(indices: &[u32], vertex_count: usize) -> Result<Vec<u8>> {
-    let bounds = unsafe { ffi::meshopt_encodeIndexBufferBound(indices.len(), vertex_count) };
-    let mut result: Vec<u8> = vec![0; bounds];
+    let mut result: Vec<u8> = vec![0; encode_index_buffer_bound(indices.len(), vertex_count)];
+    let size = encode_index_buffer_into(indices, vertex_count, &mut result)?;
+    result.resize(size, 0u8);
+    Ok(result)
+}
+
+/// Returns the maximum size of the data produced by `encode_index_buffer`.
+pub fn encode_index_buffer_bound(index_count: usize, vertex_count: usize) -> usize {
+    unsafe { ffi::meshopt_encodeIndexBufferBound(index_count, vertex_count) }
+}
+
+/// Encodes index data like `encode_index_buffer`, writing into `destination` and returning the
+/// number of bytes written.
+///
+/// Fails if `destination` is too small; `encode_index_buffer_bound` returns a size that is
+/// always large enough.
+pub fn encode_index_buffer_into(
+    indices: &[u32],
+    vertex_count: usize,
+    destination: &mut [u8],
+) -> Result<usize> {
     let size = unsafe {
         ffi::meshopt_encodeIndexBuffer(
-            result.as_mut_ptr(),
-            result.len(),
+            destination.as_mut_ptr(),
+            destination.len(),
             indices.as_ptr(),
             indices.len(),
         )
     };
-    result.resize(size, 0u8);
-    Ok(result)
+    if size == 0 {
+        return Err(Error::memory_dynamic(format!(
+            "destination buffer ({} bytes) is too small, up to {} bytes are required",
+            destination.len(),
+            encode_index_buffer_bound(indices.len(), vertex_count)
+        )));
+    }
+    Ok(size)
 }
 
 /// Decodes index data from an array of bytes generated by `encode_index_buffer`.
 /// The decoder is safe to use for untrusted input, but it may produce garbage
 /// data (e.g. out of range indices).
+///
+/// Fails with `Error::Config` if `T` isn't 2 or 4 bytes wide.
 pub fn decode_index_buffer<T: Clone + Default + Sized>(
     encoded: &[u8],
     index_count: usize,
 ) -> Result<Vec<T>> {
-    const fn assert_valid_size<T: Sized>() {
-        assert!(
-            mem::size_of::<T>() == 2 || mem::size_of::<T>() == 4,
-            "size of result type must be 2 or 4 bytes wide"
-        );
-    }
+    validate_index_size::<T>()?;
+    decode_index_buffer_with(encoded, UninitBuffer::new(index_count))
+}
 
-    assert_valid_size::<T>();
+/// Same as `decode_index_buffer`, but returns `Error::Memory` instead of aborting if the
+/// result for `index_count` indices can't be allocated, e.g. for counts read from
+/// untrusted content.
+pub fn try_decode_index_buffer<T: Clone + Default + Sized>(
+    encoded: &[u8],
+    index_count: usize,
+) -> Result<Vec<T>> {
+    validate_index_size::<T>()?;
+    decode_index_buffer_with(encoded, UninitBuffer::try_new(index_count)?)
+}
 
-    let mut result: Vec<T> = vec![Default::default(); index_count];
+pub(crate) fn validate_index_size<T>() -> Result<()> {
+    let index_size = mem::size_of::<T>();
+    if index_size != 2 && index_size != 4 {
+        return Err(Error::Config(format!(
+            "index size must be 2 or 4 bytes wide, got {}",
+            index_size
+        )));
+    }
+    Ok(())
+}
+
+fn decode_index_buffer_with<T>(encoded: &[u8], mut result: UninitBuffer<T>) -> Result<Vec<T>> {
+    let index_count = result.len();
     let result_code = unsafe {
         ffi::meshopt_decodeIndexBuffer(
             result.as_mut_ptr().cast(),
@@ -48,7 +99,15 @@ pub fn decode_index_buffer<T: Clone + Default + Sized>(
         )
     };
 
-    error_or(result_code, result)
+    error_or(result_code, (), "meshopt_decodeIndexBuffer", || {
+        format!(
+            "index_count: {}, index_size: {}, encoded_size: {}",
+            index_count,
+            mem::size_of::<T>(),
+            encoded.len()
+        )
+    })?;
+    Ok(unsafe { result.assume_init(index_count) })
 }
 
 /// Encodes vertex data into an array of bytes that is generally smaller and compresses better
@@ -57,20 +116,41 @@ pub fn decode_index_buffer<T: Clone + Default + Sized>(
 /// This function works for a single vertex stream; for multiple vertex streams,
 /// call `encode_vertex_buffer` for each stream.
 pub fn encode_vertex_buffer<T>(vertices: &[T]) -> Result<Vec<u8>> {
-    let bounds =
-        unsafe { ffi::meshopt_encodeVertexBufferBound(vertices.len(), mem::size_of::<T>()) };
-    let mut result: Vec<u8> = vec![0; bounds];
+    let mut result: Vec<u8> =
+        vec![0; encode_vertex_buffer_bound(vertices.len(), mem::size_of::<T>())];
+    let size = encode_vertex_buffer_into(vertices, &mut result)?;
+    result.resize(size, 0u8);
+    Ok(result)
+}
+
+/// Returns the maximum size of the data produced by `encode_vertex_buffer`.
+pub fn encode_vertex_buffer_bound(vertex_count: usize, vertex_size: usize) -> usize {
+    unsafe { ffi::meshopt_encodeVertexBufferBound(vertex_count, vertex_size) }
+}
+
+/// Encodes vertex data like `encode_vertex_buffer`, writing into `destination` and returning the
+/// number of bytes written.
+///
+/// Fails if `destination` is too small; `encode_vertex_buffer_bound` returns a size that is
+/// always large enough.
+pub fn encode_vertex_buffer_into<T>(vertices: &[T], destination: &mut [u8]) -> Result<usize> {
     let size = unsafe {
         ffi::meshopt_encodeVertexBuffer(
-            result.as_mut_ptr(),
-            result.len(),
+            destination.as_mut_ptr(),
+            destination.len(),
             vertices.as_ptr().cast(),
             vertices.len(),
             mem::size_of::<T>(),
         )
     };
-    result.resize(size, 0u8);
-    Ok(result)
+    if size == 0 {
+        return Err(Error::memory_dynamic(format!(
+            "destination buffer ({} bytes) is too small, up to {} bytes are required",
+            destination.len(),
+            encode_vertex_buffer_bound(vertices.len(), mem::size_of::<T>())
+        )));
+    }
+    Ok(size)
 }
 
 /// Decodes vertex data from an array of bytes generated by `encode_vertex_buffer`.
@@ -79,7 +159,20 @@ pub fn decode_vertex_buffer<T: Clone + Default>(
     encoded: &[u8],
     vertex_count: usize,
 ) -> Result<Vec<T>> {
-    let mut result: Vec<T> = vec![Default::default(); vertex_count];
+    decode_vertex_buffer_with(encoded, UninitBuffer::new(vertex_count))
+}
+
+/// Same as `decode_vertex_buffer`, but returns `Error::Memory` instead of aborting if the
+/// result for `vertex_count` vertices can't be allocated.
+pub fn try_decode_vertex_buffer<T: Clone + Default>(
+    encoded: &[u8],
+    vertex_count: usize,
+) -> Result<Vec<T>> {
+    decode_vertex_buffer_with(encoded, UninitBuffer::try_new(vertex_count)?)
+}
+
+fn decode_vertex_buffer_with<T>(encoded: &[u8], mut result: UninitBuffer<T>) -> Result<Vec<T>> {
+    let vertex_count = result.len();
     let result_code = unsafe {
         ffi::meshopt_decodeVertexBuffer(
             result.as_mut_ptr().cast(),
@@ -90,11 +183,763 @@ pub fn decode_vertex_buffer<T: Clone + Default>(
         )
     };
 
-    error_or(result_code, result)
+    error_or(result_code, (), "meshopt_decodeVertexBuffer", || {
+        format!(
+            "vertex_count: {}, vertex_size: {}, encoded_size: {}",
+            vertex_count,
+            mem::size_of::<T>(),
+            encoded.len()
+        )
+    })?;
+    Ok(unsafe { result.assume_init(vertex_count) })
+}
+
+/// SIMD instruction set used by the vertex decoder and filters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CodecSimd {
+    /// SSSE3 (and SSE4.1 for the filters) on x86.
+    Sse,
+    /// AVX-512 (VBMI, VBMI2 and VL) on x86.
+    Avx512,
+    /// NEON on ARM.
+    Neon,
+    /// SIMD128 on WebAssembly.
+    WasmSimd128,
+}
+
+/// Codec features compiled into the native library, see `codec_features`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CodecFeatures {
+    /// SIMD path of the vertex decoder and filters, or `None` if only the scalar path was
+    /// compiled in.
+    pub simd: Option<CodecSimd>,
+    /// Whether the SIMD path is only taken if the CPU supports it, falling back to the
+    /// scalar path otherwise.
+    pub runtime_dispatch: bool,
+}
+
+/// Reports the codec features compiled into the native library.
+///
+/// The SIMD path is selected at build time from the target and the `simd_sse`, `simd_avx`,
+/// `simd_neon`, `simd_wasm` and `no_simd` features; e.g. WebAssembly builds decode with
+/// scalar code unless built with `simd_wasm` (or `-C target-feature=+simd128`).
+pub fn codec_features() -> CodecFeatures {
+    let (simd, runtime_dispatch) = match env!("MESHOPT_CODEC_SIMD") {
+        "sse" => (Some(CodecSimd::Sse), false),
+        "sse_dispatch" => (Some(CodecSimd::Sse), true),
+        "avx512" => (Some(CodecSimd::Avx512), false),
+        "neon" => (Some(CodecSimd::Neon), false),
+        "wasm_simd128" => (Some(CodecSimd::WasmSimd128), false),
+        _ => (None, false),
+    };
+    CodecFeatures {
+        simd,
+        runtime_dispatch,
+    }
+}
+
+/// Estimated size of an encoded buffer, as returned by `estimate_index_buffer_size` and
+/// `estimate_vertex_buffer_size`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct EncodedSizeEstimate {
+    /// Estimated size of the encoded buffer in bytes.
+    pub bytes: usize,
+    /// Estimated bits per element: per triangle for index buffers, per vertex for vertex buffers.
+    pub bits_per_element: f32,
+    /// Whether the whole buffer was encoded, in which case the estimate is exact.
+    pub exact: bool,
+}
+
+impl EncodedSizeEstimate {
+    fn extrapolate(sample_bytes: usize, sample_count: usize, total_count: usize) -> Self {
+        if sample_count == 0 {
+            return Self {
+                bytes: sample_bytes,
+                bits_per_element: 0f32,
+                exact: total_count == 0,
+            };
+        }
+        let bits_per_element = (sample_bytes * 8) as f32 / sample_count as f32;
+        Self {
+            bytes: (sample_bytes as f64 * total_count as f64 / sample_count as f64).ceil() as usize,
+            bits_per_element,
+            exact: sample_count == total_count,
+        }
+    }
+}
+
+/// Estimates the size of `encode_index_buffer` output by encoding at most the first
+/// `max_triangles` triangles and extrapolating, which is much faster than encoding large
+/// meshes when evaluating many candidate formats.
+///
+/// The estimate assumes the sampled prefix is representative of the whole buffer.
+pub fn estimate_index_buffer_size(
+    indices: &[u32],
+    vertex_count: usize,
+    max_triangles: usize,
+) -> Result<EncodedSizeEstimate> {
+    let triangle_count = indices.len() / 3;
+    let sample_count = triangle_count.min(max_triangles);
+    let encoded = encode_index_buffer(&indices[..sample_count * 3], vertex_count)?;
+    Ok(EncodedSizeEstimate::extrapolate(
+        encoded.len(),
+        sample_count,
+        triangle_count,
+    ))
+}
+
+/// Estimates the size of `encode_vertex_buffer` output by encoding at most the first
+/// `max_vertices` vertices and extrapolating, which is much faster than encoding large
+/// meshes when evaluating many candidate formats.
+///
+/// The estimate assumes the sampled prefix is representative of the whole buffer.
+pub fn estimate_vertex_buffer_size<T>(
+    vertices: &[T],
+    max_vertices: usize,
+) -> Result<EncodedSizeEstimate> {
+    let sample_count = vertices.len().min(max_vertices);
+    let encoded = encode_vertex_buffer(&vertices[..sample_count])?;
+    Ok(EncodedSizeEstimate::extrapolate(
+        encoded.len(),
+        sample_count,
+        vertices.len(),
+    ))
+}
+
+fn validate_vertex_size(vertex_size: usize) -> Result<()> {
+    if vertex_size == 0 || vertex_size > 256 || !vertex_size.is_multiple_of(4) {
+        return Err(Error::Config(format!(
+            "vertex_size ({}) must be a multiple of 4 in 4..=256",
+            vertex_size
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the size in bytes of `vertex_count` vertices of `vertex_size` bytes, checking both.
+fn raw_buffer_size(vertex_count: usize, vertex_size: usize) -> Result<usize> {
+    validate_vertex_size(vertex_size)?;
+    vertex_count.checked_mul(vertex_size).ok_or_else(|| {
+        Error::Config(format!(
+            "vertex_count ({}) * vertex_size ({}) overflows",
+            vertex_count, vertex_size
+        ))
+    })
+}
+
+/// Encodes vertex data like `encode_vertex_buffer`, for `vertex_count` vertices of
+/// `vertex_size` bytes each stored in `vertices`.
+///
+/// This allows encoding interleaved buffers with padding or runtime-defined vertex formats
+/// without repacking them into a typed slice. `vertex_size` must be a multiple of 4 and <= 256.
+pub fn encode_vertex_buffer_raw(
+    vertices: &[u8],
+    vertex_count: usize,
+    vertex_size: usize,
+) -> Result<Vec<u8>> {
+    let size = raw_buffer_size(vertex_count, vertex_size)?;
+    if vertices.len() != size {
+        return Err(Error::Config(format!(
+            "vertex data length ({}) must be vertex_count ({}) * vertex_size ({})",
+            vertices.len(),
+            vertex_count,
+            vertex_size
+        )));
+    }
+
+    let mut result: Vec<u8> = vec![0; encode_vertex_buffer_bound(vertex_count, vertex_size)];
+    let size = unsafe {
+        ffi::meshopt_encodeVertexBuffer(
+            result.as_mut_ptr(),
+            result.len(),
+            vertices.as_ptr().cast(),
+            vertex_count,
+            vertex_size,
+        )
+    };
+    result.resize(size, 0u8);
+    Ok(result)
+}
+
+/// Decodes vertex data from an array of bytes generated by `encode_vertex_buffer` or
+/// `encode_vertex_buffer_raw`, returning `vertex_count * vertex_size` bytes.
+/// The decoder is safe to use for untrusted input, but it may produce garbage data.
+pub fn decode_vertex_buffer_raw(
+    encoded: &[u8],
+    vertex_count: usize,
+    vertex_size: usize,
+) -> Result<Vec<u8>> {
+    let size = raw_buffer_size(vertex_count, vertex_size)?;
+    decode_vertex_buffer_raw_with(encoded, vertex_size, UninitBuffer::new(size))
+}
+
+/// Same as `decode_vertex_buffer_raw`, but returns `Error::Memory` instead of aborting if
+/// the result can't be allocated.
+pub fn try_decode_vertex_buffer_raw(
+    encoded: &[u8],
+    vertex_count: usize,
+    vertex_size: usize,
+) -> Result<Vec<u8>> {
+    let size = raw_buffer_size(vertex_count, vertex_size)?;
+    decode_vertex_buffer_raw_with(encoded, vertex_size, UninitBuffer::try_new(size)?)
+}
+
+fn decode_vertex_buffer_raw_with(
+    encoded: &[u8],
+    vertex_size: usize,
+    mut result: UninitBuffer<u8>,
+) -> Result<Vec<u8>> {
+    let vertex_count = result.len() / vertex_size;
+    let result_code = unsafe {
+        ffi::meshopt_decodeVertexBuffer(
+            result.as_mut_ptr().cast(),
+            vertex_count,
+            vertex_size,
+            encoded.as_ptr(),
+            encoded.len(),
+        )
+    };
+
+    error_or(result_code, (), "meshopt_decodeVertexBuffer", || {
+        format!(
+            "vertex_count: {}, vertex_size: {}, encoded_size: {}",
+            vertex_count,
+            vertex_size,
+            encoded.len()
+        )
+    })?;
+    let size = result.len();
+    Ok(unsafe { result.assume_init(size) })
+}
+
+/// Vertex buffer split into chunks that are encoded independently of each other, so they
+/// can be encoded and decoded on multiple threads, see `encode_vertex_buffer_chunked`.
+///
+/// Every chunk but the last holds `chunk_vertices` vertices. Chunked buffers compress
+/// slightly worse than a single encoded buffer, since the encoder can't exploit the
+/// similarity of vertices across chunk boundaries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkedVertexBuffer {
+    pub vertex_count: usize,
+    pub vertex_size: usize,
+    pub chunk_vertices: usize,
+    /// Size of every encoded chunk in bytes.
+    pub chunk_sizes: Vec<usize>,
+    /// The encoded chunks, back to back.
+    pub data: Vec<u8>,
+}
+
+impl ChunkedVertexBuffer {
+    /// Serializes the buffer as a manifest followed by the encoded chunks.
+    ///
+    /// The manifest consists of the vertex count, vertex size, vertices per chunk, chunk
+    /// count and the size of every chunk, as little-endian `u32` values.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let header = [
+            self.vertex_count,
+            self.vertex_size,
+            self.chunk_vertices,
+            self.chunk_sizes.len(),
+        ];
+        let mut result =
+            Vec::with_capacity((header.len() + self.chunk_sizes.len()) * 4 + self.data.len());
+        for &value in header.iter().chain(&self.chunk_sizes) {
+            let value = u32::try_from(value).map_err(|err| {
+                Error::Config(format!("manifest value {} exceeds u32: {}", value, err))
+            })?;
+            result.extend_from_slice(&value.to_le_bytes());
+        }
+        result.extend_from_slice(&self.data);
+        Ok(result)
+    }
+
+    /// Parses a buffer serialized with `to_bytes`, checking that the manifest is consistent
+    /// with the encoded data.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let truncated = || Error::Parse("chunked vertex buffer manifest is truncated".into());
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize);
+        let mut next = || words.next().ok_or_else(truncated);
+
+        let vertex_count = next()?;
+        let vertex_size = next()?;
+        let chunk_vertices = next()?;
+        let chunk_count = next()?;
+        if chunk_count > bytes.len() / 4 {
+            return Err(truncated());
+        }
+        let chunk_sizes = (0..chunk_count)
+            .map(|_| next())
+            .collect::<Result<Vec<usize>>>()?;
+        let manifest_size = (4 + chunk_count) * 4;
+
+        let chunked = Self {
+            vertex_count,
+            vertex_size,
+            chunk_vertices,
+            chunk_sizes,
+            data: bytes[manifest_size..].to_vec(),
+        };
+        chunked.validate()?;
+        Ok(chunked)
+    }
+
+    /// Checks that the chunk count matches the vertex count and that the chunk sizes add up
+    /// to the size of the encoded data.
+    pub fn validate(&self) -> Result<()> {
+        validate_vertex_size(self.vertex_size)?;
+        if self.chunk_vertices == 0 {
+            return Err(Error::Parse("chunk_vertices must not be 0".into()));
+        }
+        let chunk_count = self.vertex_count / self.chunk_vertices
+            + usize::from(!self.vertex_count.is_multiple_of(self.chunk_vertices));
+        if self.chunk_sizes.len() != chunk_count {
+            return Err(Error::Parse(format!(
+                "{} vertices in chunks of {} require {} chunks, found {}",
+                self.vertex_count,
+                self.chunk_vertices,
+                chunk_count,
+                self.chunk_sizes.len()
+            )));
+        }
+        let total = self
+            .chunk_sizes
+            .iter()
+            .try_fold(0usize, |total, &size| total.checked_add(size));
+        if total != Some(self.data.len()) {
+            return Err(Error::Parse(format!(
+                "chunk sizes don't add up to the encoded size ({} bytes)",
+                self.data.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the encoded chunks; the buffer must be valid.
+    fn chunks(&self) -> Vec<&[u8]> {
+        let mut data = self.data.as_slice();
+        self.chunk_sizes
+            .iter()
+            .map(|&size| {
+                let (chunk, rest) = data.split_at(size);
+                data = rest;
+                chunk
+            })
+            .collect()
+    }
+
+    fn from_chunks<T>(vertex_count: usize, chunk_vertices: usize, chunks: Vec<Vec<u8>>) -> Self {
+        Self {
+            vertex_count,
+            vertex_size: mem::size_of::<T>(),
+            chunk_vertices,
+            chunk_sizes: chunks.iter().map(Vec::len).collect(),
+            data: chunks.concat(),
+        }
+    }
+}
+
+/// Encodes `vertices` in independently encoded chunks of `chunk_vertices` vertices, see
+/// `ChunkedVertexBuffer`.
+///
+/// Use `par_encode_vertex_buffer_chunked` (with the `rayon` feature) to encode the chunks in
+/// parallel. Chunks of tens of thousands of vertices keep the loss in compression small.
+pub fn encode_vertex_buffer_chunked<T>(
+    vertices: &[T],
+    chunk_vertices: usize,
+) -> Result<ChunkedVertexBuffer> {
+    validate_chunk_parameters::<T>(chunk_vertices)?;
+    let chunks = vertices
+        .chunks(chunk_vertices)
+        .map(encode_vertex_chunk)
+        .collect::<Result<Vec<Vec<u8>>>>()?;
+    Ok(ChunkedVertexBuffer::from_chunks::<T>(
+        vertices.len(),
+        chunk_vertices,
+        chunks,
+    ))
+}
+
+/// Same as `encode_vertex_buffer_chunked`, encoding the chunks on the rayon thread pool.
+#[cfg(feature = "rayon")]
+pub fn par_encode_vertex_buffer_chunked<T: Sync>(
+    vertices: &[T],
+    chunk_vertices: usize,
+) -> Result<ChunkedVertexBuffer> {
+    validate_chunk_parameters::<T>(chunk_vertices)?;
+    let chunks = vertices
+        .par_chunks(chunk_vertices)
+        .map(encode_vertex_chunk)
+        .collect::<Result<Vec<Vec<u8>>>>()?;
+    Ok(ChunkedVertexBuffer::from_chunks::<T>(
+        vertices.len(),
+        chunk_vertices,
+        chunks,
+    ))
+}
+
+/// Decodes a vertex buffer encoded with `encode_vertex_buffer_chunked`.
+/// The decoder is safe to use for untrusted input, but it may produce garbage data.
+///
+/// Use `par_decode_vertex_buffer_chunked` (with the `rayon` feature) to decode the chunks
+/// in parallel.
+pub fn decode_vertex_buffer_chunked<T: Clone + Default>(
+    chunked: &ChunkedVertexBuffer,
+) -> Result<Vec<T>> {
+    let mut result = prepare_chunked_decode::<T>(chunked)?;
+    result
+        .as_uninit_mut()
+        .chunks_mut(chunked.chunk_vertices)
+        .zip(chunked.chunks())
+        .try_for_each(|(destination, encoded)| decode_vertex_chunk(encoded, destination))?;
+    Ok(unsafe { result.assume_init(chunked.vertex_count) })
+}
+
+/// Same as `decode_vertex_buffer_chunked`, decoding the chunks on the rayon thread pool.
+#[cfg(feature = "rayon")]
+pub fn par_decode_vertex_buffer_chunked<T: Clone + Default + Send>(
+    chunked: &ChunkedVertexBuffer,
+) -> Result<Vec<T>> {
+    let mut result = prepare_chunked_decode::<T>(chunked)?;
+    result
+        .as_uninit_mut()
+        .par_chunks_mut(chunked.chunk_vertices)
+        .zip(chunked.chunks())
+        .try_for_each(|(destination, encoded)| decode_vertex_chunk(encoded, destination))?;
+    Ok(unsafe { result.assume_init(chunked.vertex_count) })
+}
+
+fn validate_chunk_parameters<T>(chunk_vertices: usize) -> Result<()> {
+    validate_vertex_size(mem::size_of::<T>())?;
+    if chunk_vertices == 0 {
+        return Err(Error::Config("chunk_vertices must not be 0".into()));
+    }
+    Ok(())
+}
+
+fn encode_vertex_chunk<T>(chunk: &[T]) -> Result<Vec<u8>> {
+    encode_vertex_buffer_raw(typed_to_bytes(chunk), chunk.len(), mem::size_of::<T>())
+}
+
+fn prepare_chunked_decode<T>(chunked: &ChunkedVertexBuffer) -> Result<UninitBuffer<T>> {
+    chunked.validate()?;
+    if chunked.vertex_size != mem::size_of::<T>() {
+        return Err(Error::Config(format!(
+            "vertex size of the buffer ({}) doesn't match the size of the vertex type ({})",
+            chunked.vertex_size,
+            mem::size_of::<T>()
+        )));
+    }
+    UninitBuffer::try_new(chunked.vertex_count)
+}
+
+fn decode_vertex_chunk<T>(encoded: &[u8], destination: &mut [MaybeUninit<T>]) -> Result<()> {
+    let result_code = unsafe {
+        ffi::meshopt_decodeVertexBuffer(
+            destination.as_mut_ptr().cast(),
+            destination.len(),
+            mem::size_of::<T>(),
+            encoded.as_ptr(),
+            encoded.len(),
+        )
+    };
+    error_or(result_code, (), "meshopt_decodeVertexBuffer", || {
+        format!(
+            "vertex_count: {}, vertex_size: {}, encoded_size: {}",
+            destination.len(),
+            mem::size_of::<T>(),
+            encoded.len()
+        )
+    })
+}
+
+/// Checks that `decoded` (as returned by `decode_index_buffer`) matches the `original`
+/// indices passed to `encode_index_buffer`.
+///
+/// The index codec may rotate the vertices of a triangle to compress better, so each
+/// decoded triangle only has to match a rotation of the original one; winding order is
+/// preserved. Returns an error describing the first mismatching triangle.
+pub fn verify_index_roundtrip<T: Index, U: Index>(original: &[T], decoded: &[U]) -> Result<()> {
+    if original.len() != decoded.len() || !original.len().is_multiple_of(3) {
+        return Err(Error::Parse(format!(
+            "decoded index count ({}) must match the original index count ({}), a multiple of 3",
+            decoded.len(),
+            original.len()
+        )));
+    }
+    let triangles = original.chunks_exact(3).zip(decoded.chunks_exact(3));
+    for (triangle, (a, b)) in triangles.enumerate() {
+        let a = [a[0].to_u32(), a[1].to_u32(), a[2].to_u32()];
+        let b = [b[0].to_u32(), b[1].to_u32(), b[2].to_u32()];
+        if b != a && b != [a[1], a[2], a[0]] && b != [a[2], a[0], a[1]] {
+            return Err(Error::Parse(format!(
+                "triangle {} decoded as {:?}, expected a rotation of {:?}",
+                triangle, b, a
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `decoded` (as returned by `decode_vertex_buffer`) is byte-identical to the
+/// `original` vertices passed to `encode_vertex_buffer`.
+///
+/// The vertex codec is lossless, so any difference indicates corrupted data. `T` must not
+/// contain padding, as padding bytes are not guaranteed to survive the roundtrip. Returns an
+/// error describing the first mismatching vertex.
+pub fn verify_vertex_roundtrip<T>(original: &[T], decoded: &[T]) -> Result<()> {
+    if original.len() != decoded.len() {
+        return Err(Error::Parse(format!(
+            "decoded vertex count ({}) must match the original vertex count ({})",
+            decoded.len(),
+            original.len()
+        )));
+    }
+    let vertex_size = mem::size_of::<T>().max(1);
+    let original = typed_to_bytes(original).chunks(vertex_size);
+    let decoded = typed_to_bytes(decoded).chunks(vertex_size);
+    match original.zip(decoded).position(|(a, b)| a != b) {
+        Some(vertex) => Err(Error::Parse(format!(
+            "vertex {} differs from the original vertex",
+            vertex
+        ))),
+        None => Ok(()),
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Element types the vertex filters read and write as raw bytes: the 8, 16 and 32-bit
+/// integer types, `f32`, and arrays of them.
+///
+/// These types have no padding and are valid for any bit pattern, which the filters rely on.
+pub trait FilterElement: Copy + private::Sealed {}
+
+macro_rules! impl_filter_element {
+    ($($ty:ty),*) => {
+        $(
+            impl private::Sealed for $ty {}
+            impl FilterElement for $ty {}
+        )*
+    };
+}
+
+impl_filter_element!(i8, u8, i16, u16, i32, u32, f32);
+
+impl<T: FilterElement, const N: usize> private::Sealed for [T; N] {}
+impl<T: FilterElement, const N: usize> FilterElement for [T; N] {}
+
+/// Encodes unit vectors (normals, tangents) using octahedral encoding with K-bit (K <= 16)
+/// signed X/Y, which compresses much better through `encode_vertex_buffer` than plain
+/// quantized vectors.
+///
+/// Each input vector takes 4 floats; W (e.g. tangent sign) is quantized to K bits as well,
+/// and isn't rescaled by `decode_filter_oct`.
+/// Each output component is stored as an 8-bit or 16-bit normalized integer, so the
+/// result type must be 4 bytes (e.g. `[i8; 4]`) or 8 bytes (e.g. `[i16; 4]`) wide,
+/// and `bits` must not exceed the component width.
+pub fn encode_filter_oct<T: FilterElement>(vectors: &[[f32; 4]], bits: u32) -> Result<Vec<T>> {
+    let stride = mem::size_of::<T>();
+    if stride != 4 && stride != 8 {
+        return Err(Error::Config(format!(
+            "octahedral filter output must be 4 or 8 bytes wide, got {}",
+            stride
+        )));
+    }
+    let max_bits = stride as u32 * 2;
+    if bits == 0 || bits > max_bits {
+        return Err(Error::Config(format!(
+            "octahedral filter bits ({}) must be in 1..={} for a {} byte output",
+            bits, max_bits, stride
+        )));
+    }
+
+    let mut result = UninitBuffer::<T>::new(vectors.len());
+    unsafe {
+        ffi::meshopt_encodeFilterOct(
+            result.as_mut_ptr().cast(),
+            vectors.len(),
+            stride,
+            bits as i32,
+            vectors.as_ptr().cast(),
+        );
+        Ok(result.assume_init(vectors.len()))
+    }
+}
+
+/// Decodes octahedral encoding of unit vectors produced by `encode_filter_oct` in place.
+///
+/// This is meant to be applied to the output of `decode_vertex_buffer`; each element
+/// must be 4 or 8 bytes wide, matching the encoded component width.
+pub fn decode_filter_oct<T: FilterElement>(data: &mut [T]) -> Result<()> {
+    let stride = mem::size_of::<T>();
+    if stride != 4 && stride != 8 {
+        return Err(Error::Config(format!(
+            "octahedral filter data must be 4 or 8 bytes wide, got {}",
+            stride
+        )));
+    }
+
+    unsafe {
+        ffi::meshopt_decodeFilterOct(data.as_mut_ptr().cast(), data.len(), stride);
+    }
+    Ok(())
+}
+
+/// Encodes unit quaternions (rotations, tangent frames) with K-bit (4 <= K <= 16)
+/// component encoding, storing 3 components plus the index of the reconstructed one.
+///
+/// Each input quaternion takes 4 floats. Each output component is stored as a
+/// 16-bit integer, so the result type must be 8 bytes wide (e.g. `[i16; 4]`).
+pub fn encode_filter_quat<T: FilterElement>(quaternions: &[[f32; 4]], bits: u32) -> Result<Vec<T>> {
+    let stride = mem::size_of::<T>();
+    if stride != 8 {
+        return Err(Error::Config(format!(
+            "quaternion filter output must be 8 bytes wide, got {}",
+            stride
+        )));
+    }
+    if !(4..=16).contains(&bits) {
+        return Err(Error::Config(format!(
+            "quaternion filter bits ({}) must be in 4..=16",
+            bits
+        )));
+    }
+
+    let mut result = UninitBuffer::<T>::new(quaternions.len());
+    unsafe {
+        ffi::meshopt_encodeFilterQuat(
+            result.as_mut_ptr().cast(),
+            quaternions.len(),
+            stride,
+            bits as i32,
+            quaternions.as_ptr().cast(),
+        );
+        Ok(result.assume_init(quaternions.len()))
+    }
+}
+
+/// Decodes quaternion encoding produced by `encode_filter_quat` in place.
+///
+/// This is meant to be applied to the output of `decode_vertex_buffer`; each element
+/// must be 8 bytes wide.
+pub fn decode_filter_quat<T: FilterElement>(data: &mut [T]) -> Result<()> {
+    let stride = mem::size_of::<T>();
+    if stride != 8 {
+        return Err(Error::Config(format!(
+            "quaternion filter data must be 8 bytes wide, got {}",
+            stride
+        )));
+    }
+
+    unsafe {
+        ffi::meshopt_decodeFilterQuat(data.as_mut_ptr().cast(), data.len(), stride);
+    }
+    Ok(())
+}
+
+/// Controls how exponents are shared by `encode_filter_exp`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EncodeExpMode {
+    /// When encoding exponents, use separate values for each component (maximum quality)
+    Separate,
+    /// When encoding exponents, use shared value for all components of each vector (better compression)
+    SharedVector,
+    /// When encoding exponents, use shared value for each component of all vectors (best compression)
+    SharedComponent,
+    /// Experimental: When encoding exponents, use separate values for each component, but clamp
+    /// to 0 (good quality if very small values are not important)
+    Clamped,
+}
+
+impl EncodeExpMode {
+    fn to_ffi(self) -> ffi::meshopt_EncodeExpMode {
+        match self {
+            EncodeExpMode::Separate => ffi::meshopt_EncodeExpMode_meshopt_EncodeExpSeparate,
+            EncodeExpMode::SharedVector => ffi::meshopt_EncodeExpMode_meshopt_EncodeExpSharedVector,
+            EncodeExpMode::SharedComponent => {
+                ffi::meshopt_EncodeExpMode_meshopt_EncodeExpSharedComponent
+            }
+            EncodeExpMode::Clamped => ffi::meshopt_EncodeExpMode_meshopt_EncodeExpClamped,
+        }
+    }
+}
+
+/// Encodes arbitrary (finite) floating-point data with 8-bit exponent and K-bit integer
+/// mantissa (1 <= K <= 24), e.g. morph target deltas or texture coordinates.
+///
+/// Each output element is a vector of `size_of::<T>() / 4` 32-bit components, so the result
+/// type must be a multiple of 4 bytes wide, at most 256 (e.g. `[u32; 3]`), and `data.len()`
+/// must be a multiple of the component count.
+pub fn encode_filter_exp<T: FilterElement>(
+    data: &[f32],
+    bits: u32,
+    mode: EncodeExpMode,
+) -> Result<Vec<T>> {
+    let stride = mem::size_of::<T>();
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        return Err(Error::Config(format!(
+            "exponential filter output must be a multiple of 4 bytes wide in 4..=256, got {}",
+            stride
+        )));
+    }
+    if !(1..=24).contains(&bits) {
+        return Err(Error::Config(format!(
+            "exponential filter bits ({}) must be in 1..=24",
+            bits
+        )));
+    }
+    let components = stride / 4;
+    if !data.len().is_multiple_of(components) {
+        return Err(Error::Config(format!(
+            "exponential filter input length ({}) must be divisible by component count ({})",
+            data.len(),
+            components
+        )));
+    }
+
+    let count = data.len() / components;
+    let mut result = UninitBuffer::<T>::new(count);
+    unsafe {
+        ffi::meshopt_encodeFilterExp(
+            result.as_mut_ptr().cast(),
+            count,
+            stride,
+            bits as i32,
+            data.as_ptr(),
+            mode.to_ffi(),
+        );
+        Ok(result.assume_init(count))
+    }
+}
+
+/// Decodes exponential encoding produced by `encode_filter_exp` in place.
+///
+/// This is meant to be applied to the output of `decode_vertex_buffer`; each element
+/// must be a multiple of 4 bytes wide. After decoding, every 32-bit component holds
+/// the bit pattern of an `f32`.
+pub fn decode_filter_exp<T: FilterElement>(data: &mut [T]) -> Result<()> {
+    let stride = mem::size_of::<T>();
+    if stride == 0 || !stride.is_multiple_of(4) {
+        return Err(Error::Config(format!(
+            "exponential filter data must be a non-zero multiple of 4 bytes wide, got {}",
+            stride
+        )));
+    }
+
+    unsafe {
+        ffi::meshopt_decodeFilterExp(data.as_mut_ptr().cast(), data.len(), stride);
+    }
+    Ok(())
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EncodeHeader {
     pub magic: [u8; 4], // OPTM
 
@@ -114,6 +959,8 @@ pub struct EncodeHeader {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EncodeObject {
     pub index_offset: u32,
     pub index_count: u32,
@@ -6,8 +6,15 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[non_exhaustive]
 pub enum Error {
     /// An error that occurred interfacing with native code through FFI.
-    #[error("native error: {0}")]
-    Native(i32),
+    ///
+    /// `function` is the native function that failed, and `context` summarizes the
+    /// parameters it was called with (counts, strides and buffer sizes).
+    #[error("native error: {code} in {function} ({context})")]
+    Native {
+        code: i32,
+        function: &'static str,
+        context: String,
+    },
 
     /// An error that occurred while accessing or allocating memory
     #[error("memory error: {0}")]
@@ -48,11 +55,22 @@ impl Error {
     }
 }
 
+/// Returns `ok` if the native `function` succeeded, or an `Error::Native` with the
+/// parameter summary produced by `context` otherwise.
 #[inline]
-pub(crate) fn error_or<T>(code: i32, ok: T) -> Result<T> {
+pub(crate) fn error_or<T>(
+    code: i32,
+    ok: T,
+    function: &'static str,
+    context: impl FnOnce() -> String,
+) -> Result<T> {
     if code == 0 {
         Ok(ok)
     } else {
-        Err(Error::Native(code))
+        Err(Error::Native {
+            code,
+            function,
+            context: context(),
+        })
     }
 }
@@ -0,0 +1,142 @@
+//! `#[repr(C)]` mirrors of meshlet, culling bounds and LOD data with a fixed field order
+//! and size, for engines consuming the output of a Rust cook step without a serialization
+//! layer.
+//!
+//! Meshlets are exported with `Meshlets::pack_for_gpu`, whose `GpuMeshlet` descriptors are
+//! also available as `ExportMeshlet`.
+//!
+//! Every struct is made of 4-byte fields without padding and matches the following C
+//! declarations, whose sizes are checked at compile time:
+//!
+//! ```c
+//! struct ExportMeshlet {      // 16 bytes, see GpuMeshlet
+//!     uint32_t vertex_offset;
+//!     uint32_t triangle_offset; // in 32-bit words of packed micro-indices
+//!     uint32_t vertex_count;
+//!     uint32_t triangle_count;
+//! };
+//!
+//! struct ExportBounds {       // 48 bytes
+//!     float center[3];
+//!     float radius;
+//!     float cone_apex[3];
+//!     float cone_cutoff;
+//!     float cone_axis[3];
+//!     int8_t cone_axis_s8[3];
+//!     int8_t cone_cutoff_s8;
+//! };
+//!
+//! struct ExportLodLevel {     // 16 bytes
+//!     uint32_t index_offset;
+//!     uint32_t index_count;
+//!     float error;
+//!     uint32_t sloppy;        // 0 or 1
+//! };
+//! ```
+//!
+//! Byte buffers use the native endianness of the cooking machine.
+
+use crate::{typed_to_bytes, Bounds, ClusterBounds, GpuMeshlet, LodChain};
+use std::mem;
+
+/// Implements `as_bytes` and `slice_as_bytes` for an export struct.
+macro_rules! impl_as_bytes {
+    ($ty:ty, $size:expr) => {
+        const _: () = assert!(mem::size_of::<$ty>() == $size);
+
+        impl $ty {
+            /// Returns the bytes of this value, laid out like the C declaration.
+            pub fn as_bytes(&self) -> &[u8] {
+                typed_to_bytes(std::slice::from_ref(self))
+            }
+
+            /// Returns the bytes of a slice of values, laid out like a C array.
+            pub fn slice_as_bytes(values: &[Self]) -> &[u8] {
+                typed_to_bytes(values)
+            }
+        }
+    };
+}
+
+/// Meshlet descriptor, as produced by `Meshlets::pack_for_gpu`.
+pub type ExportMeshlet = GpuMeshlet;
+
+impl_as_bytes!(GpuMeshlet, 16);
+
+/// Culling bounds of a cluster, with the fields of `ClusterBounds` reordered so that every
+/// vector is followed by a scalar and the struct has no padding.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportBounds {
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub cone_apex: [f32; 3],
+    pub cone_cutoff: f32,
+    pub cone_axis: [f32; 3],
+    pub cone_axis_s8: [i8; 3],
+    pub cone_cutoff_s8: i8,
+}
+
+impl_as_bytes!(ExportBounds, 48);
+
+impl From<ClusterBounds> for ExportBounds {
+    fn from(bounds: ClusterBounds) -> Self {
+        Self {
+            center: bounds.center,
+            radius: bounds.radius,
+            cone_apex: bounds.cone_apex,
+            cone_cutoff: bounds.cone_cutoff,
+            cone_axis: bounds.cone_axis,
+            cone_axis_s8: bounds.cone_axis_s8,
+            cone_cutoff_s8: bounds.cone_cutoff_s8,
+        }
+    }
+}
+
+impl From<Bounds> for ExportBounds {
+    fn from(bounds: Bounds) -> Self {
+        ClusterBounds::from(bounds).into()
+    }
+}
+
+/// A level of detail within the index buffer returned by `export_lod_table`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportLodLevel {
+    /// Offset of the first index of the level.
+    pub index_offset: u32,
+    pub index_count: u32,
+    /// Simplification error, see `LodLevel::error`.
+    pub error: f32,
+    /// 1 if the level was produced by the sloppy simplifier, 0 otherwise.
+    pub sloppy: u32,
+}
+
+impl_as_bytes!(ExportLodLevel, 16);
+
+/// Concatenates the levels of `chain` with `LodChain::concatenate`, returning the combined
+/// index buffer and a table with one entry per level, from the most detailed to the coarsest.
+///
+/// # Panics
+///
+/// Panics if the combined index buffer has more than `u32::MAX` indices.
+pub fn export_lod_table(chain: &LodChain) -> (Vec<u32>, Vec<ExportLodLevel>) {
+    let (indices, ranges) = chain.concatenate();
+    assert!(u32::try_from(indices.len()).is_ok());
+    let levels = chain
+        .levels
+        .iter()
+        .zip(ranges)
+        .map(|(level, range)| ExportLodLevel {
+            index_offset: range.start as u32,
+            index_count: range.len() as u32,
+            error: level.error,
+            sloppy: u32::from(level.sloppy),
+        })
+        .collect();
+    (indices, levels)
+}
@@ -0,0 +1,443 @@
+use crate::{
+    build_meshlets, compute_cluster_bounds, partition_clusters_with_positions,
+    simplify_with_locks_result, Meshlets, Result, SimplifyOptions, SimplifyResult, TargetCount,
+    VertexDataAdapter,
+};
+
+/// Configuration for `build_cluster_dag`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClusterDagConfig {
+    /// Maximum number of vertices per cluster, see `build_meshlets`.
+    pub max_vertices: usize,
+    /// Maximum number of triangles per cluster, see `build_meshlets`.
+    pub max_triangles: usize,
+    /// Cone weight used when splitting geometry into clusters, see `build_meshlets`.
+    pub cone_weight: f32,
+    /// Target number of clusters merged into a group before simplification.
+    pub group_size: usize,
+    /// Fraction of the triangles of a group kept by simplification.
+    pub simplify_ratio: f32,
+    /// Groups that can't be simplified below this fraction of their triangles are not
+    /// simplified any further, and their clusters become roots of the hierarchy.
+    pub simplify_threshold: f32,
+    /// Maximum number of simplification levels.
+    pub max_depth: usize,
+}
+
+impl Default for ClusterDagConfig {
+    fn default() -> Self {
+        Self {
+            max_vertices: 64,
+            max_triangles: 124,
+            cone_weight: 0f32,
+            group_size: 8,
+            simplify_ratio: 0.5,
+            simplify_threshold: 0.85,
+            max_depth: 32,
+        }
+    }
+}
+
+/// A cluster of the hierarchy produced by `build_cluster_dag`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DagCluster {
+    /// Index buffer of the cluster, referencing the source vertex buffer.
+    pub indices: Vec<u32>,
+    /// Simplification level of the cluster; 0 for clusters of the source mesh.
+    pub depth: usize,
+    /// Bounding sphere `(center, radius)` used for error projection of `error`.
+    pub bounds: ([f32; 3], f32),
+    /// Absolute simplification error of the cluster; 0 for clusters of the source mesh.
+    pub error: f32,
+    /// Bounding sphere of the group the cluster was merged into; equal to `bounds` for roots.
+    pub parent_bounds: ([f32; 3], f32),
+    /// Error of the simplified version of the cluster; `f32::MAX` for roots, which are never replaced.
+    pub parent_error: f32,
+}
+
+/// A group of clusters that were merged and simplified together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DagGroup {
+    /// Clusters merged into the group.
+    pub children: Vec<usize>,
+    /// Clusters produced by simplifying the group.
+    pub parents: Vec<usize>,
+    /// Absolute simplification error of the group, at least the error of every child.
+    pub error: f32,
+    /// Bounding sphere `(center, radius)` of the group.
+    pub bounds: ([f32; 3], f32),
+}
+
+/// A Nanite-style cluster hierarchy: a DAG of clusters, where groups of neighboring clusters are
+/// simplified together and split into coarser clusters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterDag {
+    pub clusters: Vec<DagCluster>,
+    pub groups: Vec<DagGroup>,
+}
+
+impl ClusterDag {
+    /// Selects the clusters to render for an error `threshold` (in the units of the vertex
+    /// positions), which is the set of clusters with `error <= threshold < parent_error`.
+    ///
+    /// This is the view-independent version of the selection; renderers usually project
+    /// `error` and `parent_error` using `bounds` and `parent_bounds` before comparing.
+    pub fn select(&self, threshold: f32) -> Vec<usize> {
+        self.clusters
+            .iter()
+            .enumerate()
+            .filter(|(_, cluster)| cluster.error <= threshold && threshold < cluster.parent_error)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Returns the clusters that aren't replaced by any coarser cluster.
+    pub fn roots(&self) -> Vec<usize> {
+        self.clusters
+            .iter()
+            .enumerate()
+            .filter(|(_, cluster)| cluster.parent_error == f32::MAX)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Merges bounding spheres into one sphere enclosing all of them.
+fn merge_spheres(spheres: impl IntoIterator<Item = ([f32; 3], f32)>) -> ([f32; 3], f32) {
+    let mut result: Option<([f32; 3], f32)> = None;
+    for (center, radius) in spheres {
+        result = Some(match result {
+            None => (center, radius),
+            Some((c, r)) => {
+                let d = [center[0] - c[0], center[1] - c[1], center[2] - c[2]];
+                let distance = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                if distance + radius <= r {
+                    (c, r)
+                } else if distance + r <= radius {
+                    (center, radius)
+                } else {
+                    let new_radius = (distance + r + radius) * 0.5;
+                    let k = (new_radius - r) / distance;
+                    (
+                        [c[0] + d[0] * k, c[1] + d[1] * k, c[2] + d[2] * k],
+                        new_radius,
+                    )
+                }
+            }
+        });
+    }
+    result.unwrap_or(([0f32; 3], 0f32))
+}
+
+/// Locks vertices referenced by clusters of more than one group, so that simplifying each
+/// group independently doesn't open cracks between neighboring groups.
+fn group_boundary_locks<'a>(
+    vertex_count: usize,
+    groups: impl IntoIterator<Item = (usize, &'a [u32])>,
+) -> Vec<bool> {
+    const NO_GROUP: usize = usize::MAX;
+
+    let mut locks = vec![false; vertex_count];
+    let mut owner = vec![NO_GROUP; vertex_count];
+    for (group, indices) in groups {
+        for &index in indices {
+            let index = index as usize;
+            if owner[index] == NO_GROUP {
+                owner[index] = group;
+            } else if owner[index] != group {
+                locks[index] = true;
+            }
+        }
+    }
+    locks
+}
+
+fn simplify_merged_group(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_lock: &[bool],
+    target_count: TargetCount,
+    target_error: f32,
+) -> SimplifyResult {
+    simplify_with_locks_result(
+        indices,
+        vertices,
+        vertex_lock,
+        target_count.index_count(indices.len()),
+        target_error,
+        SimplifyOptions::Sparse | SimplifyOptions::ErrorAbsolute,
+    )
+}
+
+/// Merges the meshlets in `group` and simplifies them together, which is the core step of
+/// hierarchical LOD.
+///
+/// Vertices shared with meshlets outside of the group are locked, so that the simplified
+/// group still matches its neighbors without cracks. `target_error` and the error of the
+/// result are absolute, in the units of the vertex positions.
+///
+/// The resulting index buffer references vertices from the original vertex buffer and can
+/// be split into new meshlets with `build_meshlets`.
+pub fn simplify_cluster_group(
+    meshlets: &Meshlets,
+    group: &[usize],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: TargetCount,
+    target_error: f32,
+) -> SimplifyResult {
+    let mut in_group = vec![false; meshlets.len()];
+    for &meshlet in group {
+        in_group[meshlet] = true;
+    }
+
+    let mut outside = vec![false; vertices.vertex_count];
+    for (i, meshlet) in meshlets.iter().enumerate() {
+        if !in_group[i] {
+            for &vertex in meshlet.vertices {
+                outside[vertex as usize] = true;
+            }
+        }
+    }
+
+    let mut locks = vec![false; vertices.vertex_count];
+    let mut merged: Vec<u32> = Vec::new();
+    for &meshlet in group {
+        let meshlet = meshlets.get(meshlet);
+        for &vertex in meshlet.vertices {
+            locks[vertex as usize] = outside[vertex as usize];
+        }
+        merged.extend(meshlet.global_indices());
+    }
+
+    simplify_merged_group(&merged, vertices, &locks, target_count, target_error)
+}
+
+fn split_into_clusters(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    config: &ClusterDagConfig,
+) -> Result<Vec<Vec<u32>>> {
+    if indices.is_empty() {
+        return Ok(Vec::new());
+    }
+    let meshlets = build_meshlets(
+        indices,
+        vertices,
+        config.max_vertices,
+        config.max_triangles,
+        config.cone_weight,
+    )?;
+    Ok(meshlets
+        .iter()
+        .map(|meshlet| meshlet.global_indices().collect())
+        .collect())
+}
+
+fn cluster_sphere(indices: &[u32], vertices: &VertexDataAdapter<'_>) -> ([f32; 3], f32) {
+    let bounds = compute_cluster_bounds(indices, vertices);
+    (bounds.center, bounds.radius)
+}
+
+/// Builds a cluster hierarchy for virtualized geometry rendering.
+///
+/// The mesh is split into clusters with `build_meshlets`; then, level by level, neighboring
+/// clusters are grouped with `partition_clusters_with_positions`, each group is simplified with
+/// the vertices shared with other groups locked, and the result is split into new clusters.
+/// This repeats until a single cluster remains, no group can be simplified further, or
+/// `ClusterDagConfig::max_depth` is reached.
+///
+/// Errors are absolute and monotonic: no cluster has a larger error than the clusters
+/// it is replaced by, so a consistent cut can be selected with `ClusterDag::select`.
+///
+/// Returns `Error::Config` if the cluster limits are rejected by `build_meshlets`.
+pub fn build_cluster_dag(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    config: &ClusterDagConfig,
+) -> Result<ClusterDag> {
+    let mut dag = ClusterDag::default();
+    for cluster_indices in split_into_clusters(indices, vertices, config)? {
+        let bounds = cluster_sphere(&cluster_indices, vertices);
+        dag.clusters.push(DagCluster {
+            indices: cluster_indices,
+            depth: 0,
+            bounds,
+            error: 0f32,
+            parent_bounds: bounds,
+            parent_error: f32::MAX,
+        });
+    }
+
+    // vertices of clusters that became roots stay locked on all following levels, as the
+    // roots are no longer part of any group but still border the clusters that are
+    let mut root_locks = vec![false; vertices.vertex_count];
+    let mut pending: Vec<usize> = (0..dag.clusters.len()).collect();
+    for depth in 1..=config.max_depth {
+        if pending.len() <= 1 {
+            break;
+        }
+
+        let mut cluster_indices: Vec<u32> = Vec::new();
+        let mut cluster_index_counts: Vec<u32> = Vec::with_capacity(pending.len());
+        for &cluster in &pending {
+            cluster_indices.extend_from_slice(&dag.clusters[cluster].indices);
+            cluster_index_counts.push(dag.clusters[cluster].indices.len() as u32);
+        }
+        let (partition_count, partition) = partition_clusters_with_positions(
+            &cluster_indices,
+            &cluster_index_counts,
+            vertices,
+            config.group_size,
+        );
+        let mut partitions: Vec<Vec<usize>> = vec![Vec::new(); partition_count];
+        for (i, &cluster) in pending.iter().enumerate() {
+            partitions[partition[i] as usize].push(cluster);
+        }
+
+        let mut locks = group_boundary_locks(
+            vertices.vertex_count,
+            partitions.iter().enumerate().flat_map(|(group, clusters)| {
+                let dag = &dag;
+                clusters
+                    .iter()
+                    .map(move |&cluster| (group, dag.clusters[cluster].indices.as_slice()))
+            }),
+        );
+        for (lock, &root) in locks.iter_mut().zip(&root_locks) {
+            *lock |= root;
+        }
+
+        let mut next: Vec<usize> = Vec::new();
+        for children in partitions {
+            // a single cluster can't be merged with anything, retry it on the next level
+            if children.len() == 1 {
+                next.extend_from_slice(&children);
+                continue;
+            }
+
+            let merged: Vec<u32> = children
+                .iter()
+                .flat_map(|&cluster| dag.clusters[cluster].indices.iter().copied())
+                .collect();
+            let simplified = simplify_merged_group(
+                &merged,
+                vertices,
+                &locks,
+                TargetCount::Ratio(config.simplify_ratio),
+                f32::MAX,
+            );
+            if simplified.indices.is_empty()
+                || simplified.indices.len() as f32 > merged.len() as f32 * config.simplify_threshold
+            {
+                // the group is stuck; its clusters stay roots of the hierarchy
+                for &cluster in &children {
+                    for &index in &dag.clusters[cluster].indices {
+                        root_locks[index as usize] = true;
+                    }
+                }
+                continue;
+            }
+
+            let error = children
+                .iter()
+                .map(|&cluster| dag.clusters[cluster].error)
+                .fold(simplified.error, f32::max);
+            let bounds =
+                merge_spheres(children.iter().map(|&cluster| dag.clusters[cluster].bounds));
+            for &cluster in &children {
+                dag.clusters[cluster].parent_error = error;
+                dag.clusters[cluster].parent_bounds = bounds;
+            }
+
+            let mut parents: Vec<usize> = Vec::new();
+            for parent_indices in split_into_clusters(&simplified.indices, vertices, config)? {
+                parents.push(dag.clusters.len());
+                dag.clusters.push(DagCluster {
+                    indices: parent_indices,
+                    depth,
+                    bounds,
+                    error,
+                    parent_bounds: bounds,
+                    parent_error: f32::MAX,
+                });
+            }
+            next.extend_from_slice(&parents);
+            dag.groups.push(DagGroup {
+                children,
+                parents,
+                error,
+                bounds,
+            });
+        }
+
+        if next.len() >= pending.len() {
+            break;
+        }
+        pending = next;
+    }
+
+    Ok(dag)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{build_cluster_dag, typed_to_bytes, ClusterDagConfig, VertexDataAdapter};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_cluster_dag_roots_are_watertight() {
+        // a 32x32 grid of quads, whose only open edges are on the outer border
+        const SIZE: u32 = 32;
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        for y in 0..=SIZE {
+            for x in 0..=SIZE {
+                positions.push([x as f32, y as f32, ((x * 7 + y * 13) % 5) as f32 * 0.1]);
+            }
+        }
+        let mut indices: Vec<u32> = Vec::new();
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let v = y * (SIZE + 1) + x;
+                indices.extend_from_slice(&[v, v + 1, v + SIZE + 1]);
+                indices.extend_from_slice(&[v + 1, v + SIZE + 2, v + SIZE + 1]);
+            }
+        }
+        let vertices = VertexDataAdapter::new(
+            typed_to_bytes(&positions),
+            std::mem::size_of::<[f32; 3]>(),
+            0,
+        )
+        .unwrap();
+
+        // a low threshold leaves some groups stuck, so roots are finalized on several levels
+        let config = ClusterDagConfig {
+            simplify_threshold: 0.6,
+            ..ClusterDagConfig::default()
+        };
+        let dag = build_cluster_dag(&indices, &vertices, &config).unwrap();
+
+        // vertices shared between a root and the clusters simplified after it was finalized
+        // must survive, otherwise the cut made of all roots has cracks
+        let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+        for root in dag.roots() {
+            for triangle in dag.clusters[root].indices.chunks_exact(3) {
+                for i in 0..3 {
+                    let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                    *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+                }
+            }
+        }
+        let on_border = |vertex: u32| {
+            let (x, y) = (vertex % (SIZE + 1), vertex / (SIZE + 1));
+            x == 0 || y == 0 || x == SIZE || y == SIZE
+        };
+        for (&(a, b), &count) in &edges {
+            assert!(
+                count == 2 || (on_border(a) && on_border(b)),
+                "edge {}-{} is open",
+                a,
+                b
+            );
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Reading meshes from `gltf` primitives.
+//!
+//! Accessors are read through `gltf::mesh::Reader`, which resolves strides, interleaving,
+//! sparse accessors and component types, so the buffers returned here can be passed to the
+//! functions of this crate directly:
+//!
+//! ```ignore
+//! let (document, buffers, _) = gltf::import("scene.gltf")?;
+//! for mesh in document.meshes() {
+//!     for primitive in mesh.primitives() {
+//!         let mesh = GltfMesh::from_primitive(&primitive, |buffer| {
+//!             buffers.get(buffer.index()).map(|data| &data.0[..])
+//!         })?;
+//!         let simplified = meshopt::simplify(
+//!             &mesh.indices,
+//!             &mesh.positions_adapter(),
+//!             mesh.indices.len() / 2,
+//!             1e-2,
+//!             meshopt::SimplifyOptions::empty(),
+//!             None,
+//!         );
+//!     }
+//! }
+//! ```
+
+use crate::{typed_to_bytes, Error, MeshSource, Result, Vertex, VertexDataAdapter, VertexStream};
+use gltf::mesh::Mode;
+use gltf::{Buffer, Primitive};
+use std::borrow::Cow;
+use std::mem;
+
+/// Vertex and index data of a triangle list primitive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GltfMesh {
+    /// Triangle list indices; generated for non-indexed primitives.
+    pub indices: Vec<u32>,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    /// The first texture coordinate set, converted to `f32`.
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+}
+
+impl GltfMesh {
+    /// Reads the primitive, with `get_buffer_data` returning the contents of a buffer like
+    /// for `Primitive::reader`.
+    ///
+    /// Fails if the primitive isn't a triangle list, has no positions, or an attribute or
+    /// index references a missing buffer or vertex.
+    pub fn from_primitive<'a, 's, F>(
+        primitive: &'a Primitive<'_>,
+        get_buffer_data: F,
+    ) -> Result<Self>
+    where
+        F: Clone + Fn(Buffer<'a>) -> Option<&'s [u8]>,
+    {
+        if primitive.mode() != Mode::Triangles {
+            return Err(Error::Config(format!(
+                "primitive mode must be triangles, got {:?}",
+                primitive.mode()
+            )));
+        }
+
+        let reader = primitive.reader(get_buffer_data);
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or_else(|| Error::Parse("primitive has no readable positions".into()))?
+            .collect();
+        let vertex_count = positions.len();
+
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertex_count as u32).collect(),
+        };
+        if !indices.len().is_multiple_of(3) {
+            return Err(Error::Parse(format!(
+                "index count ({}) must be a multiple of 3",
+                indices.len()
+            )));
+        }
+        if let Some(&index) = indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(Error::Parse(format!(
+                "index {} is out of range for {} vertices",
+                index, vertex_count
+            )));
+        }
+
+        let mesh = Self {
+            indices,
+            positions,
+            normals: reader.read_normals().map(Iterator::collect),
+            tangents: reader.read_tangents().map(Iterator::collect),
+            tex_coords: reader
+                .read_tex_coords(0)
+                .map(|tex_coords| tex_coords.into_f32().collect()),
+        };
+        mesh.validate_attribute_counts()?;
+        Ok(mesh)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns an adapter reading the positions, for the simplify, optimize and clusterize
+    /// functions.
+    pub fn positions_adapter(&self) -> VertexDataAdapter<'_> {
+        VertexDataAdapter::new(
+            typed_to_bytes(&self.positions),
+            mem::size_of::<[f32; 3]>(),
+            0,
+        )
+        .expect("positions are tightly packed")
+    }
+
+    /// Interleaves the normals and texture coordinates that are present, returning the
+    /// attribute buffer and its stride in bytes for `simplify_with_attributes_and_locks`.
+    ///
+    /// The stride is 0 if the primitive has neither.
+    pub fn attributes(&self) -> (Vec<f32>, usize) {
+        let normals = self.normals.as_deref();
+        let tex_coords = self.tex_coords.as_deref();
+        let count = normals.map_or(0, |_| 3) + tex_coords.map_or(0, |_| 2);
+
+        let mut attributes = Vec::with_capacity(self.vertex_count() * count);
+        for vertex in 0..self.vertex_count() {
+            if let Some(normals) = normals {
+                attributes.extend_from_slice(&normals[vertex]);
+            }
+            if let Some(tex_coords) = tex_coords {
+                attributes.extend_from_slice(&tex_coords[vertex]);
+            }
+        }
+        (attributes, count * mem::size_of::<f32>())
+    }
+
+    /// Interleaves the mesh into `Vertex` values, e.g. for `pack_vertices`; missing normals
+    /// and texture coordinates are zero.
+    pub fn vertices(&self) -> Vec<Vertex> {
+        (0..self.vertex_count())
+            .map(|vertex| Vertex {
+                p: self.positions[vertex],
+                n: self
+                    .normals
+                    .as_ref()
+                    .map_or([0f32; 3], |normals| normals[vertex]),
+                t: self
+                    .tex_coords
+                    .as_ref()
+                    .map_or([0f32; 2], |tex_coords| tex_coords[vertex]),
+            })
+            .collect()
+    }
+
+    fn validate_attribute_counts(&self) -> Result<()> {
+        let counts = [
+            ("normals", self.normals.as_ref().map(Vec::len)),
+            ("tangents", self.tangents.as_ref().map(Vec::len)),
+            ("tex_coords", self.tex_coords.as_ref().map(Vec::len)),
+        ];
+        for (name, count) in counts {
+            if let Some(count) = count.filter(|&count| count != self.vertex_count()) {
+                return Err(Error::Parse(format!(
+                    "primitive has {} {}, expected one per vertex ({})",
+                    count,
+                    name,
+                    self.vertex_count()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MeshSource for GltfMesh {
+    fn indices(&self) -> Cow<'_, [u32]> {
+        Cow::Borrowed(&self.indices)
+    }
+
+    fn positions(&self) -> VertexDataAdapter<'_> {
+        self.positions_adapter()
+    }
+
+    fn attribute_streams(&self) -> Vec<VertexStream<'_>> {
+        let mut streams = Vec::new();
+        if let Some(normals) = &self.normals {
+            streams.push(VertexStream::from_slice(normals));
+        }
+        if let Some(tangents) = &self.tangents {
+            streams.push(VertexStream::from_slice(tangents));
+        }
+        if let Some(tex_coords) = &self.tex_coords {
+            streams.push(VertexStream::from_slice(tex_coords));
+        }
+        streams
+    }
+}
@@ -0,0 +1,11 @@
+//! Adapters building the inputs of this crate from mesh file formats, each behind the
+//! feature of the same name.
+
+#[cfg(feature = "gltf")]
+pub mod gltf;
+
+#[cfg(feature = "obj")]
+pub mod obj;
+
+#[cfg(feature = "ply")]
+pub mod ply;
@@ -0,0 +1,190 @@
+//! Loading and saving Wavefront OBJ meshes, using `tobj` for parsing.
+//!
+//! All models of a file are merged into a single indexed mesh of `Vertex` values, with
+//! duplicate vertices removed through `generate_vertex_remap`. Materials are ignored.
+
+use crate::{
+    generate_vertex_remap, remap_index_buffer, remap_vertex_buffer, Error, MeshSource, Result,
+    Vertex, VertexDataAdapter, VertexStream,
+};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// An indexed triangle mesh loaded from or saved to an OBJ file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjMesh {
+    pub vertices: Vec<Vertex>,
+    /// Triangle list indices into `vertices`.
+    pub indices: Vec<u32>,
+}
+
+impl ObjMesh {
+    /// Loads the OBJ file at `path`, triangulating polygons and merging all of its models.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let models = load_models(&mut reader)
+            .map_err(|err| Error::Parse(format!("{}: {}", path.display(), err)))?;
+        Self::from_models(&models)
+    }
+
+    /// Loads an OBJ file from `reader`, like `load`.
+    pub fn load_buf<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let models = load_models(reader).map_err(|err| Error::Parse(err.to_string()))?;
+        Self::from_models(&models)
+    }
+
+    /// Merges models loaded by `tobj` with `single_index` enabled into a single mesh.
+    ///
+    /// Missing normals and texture coordinates are zero.
+    pub fn from_models(models: &[tobj::Model]) -> Result<Self> {
+        let index_count = models.iter().map(|model| model.mesh.indices.len()).sum();
+        let mut corners: Vec<Vertex> = Vec::with_capacity(index_count);
+
+        for model in models {
+            let mesh = &model.mesh;
+            if mesh.indices.len() % 3 != 0 {
+                return Err(Error::Parse(format!(
+                    "model '{}' has {} indices, expected a triangle list",
+                    model.name,
+                    mesh.indices.len()
+                )));
+            }
+
+            let vertex_count = mesh.positions.len() / 3;
+            let has_normals = !mesh.normals.is_empty();
+            let has_tex_coords = !mesh.texcoords.is_empty();
+            if (has_normals && mesh.normals.len() != vertex_count * 3)
+                || (has_tex_coords && mesh.texcoords.len() != vertex_count * 2)
+            {
+                return Err(Error::Parse(format!(
+                    "model '{}' has attributes for a different number of vertices than its {} positions",
+                    model.name, vertex_count
+                )));
+            }
+
+            for &index in &mesh.indices {
+                let index = index as usize;
+                if index >= vertex_count {
+                    return Err(Error::Parse(format!(
+                        "model '{}' references vertex {} of {}",
+                        model.name, index, vertex_count
+                    )));
+                }
+
+                let p = [
+                    mesh.positions[index * 3],
+                    mesh.positions[index * 3 + 1],
+                    mesh.positions[index * 3 + 2],
+                ];
+                let n = if has_normals {
+                    [
+                        mesh.normals[index * 3],
+                        mesh.normals[index * 3 + 1],
+                        mesh.normals[index * 3 + 2],
+                    ]
+                } else {
+                    [0f32; 3]
+                };
+                let t = if has_tex_coords {
+                    [mesh.texcoords[index * 2], mesh.texcoords[index * 2 + 1]]
+                } else {
+                    [0f32; 2]
+                };
+                corners.push(Vertex { p, n, t });
+            }
+        }
+
+        let (vertex_count, remap) = generate_vertex_remap(&corners, None);
+        Ok(Self {
+            vertices: remap_vertex_buffer(&corners, vertex_count, &remap),
+            indices: remap_index_buffer(None, index_count, &remap),
+        })
+    }
+
+    /// Expands the mesh into one vertex per index.
+    pub fn deindex(&self) -> Vec<Vertex> {
+        self.indices
+            .iter()
+            .map(|&index| self.vertices[index as usize])
+            .collect()
+    }
+
+    /// Saves the mesh as an OBJ file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the mesh in the OBJ format, with a position, normal and texture coordinate
+    /// per vertex.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(Error::Config(format!(
+                "index count ({}) must be a multiple of 3",
+                self.indices.len()
+            )));
+        }
+        if let Some(&index) = self
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= self.vertices.len())
+        {
+            return Err(Error::Config(format!(
+                "index {} is out of range for {} vertices",
+                index,
+                self.vertices.len()
+            )));
+        }
+
+        for vertex in &self.vertices {
+            writeln!(writer, "v {} {} {}", vertex.p[0], vertex.p[1], vertex.p[2])?;
+            writeln!(writer, "vn {} {} {}", vertex.n[0], vertex.n[1], vertex.n[2])?;
+            writeln!(writer, "vt {} {} {}", vertex.t[0], vertex.t[1], 0f32)?;
+        }
+
+        for triangle in self.indices.chunks_exact(3) {
+            // OBJ indices are 1-based
+            let [i0, i1, i2] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+            writeln!(
+                writer,
+                "f {}/{}/{} {}/{}/{} {}/{}/{}",
+                i0, i0, i0, i1, i1, i1, i2, i2, i2
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn load_models<R: BufRead>(
+    reader: &mut R,
+) -> std::result::Result<Vec<tobj::Model>, tobj::LoadError> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    // materials are ignored, so referenced material libraries aren't opened
+    let (models, _materials) =
+        tobj::load_obj_buf(reader, &options, |_| Err(tobj::LoadError::OpenFileFailed))?;
+    Ok(models)
+}
+
+impl MeshSource for ObjMesh {
+    fn indices(&self) -> Cow<'_, [u32]> {
+        Cow::Borrowed(&self.indices)
+    }
+
+    fn positions(&self) -> VertexDataAdapter<'_> {
+        VertexDataAdapter::from_slice(&self.vertices, 0).expect("vertices start with the position")
+    }
+
+    /// The whole `Vertex` is compared, which includes the position.
+    fn attribute_streams(&self) -> Vec<VertexStream<'_>> {
+        vec![VertexStream::from_slice(&self.vertices)]
+    }
+}
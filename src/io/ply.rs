@@ -0,0 +1,682 @@
+//! Loading and saving PLY meshes and point clouds, in the ASCII and binary little-endian
+//! formats.
+//!
+//! Vertices are read from the `x`, `y`, `z`, `nx`, `ny`, `nz`, `red`, `green`, `blue` and
+//! `s`, `t` (or `u`, `v`) properties of the `vertex` element, and polygons from the
+//! `vertex_indices` list of the `face` element, triangulated as fans. Other elements and
+//! properties are skipped, and files without faces load as point clouds.
+
+use crate::{typed_to_bytes, Error, MeshSource, Result, VertexDataAdapter, VertexStream};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+
+/// Encoding of the body of a PLY file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// A triangle mesh or point cloud loaded from or saved to a PLY file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlyMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    /// Vertex colors normalized to `[0, 1]`, as expected by the point cloud simplifier.
+    pub colors: Option<Vec<[f32; 3]>>,
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+    /// Triangle list indices; empty for point clouds.
+    pub indices: Vec<u32>,
+}
+
+impl PlyMesh {
+    /// Loads the PLY file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(File::open(path)?)
+    }
+
+    /// Reads a PLY file from `reader`.
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let (format, elements) = read_header(&mut reader)?;
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+
+        let mut values = match format {
+            PlyFormat::Ascii => Values::Ascii(
+                std::str::from_utf8(&body)
+                    .map_err(|err| Error::Parse(format!("invalid ASCII body: {}", err)))?
+                    .split_ascii_whitespace(),
+            ),
+            PlyFormat::BinaryLittleEndian => Values::Binary {
+                data: &body,
+                offset: 0,
+            },
+        };
+
+        let mut mesh = Self::default();
+        for element in &elements {
+            match element.name.as_str() {
+                "vertex" => mesh.read_vertices(element, &mut values, body.len())?,
+                "face" => mesh.read_faces(element, &mut values, body.len())?,
+                _ => {
+                    for _ in 0..element.count {
+                        for property in &element.properties {
+                            values.skip_property(property)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let vertex_count = mesh.vertex_count();
+        if let Some(&index) = mesh
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(Error::Parse(format!(
+                "index {} is out of range for {} vertices",
+                index, vertex_count
+            )));
+        }
+        Ok(mesh)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns an adapter reading the positions, for the simplify, optimize and clusterize
+    /// functions.
+    pub fn positions_adapter(&self) -> VertexDataAdapter<'_> {
+        VertexDataAdapter::new(
+            typed_to_bytes(&self.positions),
+            mem::size_of::<[f32; 3]>(),
+            0,
+        )
+        .expect("positions are tightly packed")
+    }
+
+    /// Saves the mesh as a PLY file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: PlyFormat) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the mesh in the PLY format, with the attributes that are present.
+    ///
+    /// Colors are written as 8-bit values, and faces only if there are indices.
+    pub fn write<W: Write>(&self, writer: &mut W, format: PlyFormat) -> Result<()> {
+        self.validate()?;
+
+        let normals = self.normals.as_deref();
+        let colors = self.colors.as_deref();
+        let tex_coords = self.tex_coords.as_deref();
+
+        writeln!(writer, "ply")?;
+        match format {
+            PlyFormat::Ascii => writeln!(writer, "format ascii 1.0")?,
+            PlyFormat::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+        }
+        writeln!(writer, "element vertex {}", self.vertex_count())?;
+        for name in ["x", "y", "z"] {
+            writeln!(writer, "property float {}", name)?;
+        }
+        if normals.is_some() {
+            for name in ["nx", "ny", "nz"] {
+                writeln!(writer, "property float {}", name)?;
+            }
+        }
+        if colors.is_some() {
+            for name in ["red", "green", "blue"] {
+                writeln!(writer, "property uchar {}", name)?;
+            }
+        }
+        if tex_coords.is_some() {
+            for name in ["s", "t"] {
+                writeln!(writer, "property float {}", name)?;
+            }
+        }
+        if !self.indices.is_empty() {
+            writeln!(writer, "element face {}", self.indices.len() / 3)?;
+            writeln!(writer, "property list uchar uint vertex_indices")?;
+        }
+        writeln!(writer, "end_header")?;
+
+        for vertex in 0..self.vertex_count() {
+            let mut floats: Vec<f32> = self.positions[vertex].to_vec();
+            if let Some(normals) = normals {
+                floats.extend_from_slice(&normals[vertex]);
+            }
+            let color = colors.map(|colors| colors[vertex].map(quantize_color));
+            match format {
+                PlyFormat::Ascii => {
+                    let mut line: Vec<String> = floats.iter().map(f32::to_string).collect();
+                    if let Some(color) = color {
+                        line.extend(color.iter().map(u8::to_string));
+                    }
+                    if let Some(tex_coords) = tex_coords {
+                        line.extend(tex_coords[vertex].iter().map(f32::to_string));
+                    }
+                    writeln!(writer, "{}", line.join(" "))?;
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    for value in floats {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                    if let Some(color) = color {
+                        writer.write_all(&color)?;
+                    }
+                    if let Some(tex_coords) = tex_coords {
+                        for value in tex_coords[vertex] {
+                            writer.write_all(&value.to_le_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+
+        for triangle in self.indices.chunks_exact(3) {
+            match format {
+                PlyFormat::Ascii => {
+                    writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    writer.write_all(&[3u8])?;
+                    for &index in triangle {
+                        writer.write_all(&index.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let vertex_count = self.vertex_count();
+        let counts = [
+            ("normals", self.normals.as_ref().map(Vec::len)),
+            ("colors", self.colors.as_ref().map(Vec::len)),
+            ("tex_coords", self.tex_coords.as_ref().map(Vec::len)),
+        ];
+        for (name, count) in counts {
+            if let Some(count) = count.filter(|&count| count != vertex_count) {
+                return Err(Error::Config(format!(
+                    "mesh has {} {}, expected one per vertex ({})",
+                    count, name, vertex_count
+                )));
+            }
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(Error::Config(format!(
+                "index count ({}) must be a multiple of 3",
+                self.indices.len()
+            )));
+        }
+        if let Some(&index) = self
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(Error::Config(format!(
+                "index {} is out of range for {} vertices",
+                index, vertex_count
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_vertices(
+        &mut self,
+        element: &Element,
+        values: &mut Values<'_>,
+        body_size: usize,
+    ) -> Result<()> {
+        let find = |names: &[&str]| -> Option<usize> {
+            element.properties.iter().position(|property| {
+                matches!(property.kind, PropertyKind::Scalar(_))
+                    && names.contains(&property.name.as_str())
+            })
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let color = [
+            find(&["red", "r"]),
+            find(&["green", "g"]),
+            find(&["blue", "b"]),
+        ];
+        let tex_coord = [
+            find(&["s", "u", "texture_u"]),
+            find(&["t", "v", "texture_v"]),
+        ];
+        if position.iter().any(Option::is_none) {
+            return Err(Error::Parse(
+                "vertex element has no x, y and z properties".into(),
+            ));
+        }
+        let has_normals = normal.iter().all(Option::is_some);
+        let has_colors = color.iter().all(Option::is_some);
+        let has_tex_coords = tex_coord.iter().all(Option::is_some);
+
+        // the header is untrusted, so don't reserve more than the body could hold
+        let capacity = element.count.min(body_size);
+        self.positions = Vec::with_capacity(capacity);
+        let mut normals = Vec::with_capacity(if has_normals { capacity } else { 0 });
+        let mut colors = Vec::with_capacity(if has_colors { capacity } else { 0 });
+        let mut tex_coords = Vec::with_capacity(if has_tex_coords { capacity } else { 0 });
+
+        let mut row = vec![0f64; element.properties.len()];
+        for _ in 0..element.count {
+            for (value, property) in row.iter_mut().zip(&element.properties) {
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => *value = values.read(scalar)?,
+                    PropertyKind::List { .. } => values.skip_property(property)?,
+                }
+            }
+            let get = |index: Option<usize>| index.map_or(0f32, |index| row[index] as f32);
+            self.positions.push(position.map(get));
+            if has_normals {
+                normals.push(normal.map(get));
+            }
+            if has_colors {
+                colors.push(color.map(|index| {
+                    let index = index.expect("color channels are present");
+                    match element.properties[index].kind {
+                        PropertyKind::Scalar(ScalarType::U8) => (row[index] / 255.0) as f32,
+                        PropertyKind::Scalar(ScalarType::U16) => (row[index] / 65535.0) as f32,
+                        _ => row[index] as f32,
+                    }
+                }));
+            }
+            if has_tex_coords {
+                tex_coords.push(tex_coord.map(get));
+            }
+        }
+
+        self.normals = has_normals.then_some(normals);
+        self.colors = has_colors.then_some(colors);
+        self.tex_coords = has_tex_coords.then_some(tex_coords);
+        Ok(())
+    }
+
+    fn read_faces(
+        &mut self,
+        element: &Element,
+        values: &mut Values<'_>,
+        body_size: usize,
+    ) -> Result<()> {
+        let indices_property = element
+            .properties
+            .iter()
+            .position(|property| {
+                matches!(property.kind, PropertyKind::List { .. })
+                    && matches!(property.name.as_str(), "vertex_indices" | "vertex_index")
+            })
+            .ok_or_else(|| Error::Parse("face element has no vertex_indices list".into()))?;
+
+        self.indices = Vec::with_capacity(element.count.min(body_size).saturating_mul(3));
+        let mut polygon: Vec<u32> = Vec::new();
+        for _ in 0..element.count {
+            for (index, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::List { count, item } if index == indices_property => {
+                        let length = values.read_count(count)?;
+                        polygon.clear();
+                        for _ in 0..length {
+                            let value = values.read(item)?;
+                            if !(0.0..=f64::from(u32::MAX)).contains(&value) {
+                                return Err(Error::Parse(format!(
+                                    "invalid vertex index {}",
+                                    value
+                                )));
+                            }
+                            polygon.push(value as u32);
+                        }
+                        // triangulate as a fan; points and lines have no triangles
+                        for i in 2..polygon.len() {
+                            self.indices.extend_from_slice(&[
+                                polygon[0],
+                                polygon[i - 1],
+                                polygon[i],
+                            ]);
+                        }
+                    }
+                    _ => values.skip_property(property)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quantize_color(value: f32) -> u8 {
+    (value.clamp(0f32, 1f32) * 255f32).round() as u8
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(Error::Parse(format!("unknown property type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<(PlyFormat, Vec<Element>)> {
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> Result<()> {
+        line.clear();
+        if reader.read_line(line)? == 0 {
+            return Err(Error::Parse("PLY header ends before end_header".into()));
+        }
+        Ok(())
+    };
+
+    next_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(Error::Parse("missing PLY magic".into()));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        next_line(&mut line)?;
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+        match tokens.as_slice() {
+            ["end_header"] => break,
+            [] | ["comment" | "obj_info", ..] => {}
+            ["format", name, "1.0"] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    _ => return Err(Error::Parse(format!("unsupported PLY format '{}'", name))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: (*name).to_owned(),
+                count: count
+                    .parse()
+                    .map_err(|err| Error::Parse(format!("invalid element count: {}", err)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| Error::Parse("property before any element".into()))?;
+                element.properties.push(Property {
+                    name: (*name).to_owned(),
+                    kind: PropertyKind::List {
+                        count: ScalarType::parse(count)?,
+                        item: ScalarType::parse(item)?,
+                    },
+                });
+            }
+            ["property", scalar, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| Error::Parse("property before any element".into()))?;
+                element.properties.push(Property {
+                    name: (*name).to_owned(),
+                    kind: PropertyKind::Scalar(ScalarType::parse(scalar)?),
+                });
+            }
+            _ => {
+                return Err(Error::Parse(format!(
+                    "invalid PLY header line '{}'",
+                    line.trim_end()
+                )))
+            }
+        }
+    }
+
+    let format = format.ok_or_else(|| Error::Parse("PLY header has no format".into()))?;
+    Ok((format, elements))
+}
+
+/// Values of the body of a PLY file, read in order.
+enum Values<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], offset: usize },
+}
+
+impl Values<'_> {
+    fn read(&mut self, scalar: ScalarType) -> Result<f64> {
+        match self {
+            Self::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| Error::Parse("PLY body ends early".into()))?;
+                token
+                    .parse()
+                    .map_err(|err| Error::Parse(format!("invalid value '{}': {}", token, err)))
+            }
+            Self::Binary { data, offset } => {
+                let size = scalar.size();
+                let bytes = data
+                    .get(*offset..*offset + size)
+                    .ok_or_else(|| Error::Parse("PLY body ends early".into()))?;
+                *offset += size;
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(bytes);
+                Ok(match scalar {
+                    ScalarType::I8 => f64::from(bytes[0] as i8),
+                    ScalarType::U8 => f64::from(bytes[0]),
+                    ScalarType::I16 => f64::from(i16::from_le_bytes([buffer[0], buffer[1]])),
+                    ScalarType::U16 => f64::from(u16::from_le_bytes([buffer[0], buffer[1]])),
+                    ScalarType::I32 => f64::from(i32::from_le_bytes([
+                        buffer[0], buffer[1], buffer[2], buffer[3],
+                    ])),
+                    ScalarType::U32 => f64::from(u32::from_le_bytes([
+                        buffer[0], buffer[1], buffer[2], buffer[3],
+                    ])),
+                    ScalarType::F32 => f64::from(f32::from_le_bytes([
+                        buffer[0], buffer[1], buffer[2], buffer[3],
+                    ])),
+                    ScalarType::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    fn read_count(&mut self, scalar: ScalarType) -> Result<usize> {
+        let count = self.read(scalar)?;
+        if !(0.0..=f64::from(u32::MAX)).contains(&count) || count.fract() != 0.0 {
+            return Err(Error::Parse(format!("invalid list length {}", count)));
+        }
+        Ok(count as usize)
+    }
+
+    fn skip_property(&mut self, property: &Property) -> Result<()> {
+        match property.kind {
+            PropertyKind::Scalar(scalar) => {
+                self.read(scalar)?;
+            }
+            PropertyKind::List { count, item } => {
+                for _ in 0..self.read_count(count)? {
+                    self.read(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MeshSource for PlyMesh {
+    fn indices(&self) -> Cow<'_, [u32]> {
+        Cow::Borrowed(&self.indices)
+    }
+
+    fn positions(&self) -> VertexDataAdapter<'_> {
+        self.positions_adapter()
+    }
+
+    fn attribute_streams(&self) -> Vec<VertexStream<'_>> {
+        let mut streams = Vec::new();
+        if let Some(normals) = &self.normals {
+            streams.push(VertexStream::from_slice(normals));
+        }
+        if let Some(colors) = &self.colors {
+            streams.push(VertexStream::from_slice(colors));
+        }
+        if let Some(tex_coords) = &self.tex_coords {
+            streams.push(VertexStream::from_slice(tex_coords));
+        }
+        streams
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlyFormat, PlyMesh};
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    fn binary_quad(indices: &[i32]) -> Vec<u8> {
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+"
+        .to_vec();
+        for position in [
+            [0f32, 0f32, 0f32],
+            [1f32, 0f32, 0f32],
+            [1f32, 1f32, 0f32],
+            [0f32, 1f32, 0f32],
+        ] {
+            for value in position {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        data.push(indices.len() as u8);
+        for index in indices {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_ascii() {
+        let mesh = PlyMesh::read(ASCII.as_bytes()).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.positions[2], [1f32, 1f32, 0f32]);
+        assert_eq!(
+            mesh.colors.as_deref().unwrap()[..2],
+            [[1f32, 0f32, 0f32], [0f32, 1f32, 0f32]]
+        );
+        assert_eq!(mesh.normals, None);
+        // polygons are triangulated as fans
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn test_read_binary() {
+        let mesh = PlyMesh::read(&binary_quad(&[0, 1, 2, 3])[..]).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.positions[3], [0f32, 1f32, 0f32]);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+
+        let mut ascii = Vec::new();
+        mesh.write(&mut ascii, PlyFormat::Ascii).unwrap();
+        assert_eq!(PlyMesh::read(&ascii[..]).unwrap(), mesh);
+
+        let mut binary = Vec::new();
+        mesh.write(&mut binary, PlyFormat::BinaryLittleEndian)
+            .unwrap();
+        assert_eq!(PlyMesh::read(&binary[..]).unwrap(), mesh);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let out_of_range = ASCII.replace("4 0 1 2 3", "4 0 1 2 4");
+        assert!(PlyMesh::read(out_of_range.as_bytes()).is_err());
+        assert!(PlyMesh::read(&binary_quad(&[0, 1, 4])[..]).is_err());
+        assert!(PlyMesh::read(&binary_quad(&[0, -1, 2])[..]).is_err());
+
+        let truncated = &ASCII[..ASCII.len() - 4];
+        assert!(PlyMesh::read(truncated.as_bytes()).is_err());
+        let binary = binary_quad(&[0, 1, 2, 3]);
+        for length in [binary.len() - 1, binary.len() - 17, binary.len() - 40] {
+            assert!(PlyMesh::read(&binary[..length]).is_err());
+        }
+
+        let no_header = ASCII.replace("end_header\n", "");
+        assert!(PlyMesh::read(no_header.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{Error, GltfCompressionFilter, Result, VertexDataAdapter};
+
+/// Meaning of a vertex attribute, see `VertexLayout`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AttributeSemantic {
+    Position,
+    Normal,
+    /// Tangent, optionally with the bitangent sign in `w`.
+    Tangent,
+    /// Rotation quaternion, e.g. of an instance or a tangent frame.
+    Rotation,
+    /// Texture coordinate set.
+    TexCoord(u8),
+    /// Color set.
+    Color(u8),
+    /// Application defined attribute.
+    Custom(u32),
+}
+
+/// Storage format of a vertex attribute; the number is the component count.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AttributeFormat {
+    Float32(usize),
+    Float16(usize),
+    Unorm16(usize),
+    Snorm16(usize),
+    Unorm8(usize),
+    Snorm8(usize),
+    /// Three 10-bit and one 2-bit normalized signed components packed in 32 bits.
+    Snorm10x3,
+}
+
+impl AttributeFormat {
+    /// Size of the attribute in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            AttributeFormat::Float32(count) => count.saturating_mul(4),
+            AttributeFormat::Float16(count)
+            | AttributeFormat::Unorm16(count)
+            | AttributeFormat::Snorm16(count) => count.saturating_mul(2),
+            AttributeFormat::Unorm8(count) | AttributeFormat::Snorm8(count) => count,
+            AttributeFormat::Snorm10x3 => 4,
+        }
+    }
+}
+
+/// A single attribute of a `VertexLayout`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LayoutAttribute {
+    pub semantic: AttributeSemantic,
+    /// Byte offset of the attribute within a vertex.
+    pub offset: usize,
+    pub format: AttributeFormat,
+}
+
+impl LayoutAttribute {
+    /// Returns the `EXT_meshopt_compression` filter suited to the attribute, based on its
+    /// semantic and format, for use with `encode_filter_*` or `GltfCompressedBufferView`.
+    pub fn filter(&self) -> GltfCompressionFilter {
+        match (self.semantic, self.format) {
+            (
+                AttributeSemantic::Normal | AttributeSemantic::Tangent,
+                AttributeFormat::Snorm8(4) | AttributeFormat::Snorm16(4),
+            ) => GltfCompressionFilter::Octahedral,
+            (AttributeSemantic::Rotation, AttributeFormat::Snorm16(4)) => {
+                GltfCompressionFilter::Quaternion
+            }
+            (_, AttributeFormat::Float32(_)) => GltfCompressionFilter::Exponential,
+            _ => GltfCompressionFilter::None,
+        }
+    }
+}
+
+/// Describes the attributes stored in an interleaved vertex buffer, so that the same
+/// description can be passed to simplification, shadow index generation, seam locking,
+/// encoding and compression.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    /// Size of a vertex in bytes.
+    pub stride: usize,
+    pub attributes: Vec<LayoutAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new(stride: usize) -> Self {
+        Self {
+            stride,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Adds an attribute at byte `offset`.
+    pub fn with_attribute(
+        mut self,
+        semantic: AttributeSemantic,
+        offset: usize,
+        format: AttributeFormat,
+    ) -> Self {
+        self.attributes.push(LayoutAttribute {
+            semantic,
+            offset,
+            format,
+        });
+        self
+    }
+
+    /// Returns the first attribute with the given semantic.
+    pub fn find(&self, semantic: AttributeSemantic) -> Option<&LayoutAttribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.semantic == semantic)
+    }
+
+    /// Returns the attribute with the given semantic, or an error if it is missing.
+    pub(crate) fn require(&self, semantic: AttributeSemantic) -> Result<&LayoutAttribute> {
+        self.find(semantic)
+            .ok_or_else(|| Error::Config(format!("vertex layout has no {:?} attribute", semantic)))
+    }
+
+    /// Checks that all attributes fit within the stride.
+    pub fn validate(&self) -> Result<()> {
+        for attribute in &self.attributes {
+            let end = attribute.offset.checked_add(attribute.format.size());
+            if !matches!(end, Some(end) if end <= self.stride) {
+                return Err(Error::Config(format!(
+                    "{:?} attribute at offset {} ({} bytes) must fit within stride ({})",
+                    attribute.semantic,
+                    attribute.offset,
+                    attribute.format.size(),
+                    self.stride
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a `VertexDataAdapter` for `data`, which must have a `Float32(3)` position.
+    pub fn adapter<'a>(&self, data: &'a [u8]) -> Result<VertexDataAdapter<'a>> {
+        self.validate()?;
+        let position = self.require(AttributeSemantic::Position)?;
+        if position.format != AttributeFormat::Float32(3) {
+            return Err(Error::Config(format!(
+                "position format ({:?}) must be Float32(3)",
+                position.format
+            )));
+        }
+        VertexDataAdapter::new(data, self.stride, position.offset)
+    }
+}
+
+/// Implemented by vertex types to describe their layout.
+pub trait HasVertexLayout {
+    fn vertex_layout() -> VertexLayout;
+}
@@ -81,22 +81,54 @@
 // This crate is doing a lot of FFI and byte munging
 #![allow(unsafe_code)]
 
+pub mod allocator;
 pub mod analyze;
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "bevy")]
+pub mod bevy;
 pub mod clusterize;
+pub mod compression;
+pub mod context;
+pub mod decoding;
 pub mod encoding;
 pub mod error;
+pub mod export;
 pub mod ffi;
+pub mod hierarchy;
+pub mod io;
+pub mod layout;
+pub mod locks;
+pub mod lod;
+#[cfg(any(feature = "glam", feature = "mint", feature = "nalgebra"))]
+mod math;
+pub mod mesh_shader;
 pub mod optimize;
+pub mod overdraw;
 pub mod packing;
+pub mod quantization;
 pub mod remap;
+#[cfg(feature = "rkyv")]
+mod rkyv_impls;
+pub mod safe;
+#[cfg(feature = "serde")]
+mod serde_impls;
 pub mod shadow;
 pub mod simplify;
+pub mod source;
+pub mod streaming;
 pub mod stripify;
 pub mod utilities;
+#[cfg(feature = "wgpu")]
+pub mod wgpu;
 
+#[cfg(feature = "rayon")]
+pub use crate::batch::*;
 pub use crate::{
-    analyze::*, clusterize::*, encoding::*, error::*, optimize::*, packing::*, remap::*, shadow::*,
-    simplify::*, stripify::*, utilities::*,
+    allocator::*, analyze::*, clusterize::*, compression::*, context::*, decoding::*, encoding::*,
+    error::*, export::*, hierarchy::*, layout::*, locks::*, lod::*, mesh_shader::*, optimize::*,
+    overdraw::*, packing::*, quantization::*, remap::*, shadow::*, simplify::*, source::*,
+    streaming::*, stripify::*, utilities::*,
 };
 use std::marker::PhantomData;
 
@@ -104,21 +136,32 @@ use std::marker::PhantomData;
 ///
 /// Each element takes size bytes, with stride controlling
 /// the spacing between successive elements.
+///
+/// Streams only ever read the vertex data they point to, so they are `Send` and `Sync`
+/// and can be shared across threads like the `&'a [u8]` they stand in for. Safe code can
+/// only create streams from borrowed slices; the pointer constructors are `unsafe`.
 #[derive(Debug, Copy, Clone)]
 pub struct VertexStream<'a> {
     /// Pointer to buffer which contains vertex data.
-    pub data: *const u8,
+    pub(crate) data: *const u8,
     /// Space between vertices inside the buffer (in bytes).
-    pub stride: usize,
+    pub(crate) stride: usize,
     /// The size in bytes of the vertex attribute this Stream is representing.
-    pub size: usize,
+    pub(crate) size: usize,
+    /// Number of vertices in the buffer, if known.
+    pub(crate) vertex_count: Option<usize>,
 
     _marker: PhantomData<&'a ()>,
 }
 
 impl<'a> VertexStream<'a> {
     /// Create a `VertexStream` for a buffer consisting only of elements of type `T`.
-    pub fn new<T>(ptr: *const T) -> VertexStream<'a> {
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to as many values of `T` as the functions the stream is passed to
+    /// read, which stay valid and unmodified for `'a` and can be read from any thread.
+    pub unsafe fn new<T>(ptr: *const T) -> VertexStream<'a> {
         Self::new_with_stride::<T, T>(ptr, std::mem::size_of::<T>())
     }
 
@@ -129,7 +172,11 @@ impl<'a> VertexStream<'a> {
     ///
     /// (The `VertexType` does not need to be a concrete type,
     /// it is only used here to avoid casts on the caller side).
-    pub fn new_with_stride<T, VertexType>(
+    ///
+    /// # Safety
+    ///
+    /// Same as `new`, for vertices `stride` bytes apart.
+    pub unsafe fn new_with_stride<T, VertexType>(
         ptr: *const VertexType,
         stride: usize,
     ) -> VertexStream<'a> {
@@ -137,8 +184,117 @@ impl<'a> VertexStream<'a> {
             data: ptr.cast(),
             stride,
             size: std::mem::size_of::<T>(),
+            vertex_count: None,
 
             _marker: PhantomData,
         }
     }
+
+    /// Create a `VertexStream` borrowing a slice of elements of type `T`.
+    ///
+    /// Unlike streams created from pointers, the length of the slice is known, so functions
+    /// taking streams can check that it contains enough vertices.
+    pub fn from_slice<T>(vertices: &'a [T]) -> VertexStream<'a> {
+        VertexStream {
+            vertex_count: Some(vertices.len()),
+            // the slice covers `vertices.len()` values, as checked by `validate`
+            ..unsafe { Self::new(vertices.as_ptr()) }
+        }
+    }
+
+    /// Create a `VertexStream` borrowing raw vertex data, where each element takes `size`
+    /// bytes and successive elements are `stride` bytes apart, starting at the beginning of
+    /// `data`, e.g. an attribute of interleaved vertices sliced from its offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0 or larger than `stride`.
+    pub fn from_bytes(data: &'a [u8], stride: usize, size: usize) -> VertexStream<'a> {
+        assert!(size > 0 && size <= stride);
+        // the last element only needs `size` bytes
+        let vertex_count = if data.len() >= size {
+            (data.len() - size) / stride + 1
+        } else {
+            0
+        };
+        VertexStream {
+            data: data.as_ptr(),
+            stride,
+            size,
+            vertex_count: Some(vertex_count),
+
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a `VertexStream` for the `size` bytes at `offset` within each vertex of type
+    /// `T`, e.g. a single attribute of an interleaved vertex located with `offset_of!`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0 or the attribute doesn't fit within `T`.
+    pub fn attribute<T>(vertices: &'a [T], offset: usize, size: usize) -> VertexStream<'a> {
+        let stride = std::mem::size_of::<T>();
+        assert!(
+            size > 0 && matches!(offset.checked_add(size), Some(end) if end <= stride),
+            "attribute of {} bytes at offset {} must be non-empty and fit within stride ({})",
+            size,
+            offset,
+            stride
+        );
+        VertexStream {
+            // every attribute lies within its vertex, so the slice covers all of them
+            data: vertices.as_ptr().cast::<u8>().wrapping_add(offset),
+            stride,
+            size,
+            vertex_count: Some(vertices.len()),
+
+            _marker: PhantomData,
+        }
+    }
+
+    /// Space between vertices inside the buffer (in bytes).
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// The size in bytes of the vertex attribute this stream is representing.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Checks that the stream holds at least `vertex_count` vertices, if its length is known.
+    pub(crate) fn validate(&self, vertex_count: usize) {
+        if let Some(count) = self.vertex_count {
+            assert!(
+                count >= vertex_count,
+                "vertex stream contains {} vertices, expected at least {}",
+                count,
+                vertex_count
+            );
+        }
+    }
 }
+
+// The stream is a read-only view of vertex data borrowed for `'a`, equivalent to `&'a [u8]`:
+// its fields are private, and streams are only created from slices or by unsafe constructors
+// whose callers guarantee the same.
+unsafe impl Send for VertexStream<'_> {}
+unsafe impl Sync for VertexStream<'_> {}
+
+// Core types are shared across threads when processing meshes in parallel; these fail to
+// compile if a change to their fields makes them `!Send` or `!Sync`.
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn assert_core_types() {
+        assert_send_sync::<VertexStream<'_>>();
+        assert_send_sync::<VertexDataAdapter<'_>>();
+        assert_send_sync::<Meshlets>();
+        assert_send_sync::<Meshlet<'_>>();
+        assert_send_sync::<Bounds>();
+        assert_send_sync::<ClusterBounds>();
+        assert_send_sync::<IndexBuffer>();
+    }
+};
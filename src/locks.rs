@@ -0,0 +1,238 @@
+use crate::{
+    validate_indices, AttributeFormat, AttributeSemantic, Error, Result, VertexDataAdapter,
+    VertexLayout,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Describes where the attributes used for seam detection are stored in interleaved vertex data.
+///
+/// Prefer creating it with `from_vertex_layout` over filling in the offsets by hand, so that
+/// they stay in sync with the `VertexLayout` used elsewhere.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SeamLayout {
+    /// Byte offset of the texture coordinates (2 x `f32`) within a vertex, if present.
+    pub uv_offset: Option<usize>,
+    /// Byte offset of the normal (3 x `f32`) within a vertex, if present.
+    pub normal_offset: Option<usize>,
+    /// Texture coordinates that differ by more than this in any component form a UV seam.
+    pub uv_epsilon: f32,
+    /// Normals with a dot product (cosine of the angle between them) below this form a hard edge.
+    pub normal_threshold: f32,
+}
+
+impl Default for SeamLayout {
+    fn default() -> Self {
+        Self {
+            uv_offset: None,
+            normal_offset: None,
+            uv_epsilon: 1e-6,
+            normal_threshold: 0.999,
+        }
+    }
+}
+
+impl SeamLayout {
+    /// Uses the first texture coordinate set and the normal of `layout`, when they are
+    /// stored as `Float32(2)` and `Float32(3)` respectively.
+    pub fn from_vertex_layout(layout: &VertexLayout) -> Self {
+        let offset = |semantic, format| {
+            layout
+                .find(semantic)
+                .filter(|attribute| attribute.format == format)
+                .map(|attribute| attribute.offset)
+        };
+        Self {
+            uv_offset: offset(AttributeSemantic::TexCoord(0), AttributeFormat::Float32(2)),
+            normal_offset: offset(AttributeSemantic::Normal, AttributeFormat::Float32(3)),
+            ..Self::default()
+        }
+    }
+}
+
+fn edge_key(a: u32, b: u32) -> u64 {
+    (u64::from(a) << 32) | u64::from(b)
+}
+
+fn mark_border_vertices(indices: &[u32], locks: &mut [bool]) {
+    let edges: HashSet<u64> = indices
+        .chunks_exact(3)
+        .flat_map(|t| {
+            [
+                edge_key(t[0], t[1]),
+                edge_key(t[1], t[2]),
+                edge_key(t[2], t[0]),
+            ]
+        })
+        .collect();
+
+    for t in indices.chunks_exact(3) {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            // an edge without a matching opposite half-edge lies on the border
+            if !edges.contains(&edge_key(b, a)) {
+                locks[a as usize] = true;
+                locks[b as usize] = true;
+            }
+        }
+    }
+}
+
+/// Computes vertex locks for the vertices that lie on the topological border of the mesh,
+/// i.e. vertices of edges that are only used by one triangle (in one direction).
+///
+/// The result has one entry per vertex and can be passed to `simplify_with_locks` as `vertex_lock`.
+///
+/// Fails if an index is out of range.
+pub fn compute_border_locks(indices: &[u32], vertex_count: usize) -> Result<Vec<bool>> {
+    validate_indices(indices, vertex_count)?;
+    let mut locks = vec![false; vertex_count];
+    mark_border_vertices(indices, &mut locks);
+    Ok(locks)
+}
+
+/// Computes vertex locks for the vertices that lie on the border of any chunk of the mesh,
+/// where `chunks` are index ranges into `indices` (e.g. terrain tiles).
+///
+/// This includes vertices on the topological border of each chunk as well as vertices
+/// referenced by more than one chunk, so that chunks simplified with these locks can be
+/// stitched together without gaps.
+///
+/// The result has one entry per vertex and can be passed to `simplify_with_locks` as `vertex_lock`.
+///
+/// Fails if an index is out of range or a chunk range is out of bounds.
+pub fn compute_chunk_border_locks(
+    indices: &[u32],
+    vertex_count: usize,
+    chunks: &[Range<usize>],
+) -> Result<Vec<bool>> {
+    const NO_CHUNK: usize = usize::MAX;
+
+    validate_indices(indices, vertex_count)?;
+    if let Some(range) = chunks
+        .iter()
+        .find(|range| range.start > range.end || range.end > indices.len())
+    {
+        return Err(Error::Config(format!(
+            "chunk range {:?} is out of bounds for {} indices",
+            range,
+            indices.len()
+        )));
+    }
+
+    let mut locks = vec![false; vertex_count];
+    let mut owner = vec![NO_CHUNK; vertex_count];
+    for (chunk, range) in chunks.iter().enumerate() {
+        let chunk_indices = &indices[range.clone()];
+        mark_border_vertices(chunk_indices, &mut locks);
+        for &index in chunk_indices {
+            let index = index as usize;
+            if owner[index] == NO_CHUNK {
+                owner[index] = chunk;
+            } else if owner[index] != chunk {
+                locks[index] = true;
+            }
+        }
+    }
+    Ok(locks)
+}
+
+/// Computes vertex locks for UV seams and hard normal edges.
+///
+/// Vertices that share a position with another vertex but have different texture coordinates
+/// or normals (as described by `layout`) are locked, so that `simplify_with_locks` can't
+/// collapse them and tear the seam apart.
+///
+/// The result has one entry per vertex and can be passed to `simplify_with_locks` as `vertex_lock`.
+pub fn compute_seam_locks(
+    vertices: &VertexDataAdapter<'_>,
+    layout: &SeamLayout,
+) -> Result<Vec<bool>> {
+    for (name, offset, size) in [
+        ("uv_offset", layout.uv_offset, 8),
+        ("normal_offset", layout.normal_offset, 12),
+    ] {
+        if let Some(offset) = offset {
+            if !matches!(offset.checked_add(size), Some(end) if end <= vertices.vertex_stride) {
+                return Err(Error::Config(format!(
+                    "{} ({}) must leave room for {} bytes within vertex_stride ({})",
+                    name, offset, size, vertices.vertex_stride
+                )));
+            }
+        }
+    }
+
+    // group vertices by their exact position
+    let mut groups: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for vertex in 0..vertices.vertex_count {
+        let p = vertices.position(vertex);
+        // canonicalize -0.0 so it matches 0.0
+        let key = p.map(|v| if v == 0f32 { 0 } else { v.to_bits() });
+        groups.entry(key).or_default().push(vertex);
+    }
+
+    let is_seam = |a: usize, b: usize| {
+        if let Some(offset) = layout.uv_offset {
+            let ta: [f32; 2] = vertices.attribute(a, offset);
+            let tb: [f32; 2] = vertices.attribute(b, offset);
+            if (ta[0] - tb[0]).abs() > layout.uv_epsilon
+                || (ta[1] - tb[1]).abs() > layout.uv_epsilon
+            {
+                return true;
+            }
+        }
+        if let Some(offset) = layout.normal_offset {
+            let na: [f32; 3] = vertices.attribute(a, offset);
+            let nb: [f32; 3] = vertices.attribute(b, offset);
+            let dot = na[0] * nb[0] + na[1] * nb[1] + na[2] * nb[2];
+            let length = ((na[0] * na[0] + na[1] * na[1] + na[2] * na[2])
+                * (nb[0] * nb[0] + nb[1] * nb[1] + nb[2] * nb[2]))
+                .sqrt();
+            if length > 0f32 && dot / length < layout.normal_threshold {
+                return true;
+            }
+        }
+        false
+    };
+
+    let mut locks = vec![false; vertices.vertex_count];
+    for group in groups.values().filter(|group| group.len() > 1) {
+        for (i, &a) in group.iter().enumerate() {
+            for &b in &group[i + 1..] {
+                if is_seam(a, b) {
+                    locks[a] = true;
+                    locks[b] = true;
+                }
+            }
+        }
+    }
+    Ok(locks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compute_border_locks, compute_chunk_border_locks};
+
+    #[test]
+    fn test_border_locks() {
+        // 3x3 vertex grid, 2x2 quads; only the center vertex is interior
+        let mut indices: Vec<u32> = Vec::new();
+        for y in 0..2 {
+            for x in 0..2 {
+                let i = y * 3 + x;
+                indices.extend_from_slice(&[i, i + 1, i + 3, i + 3, i + 1, i + 4]);
+            }
+        }
+
+        let locks = compute_border_locks(&indices, 9).unwrap();
+        assert_eq!(locks.iter().filter(|&&lock| lock).count(), 8);
+        assert!(!locks[4]);
+
+        // one chunk per quad; the center vertex is shared between all chunks
+        let locks =
+            compute_chunk_border_locks(&indices, 9, &[0..6, 6..12, 12..18, 18..24]).unwrap();
+        assert!(locks[4]);
+
+        assert!(compute_border_locks(&indices, 8).is_err());
+        assert!(compute_chunk_border_locks(&indices, 9, &[0..6, 18..30]).is_err());
+    }
+}
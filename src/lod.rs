@@ -0,0 +1,299 @@
+use crate::{
+    ffi, optimize_overdraw_in_place, optimize_vertex_cache_in_place, remap_index_buffer,
+    simplify_in_place, simplify_result, simplify_scale, simplify_sloppy_result, SimplifyOptions,
+    TargetCount, TargetError, VertexDataAdapter,
+};
+
+/// Configuration for `generate_lod_chain`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LodConfig {
+    /// Total number of levels, including the source mesh as level 0.
+    pub lod_count: usize,
+    /// Fraction of triangles kept by each level relative to the previous one, e.g. 0.7
+    /// makes level `i` target `0.7^i` of the source triangles.
+    pub reduction: f32,
+    /// Error that can be tolerated by each level.
+    pub target_error: TargetError,
+    /// Options passed to the simplifier; `ErrorAbsolute` is controlled by `target_error`.
+    pub options: SimplifyOptions,
+    /// Simplify every level from the source mesh instead of from the previous level.
+    /// This sometimes produces better results, but simplifying from the previous level is faster.
+    pub simplify_from_source: bool,
+    /// Fall back to `simplify_sloppy` when the regular simplifier can't reach the target
+    /// index count, e.g. because of topology constraints.
+    pub sloppy_fallback: bool,
+    /// Optimize each level for vertex cache and overdraw.
+    pub optimize: bool,
+    /// Overdraw threshold used when `optimize` is set; see `optimize_overdraw_in_place`.
+    pub overdraw_threshold: f32,
+    /// Optimize the shared vertex buffer for vertex fetch across all levels, see `LodChain::vertex_remap`.
+    pub optimize_vertex_fetch: bool,
+}
+
+impl Default for LodConfig {
+    fn default() -> Self {
+        Self {
+            lod_count: 5,
+            reduction: 0.7,
+            target_error: TargetError::Relative(1e-2),
+            options: SimplifyOptions::None,
+            simplify_from_source: false,
+            sloppy_fallback: false,
+            optimize: true,
+            overdraw_threshold: 1.0,
+            optimize_vertex_fetch: false,
+        }
+    }
+}
+
+/// A single level of detail produced by `generate_lod_chain`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LodLevel {
+    /// Index buffer of this level, referencing the shared vertex buffer.
+    pub indices: Vec<u32>,
+    /// Simplification error of this level, in the units of `LodConfig::target_error`.
+    pub error: f32,
+    /// Whether this level was produced by the sloppy fallback.
+    pub sloppy: bool,
+}
+
+/// Levels of detail sharing a single vertex buffer, as produced by `generate_lod_chain`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LodChain {
+    /// The levels, from the most detailed (level 0, the source mesh) to the coarsest.
+    pub levels: Vec<LodLevel>,
+    /// When `LodConfig::optimize_vertex_fetch` is set, the remap table that has to be applied
+    /// to the vertex buffer with `remap_vertex_buffer` (using `vertex_count`); the indices of
+    /// all levels already reference the remapped vertices.
+    pub vertex_remap: Option<Vec<u32>>,
+    /// Number of vertices referenced by the levels.
+    pub vertex_count: usize,
+}
+
+impl LodChain {
+    /// Concatenates all levels into one index buffer, returning it together with the index
+    /// range of each level.
+    ///
+    /// Coarse levels are placed first, so that the vertex range they reference is as small
+    /// as possible, which also benefits vertex fetch optimization of the combined buffer.
+    pub fn concatenate(&self) -> (Vec<u32>, Vec<std::ops::Range<usize>>) {
+        let total_index_count = self.levels.iter().map(|level| level.indices.len()).sum();
+        let mut indices: Vec<u32> = Vec::with_capacity(total_index_count);
+        let mut ranges = vec![0..0; self.levels.len()];
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            ranges[i] = indices.len()..indices.len() + level.indices.len();
+            indices.extend_from_slice(&level.indices);
+        }
+        (indices, ranges)
+    }
+}
+
+/// Generates a chain of progressively simplified levels of detail that share the source
+/// vertex buffer.
+///
+/// Level 0 is the source mesh; each subsequent level targets `LodConfig::reduction` of the
+/// triangles of the previous one. Generation stops early if a level can't be simplified
+/// any further, so the chain may contain fewer than `LodConfig::lod_count` levels.
+pub fn generate_lod_chain(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    config: &LodConfig,
+) -> LodChain {
+    let (target_error, scale) = match config.target_error {
+        TargetError::Relative(error) => (error, 1f32),
+        TargetError::Absolute(error) => {
+            let scale = simplify_scale(vertices);
+            (if scale > 0f32 { error / scale } else { 0f32 }, scale)
+        }
+    };
+    let options = config.options - SimplifyOptions::ErrorAbsolute;
+
+    let mut levels: Vec<LodLevel> = Vec::with_capacity(config.lod_count);
+    levels.push(LodLevel {
+        indices: indices.to_vec(),
+        error: 0f32,
+        sloppy: false,
+    });
+
+    for i in 1..config.lod_count {
+        let source = if config.simplify_from_source {
+            indices
+        } else {
+            &levels[levels.len() - 1].indices
+        };
+        let target_count = TargetCount::Ratio(config.reduction.powi(i as i32))
+            .index_count(indices.len())
+            .min(source.len());
+
+        let mut result = simplify_result(source, vertices, target_count, target_error, options);
+        let mut sloppy = false;
+        if config.sloppy_fallback && result.indices.len() > target_count {
+            let fallback = simplify_sloppy_result(source, vertices, target_count, target_error);
+            if fallback.indices.len() < result.indices.len() {
+                result = fallback;
+                sloppy = true;
+            }
+        }
+
+        let previous = &levels[levels.len() - 1];
+        if result.indices.is_empty() || result.indices.len() >= previous.indices.len() {
+            break;
+        }
+
+        // keep errors monotonic when simplifying from the previous level
+        let base_error = if config.simplify_from_source {
+            0f32
+        } else {
+            previous.error
+        };
+        levels.push(LodLevel {
+            indices: result.indices,
+            error: base_error.max(result.error * scale),
+            sloppy,
+        });
+    }
+
+    if config.optimize {
+        for level in &mut levels {
+            optimize_vertex_cache_in_place(&mut level.indices, vertices.vertex_count);
+            optimize_overdraw_in_place(&mut level.indices, vertices, config.overdraw_threshold);
+        }
+    }
+
+    let mut chain = LodChain {
+        levels,
+        vertex_remap: None,
+        vertex_count: vertices.vertex_count,
+    };
+
+    if config.optimize_vertex_fetch {
+        // the vertex order depends on the final index order, with coarse levels first
+        let (combined, _) = chain.concatenate();
+        let mut remap: Vec<u32> = vec![0; vertices.vertex_count];
+        let vertex_count = unsafe {
+            ffi::meshopt_optimizeVertexFetchRemap(
+                remap.as_mut_ptr(),
+                combined.as_ptr(),
+                combined.len(),
+                vertices.vertex_count,
+            )
+        };
+        for level in &mut chain.levels {
+            level.indices = remap_index_buffer(Some(&level.indices), 0, &remap);
+        }
+        chain.vertex_remap = Some(remap);
+        chain.vertex_count = vertex_count;
+    }
+
+    chain
+}
+
+/// A pass made by an `IncrementalSimplifier`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SimplifyStep {
+    /// Number of indices after the pass.
+    pub index_count: usize,
+    /// Error after the pass, see `IncrementalSimplifier::error`.
+    pub error: f32,
+}
+
+/// Simplifies a mesh step by step toward lower targets, continuing from the previous result
+/// instead of restarting from the source mesh every time, e.g. for interactive LOD previews.
+///
+/// The simplifier approaches each target in passes that remove at most half of the
+/// remaining triangles, simplifying the current result in place, and records the index
+/// count after every pass; `index_counts` returns them from coarse to fine, so a preview can
+/// step back through the intermediate results without simplifying again. Since every pass
+/// works on an already reduced mesh, reaching a target costs at most about twice as much
+/// as a single `simplify` call.
+#[derive(Debug)]
+pub struct IncrementalSimplifier<'a> {
+    vertices: &'a VertexDataAdapter<'a>,
+    options: SimplifyOptions,
+    source: Vec<u32>,
+    indices: Vec<u32>,
+    steps: Vec<SimplifyStep>,
+}
+
+impl<'a> IncrementalSimplifier<'a> {
+    pub fn new(
+        indices: &[u32],
+        vertices: &'a VertexDataAdapter<'a>,
+        options: SimplifyOptions,
+    ) -> Self {
+        Self {
+            vertices,
+            options,
+            source: indices.to_vec(),
+            indices: indices.to_vec(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// The current result, referencing vertices from the original vertex buffer.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Error of the current result: like in `generate_lod_chain`, the largest error reported
+    /// by the steps taken, which keeps errors monotonic; 0 before the first step.
+    pub fn error(&self) -> f32 {
+        self.steps.last().map_or(0f32, |step| step.error)
+    }
+
+    /// The passes made since creation or the last `reset`, from the finest to the coarsest
+    /// result.
+    pub fn steps(&self) -> &[SimplifyStep] {
+        &self.steps
+    }
+
+    /// The index counts the simplifier passed through, from the current (coarsest) result
+    /// to the source mesh.
+    pub fn index_counts(&self) -> Vec<usize> {
+        self.steps
+            .iter()
+            .rev()
+            .map(|step| step.index_count)
+            .chain(std::iter::once(self.source.len()))
+            .collect()
+    }
+
+    /// Continues simplifying the current result toward `target_count` indices, see
+    /// `simplify`, and returns the new result.
+    ///
+    /// Targets at or above the current index count leave the result as is; simplification
+    /// stops after the first pass that falls short of its target because of `target_error`,
+    /// and passes that don't reduce the index count aren't recorded.
+    pub fn simplify_to(&mut self, target_count: usize, target_error: f32) -> &[u32] {
+        while target_count < self.indices.len() {
+            let index_count = self.indices.len();
+            let pass_target = target_count.max(index_count / 6 * 3);
+            let mut error = 0f32;
+            simplify_in_place(
+                &mut self.indices,
+                self.vertices,
+                pass_target,
+                target_error,
+                self.options,
+                Some(&mut error),
+            );
+            if self.indices.len() < index_count {
+                self.steps.push(SimplifyStep {
+                    index_count: self.indices.len(),
+                    error: self.error().max(error),
+                });
+            }
+            // the simplifier stops short of the target when it would exceed `target_error`
+            if self.indices.len() > pass_target {
+                break;
+            }
+        }
+        &self.indices
+    }
+
+    /// Restores the source mesh, discarding all steps.
+    pub fn reset(&mut self) {
+        self.indices.clone_from(&self.source);
+        self.steps.clear();
+    }
+}
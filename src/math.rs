@@ -0,0 +1,40 @@
+//! `DecodePosition` implementations and `VertexDataAdapter` conversions for the vector types
+//! of common math crates, enabled by the `glam`, `mint` and `nalgebra` features.
+
+use crate::{DecodePosition, Error, VertexDataAdapter};
+
+macro_rules! impl_position_type {
+    ($ty:ty, |$v:ident| $decode:expr) => {
+        impl DecodePosition for $ty {
+            const POSITION_OFFSET: Option<usize> = Some(0);
+
+            fn decode_position(&self) -> [f32; 3] {
+                let $v = self;
+                $decode
+            }
+        }
+
+        impl<'a> TryFrom<&'a [$ty]> for VertexDataAdapter<'a> {
+            type Error = Error;
+
+            fn try_from(vertices: &'a [$ty]) -> Result<Self, Self::Error> {
+                VertexDataAdapter::from_slice(vertices, 0)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "glam")]
+impl_position_type!(glam::Vec3, |v| v.to_array());
+#[cfg(feature = "glam")]
+impl_position_type!(glam::Vec3A, |v| v.to_array());
+
+#[cfg(feature = "mint")]
+impl_position_type!(mint::Point3<f32>, |v| [v.x, v.y, v.z]);
+#[cfg(feature = "mint")]
+impl_position_type!(mint::Vector3<f32>, |v| [v.x, v.y, v.z]);
+
+#[cfg(feature = "nalgebra")]
+impl_position_type!(nalgebra::Point3<f32>, |v| [v.x, v.y, v.z]);
+#[cfg(feature = "nalgebra")]
+impl_position_type!(nalgebra::Vector3<f32>, |v| [v.x, v.y, v.z]);
@@ -0,0 +1,294 @@
+//! Meshlet layouts consumed directly by mesh shading pipelines.
+//!
+//! `Meshlets::pack_for_gpu` produces a generic layout; the layouts here match what the mesh
+//! shaders of the respective API typically read, storing micro-indices the way those shaders
+//! write their primitives. `Meshlets::pack_for_mesh_shader` selects the layout with
+//! `MeshShaderLayout`.
+
+use crate::{compute_meshlet_bounds, Meshlets, VertexDataAdapter};
+
+/// Meshlet descriptor with interleaved culling data, for `VK_EXT_mesh_shader` pipelines.
+///
+/// The layout matches the following std430 GLSL struct (32 bytes):
+///
+/// ```glsl
+/// struct Meshlet {
+///     vec4 sphere;          // center, radius
+///     uint cone;            // axis xyz and cutoff as 8-bit snorm
+///     uint vertex_offset;
+///     uint triangle_offset;
+///     uint counts;          // vertex_count | (triangle_count << 16)
+/// };
+/// ```
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct VkMeshlet {
+    /// Center and radius of the bounding sphere.
+    pub sphere: [f32; 4],
+    /// Axis and cutoff of the normal cone, quantized to 8-bit snorm.
+    pub cone: [i8; 4],
+    /// Offset of the first vertex in `VkMeshlets::vertices`.
+    pub vertex_offset: u32,
+    /// Offset of the first triangle in `VkMeshlets::triangles`.
+    pub triangle_offset: u32,
+    pub vertex_count: u16,
+    pub triangle_count: u16,
+}
+
+/// Meshlets packed for `VK_EXT_mesh_shader` pipelines, as produced by
+/// `Meshlets::pack_for_vk_mesh_shader`, to be uploaded as three storage buffers.
+///
+/// Every triangle is stored in its own word, with its micro-indices in the low three bytes,
+/// so a mesh shader can write `gl_PrimitiveTriangleIndicesEXT` directly:
+///
+/// ```glsl
+/// layout(local_size_x = 32) in;
+/// layout(triangles, max_vertices = 64, max_primitives = 124) out;
+///
+/// layout(binding = 0) readonly buffer Meshlets { Meshlet meshlets[]; };
+/// layout(binding = 1) readonly buffer MeshletVertices { uint meshlet_vertices[]; };
+/// layout(binding = 2) readonly buffer MeshletTriangles { uint meshlet_triangles[]; };
+///
+/// void main() {
+///     Meshlet meshlet = meshlets[gl_WorkGroupID.x];
+///     uint vertex_count = meshlet.counts & 0xffff;
+///     uint triangle_count = meshlet.counts >> 16;
+///     SetMeshOutputsEXT(vertex_count, triangle_count);
+///
+///     for (uint i = gl_LocalInvocationIndex; i < vertex_count; i += 32) {
+///         uint index = meshlet_vertices[meshlet.vertex_offset + i];
+///         gl_MeshVerticesEXT[i].gl_Position = transform(index);
+///     }
+///     for (uint i = gl_LocalInvocationIndex; i < triangle_count; i += 32) {
+///         uint triangle = meshlet_triangles[meshlet.triangle_offset + i];
+///         gl_PrimitiveTriangleIndicesEXT[i] =
+///             uvec3(triangle & 0xff, (triangle >> 8) & 0xff, (triangle >> 16) & 0xff);
+///     }
+/// }
+/// ```
+///
+/// The cone can be unpacked with `unpackSnorm4x8(meshlet.cone)` in a task shader, and
+/// tested like in `ClusterBounds::is_backface_culled_sphere_s8`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct VkMeshlets {
+    pub meshlets: Vec<VkMeshlet>,
+    pub vertices: Vec<u32>,
+    pub triangles: Vec<u32>,
+}
+
+impl Meshlets {
+    /// Packs the meshlets and their culling data for `VK_EXT_mesh_shader` pipelines, see
+    /// `VkMeshlets`.
+    ///
+    /// `vertices` must be the vertex buffer the meshlets were built from.
+    pub fn pack_for_vk_mesh_shader(&self, vertices: &VertexDataAdapter<'_>) -> VkMeshlets {
+        let mut descriptors: Vec<VkMeshlet> = Vec::with_capacity(self.len());
+        let mut meshlet_vertices: Vec<u32> = Vec::with_capacity(self.vertices.len());
+        let mut triangles: Vec<u32> = Vec::with_capacity(self.triangles.len() / 3);
+        for meshlet in self.iter() {
+            let bounds = compute_meshlet_bounds(meshlet, vertices);
+            descriptors.push(VkMeshlet {
+                sphere: [
+                    bounds.center[0],
+                    bounds.center[1],
+                    bounds.center[2],
+                    bounds.radius,
+                ],
+                cone: [
+                    bounds.cone_axis_s8[0],
+                    bounds.cone_axis_s8[1],
+                    bounds.cone_axis_s8[2],
+                    bounds.cone_cutoff_s8,
+                ],
+                vertex_offset: meshlet_vertices.len() as u32,
+                triangle_offset: triangles.len() as u32,
+                vertex_count: meshlet.vertices.len() as u16,
+                triangle_count: (meshlet.triangles.len() / 3) as u16,
+            });
+            meshlet_vertices.extend_from_slice(meshlet.vertices);
+            triangles.extend(
+                meshlet
+                    .triangles
+                    .chunks_exact(3)
+                    .map(|triangle| u32::from_le_bytes([triangle[0], triangle[1], triangle[2], 0])),
+            );
+        }
+        VkMeshlets {
+            meshlets: descriptors,
+            vertices: meshlet_vertices,
+            triangles,
+        }
+    }
+}
+
+/// Meshlet descriptor of the D3D12 mesh shader samples, matching the HLSL struct:
+///
+/// ```hlsl
+/// struct Meshlet {
+///     uint VertCount;
+///     uint VertOffset;
+///     uint PrimCount;
+///     uint PrimOffset;
+/// };
+/// ```
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct D3d12Meshlet {
+    pub vertex_count: u32,
+    /// Offset of the first vertex in `D3d12Meshlets::vertices`.
+    pub vertex_offset: u32,
+    pub primitive_count: u32,
+    /// Offset of the first primitive in `D3d12Meshlets::primitives`.
+    pub primitive_offset: u32,
+}
+
+/// Unique vertex indices of `D3d12Meshlets`, as 16-bit values if all of them fit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub enum D3d12VertexIndices {
+    /// 16-bit indices, padded to an even count so the buffer size is a multiple of 4 bytes
+    /// as required for `ByteAddressBuffer`.
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Default for D3d12VertexIndices {
+    fn default() -> Self {
+        Self::U16(Vec::new())
+    }
+}
+
+impl D3d12VertexIndices {
+    /// Size of an index in bytes, the `IndexSize` of the samples.
+    pub fn index_size(&self) -> usize {
+        match self {
+            Self::U16(_) => 2,
+            Self::U32(_) => 4,
+        }
+    }
+}
+
+/// Meshlets packed like in the D3D12 mesh shader samples, as produced by
+/// `Meshlets::pack_for_d3d12_mesh_shader`.
+///
+/// Every primitive is a word holding its three micro-indices as 10-bit values, which a
+/// mesh shader unpacks with:
+///
+/// ```hlsl
+/// uint3 UnpackPrimitive(uint primitive) {
+///     return uint3(primitive & 0x3FF, (primitive >> 10) & 0x3FF, (primitive >> 20) & 0x3FF);
+/// }
+///
+/// uint GetVertexIndex(Meshlet m, uint localIndex) {
+///     localIndex = m.VertOffset + localIndex;
+///     if (MeshInfo.IndexBytes == 4) {
+///         return UniqueVertexIndices.Load(localIndex * 4);
+///     } else {
+///         uint wordOffset = (localIndex & 0x1);
+///         uint byteOffset = (localIndex / 2) * 4;
+///         uint indexPair = UniqueVertexIndices.Load(byteOffset);
+///         return (indexPair >> (wordOffset * 16)) & 0xffff;
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct D3d12Meshlets {
+    pub meshlets: Vec<D3d12Meshlet>,
+    pub vertices: D3d12VertexIndices,
+    pub primitives: Vec<u32>,
+}
+
+/// Mesh shader layouts supported by `Meshlets::pack_for_mesh_shader`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MeshShaderLayout {
+    /// `VK_EXT_mesh_shader` layout with interleaved culling data, see `VkMeshlets`.
+    VkExt,
+    /// Layout of the D3D12 mesh shader samples, see `D3d12Meshlets`.
+    D3d12,
+}
+
+/// Meshlets packed in one of the `MeshShaderLayout` layouts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshShaderMeshlets {
+    VkExt(VkMeshlets),
+    D3d12(D3d12Meshlets),
+}
+
+impl Meshlets {
+    /// Packs the meshlets like in the D3D12 mesh shader samples, see `D3d12Meshlets`.
+    pub fn pack_for_d3d12_mesh_shader(&self) -> D3d12Meshlets {
+        let mut descriptors: Vec<D3d12Meshlet> = Vec::with_capacity(self.len());
+        let mut vertices: Vec<u32> = Vec::with_capacity(self.vertices.len());
+        let mut primitives: Vec<u32> = Vec::with_capacity(self.triangles.len() / 3);
+        for meshlet in self.iter() {
+            descriptors.push(D3d12Meshlet {
+                vertex_count: meshlet.vertices.len() as u32,
+                vertex_offset: vertices.len() as u32,
+                primitive_count: (meshlet.triangles.len() / 3) as u32,
+                primitive_offset: primitives.len() as u32,
+            });
+            vertices.extend_from_slice(meshlet.vertices);
+            primitives.extend(meshlet.triangles.chunks_exact(3).map(|triangle| {
+                u32::from(triangle[0]) | u32::from(triangle[1]) << 10 | u32::from(triangle[2]) << 20
+            }));
+        }
+
+        let vertices = if vertices.iter().all(|&index| u16::try_from(index).is_ok()) {
+            let mut vertices: Vec<u16> = vertices.iter().map(|&index| index as u16).collect();
+            if !vertices.len().is_multiple_of(2) {
+                vertices.push(0);
+            }
+            D3d12VertexIndices::U16(vertices)
+        } else {
+            D3d12VertexIndices::U32(vertices)
+        };
+        D3d12Meshlets {
+            meshlets: descriptors,
+            vertices,
+            primitives,
+        }
+    }
+
+    /// Packs the meshlets in the given mesh shader `layout`.
+    ///
+    /// `vertices` must be the vertex buffer the meshlets were built from; it's used to compute
+    /// the culling data of layouts that include it.
+    pub fn pack_for_mesh_shader(
+        &self,
+        layout: MeshShaderLayout,
+        vertices: &VertexDataAdapter<'_>,
+    ) -> MeshShaderMeshlets {
+        match layout {
+            MeshShaderLayout::VkExt => {
+                MeshShaderMeshlets::VkExt(self.pack_for_vk_mesh_shader(vertices))
+            }
+            MeshShaderLayout::D3d12 => MeshShaderMeshlets::D3d12(self.pack_for_d3d12_mesh_shader()),
+        }
+    }
+}
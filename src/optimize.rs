@@ -1,36 +1,48 @@
-use crate::{ffi, DecodePosition, VertexDataAdapter};
+use crate::{
+    ffi,
+    utilities::{check_indices, UninitBuffer},
+    DecodePosition, Index, PositionBuffer, VertexCacheModel, VertexDataAdapter,
+};
+use std::collections::HashMap;
 use std::mem;
+use std::ops::Range;
 
 /// Reorders indices to reduce the number of GPU vertex shader invocations.
 ///
+/// Accepts both `u16` and `u32` indices, as do the other optimizers.
+///
 /// If index buffer contains multiple ranges for multiple draw calls,
 /// this function needs to be called on each range individually.
-pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
-    let mut optimized: Vec<u32> = vec![0; indices.len()];
-    unsafe {
+pub fn optimize_vertex_cache<T: Index>(indices: &[T], vertex_count: usize) -> Vec<T> {
+    check_indices(indices, vertex_count);
+    let indices = T::to_u32_slice(indices);
+    let mut optimized = UninitBuffer::new(indices.len());
+    let optimized = unsafe {
         ffi::meshopt_optimizeVertexCache(
             optimized.as_mut_ptr(),
             indices.as_ptr(),
             indices.len(),
             vertex_count,
         );
-    }
-    optimized
+        optimized.assume_init(indices.len())
+    };
+    optimized.into_iter().map(T::from_u32).collect()
 }
 
 /// Reorders indices to reduce the number of GPU vertex shader invocations.
 ///
 /// If index buffer contains multiple ranges for multiple draw calls,
 /// this function needs to be called on each range individually.
-pub fn optimize_vertex_cache_in_place(indices: &mut [u32], vertex_count: usize) {
-    unsafe {
+pub fn optimize_vertex_cache_in_place<T: Index>(indices: &mut [T], vertex_count: usize) {
+    check_indices(indices, vertex_count);
+    T::with_u32_slice_mut(indices, |indices| unsafe {
         ffi::meshopt_optimizeVertexCache(
             indices.as_mut_ptr(),
             indices.as_ptr(),
             indices.len(),
             vertex_count,
         );
-    }
+    });
 }
 
 /// Vertex transform cache optimizer for FIFO caches.
@@ -41,22 +53,15 @@ pub fn optimize_vertex_cache_in_place(indices: &mut [u32], vertex_count: usize)
 /// results compared to `optimize_vertex_cache`.
 ///
 /// If index buffer contains multiple ranges for multiple draw calls,
-/// this function needs to be called on each range individually.
-pub fn optimize_vertex_cache_fifo(
-    indices: &[u32],
+/// this function needs to be called on each range individually, or use
+/// `optimize_vertex_cache_fifo_ranges`.
+pub fn optimize_vertex_cache_fifo<T: Index>(
+    indices: &[T],
     vertex_count: usize,
     cache_size: u32,
-) -> Vec<u32> {
-    let mut optimized: Vec<u32> = vec![0; indices.len()];
-    unsafe {
-        ffi::meshopt_optimizeVertexCacheFifo(
-            optimized.as_mut_ptr(),
-            indices.as_ptr(),
-            indices.len(),
-            vertex_count,
-            cache_size,
-        );
-    }
+) -> Vec<T> {
+    let mut optimized = indices.to_vec();
+    optimize_vertex_cache_fifo_in_place(&mut optimized, vertex_count, cache_size);
     optimized
 }
 
@@ -68,13 +73,15 @@ pub fn optimize_vertex_cache_fifo(
 /// results compared to `optimize_vertex_cache_fifo_in_place`.
 ///
 /// If index buffer contains multiple ranges for multiple draw calls,
-/// this function needs to be called on each range individually.
-pub fn optimize_vertex_cache_fifo_in_place(
-    indices: &mut [u32],
+/// this function needs to be called on each range individually, or use
+/// `optimize_vertex_cache_fifo_ranges`.
+pub fn optimize_vertex_cache_fifo_in_place<T: Index>(
+    indices: &mut [T],
     vertex_count: usize,
     cache_size: u32,
 ) {
-    unsafe {
+    check_indices(indices, vertex_count);
+    T::with_u32_slice_mut(indices, |indices| unsafe {
         ffi::meshopt_optimizeVertexCacheFifo(
             indices.as_mut_ptr(),
             indices.as_ptr(),
@@ -82,6 +89,146 @@ pub fn optimize_vertex_cache_fifo_in_place(
             vertex_count,
             cache_size,
         );
+    });
+}
+
+/// Vertex transform cache optimizer for FIFO caches, optimizing each of the draw call
+/// `ranges` of the index buffer separately.
+///
+/// Same as calling `optimize_vertex_cache_fifo` for each range, but much faster for many
+/// small ranges: the vertices of each range are renumbered compactly using temporary state
+/// shared by all ranges, so the cost of each range depends on its size rather than on
+/// `vertex_count`. Since the vertices are renumbered, the resulting order may differ
+/// slightly from per-range calls. Indices outside of the ranges are left as is.
+pub fn optimize_vertex_cache_fifo_ranges<T: Index>(
+    indices: &[T],
+    ranges: &[Range<usize>],
+    vertex_count: usize,
+    cache_size: u32,
+) -> Vec<T> {
+    let mut optimized = indices.to_vec();
+    optimize_vertex_cache_fifo_ranges_in_place(&mut optimized, ranges, vertex_count, cache_size);
+    optimized
+}
+
+/// Vertex transform cache optimizer for FIFO caches (in place), optimizing each of the draw
+/// call `ranges` of the index buffer separately, see `optimize_vertex_cache_fifo_ranges`.
+///
+/// Panics if a range is out of bounds or doesn't consist of whole triangles.
+pub fn optimize_vertex_cache_fifo_ranges_in_place<T: Index>(
+    indices: &mut [T],
+    ranges: &[Range<usize>],
+    vertex_count: usize,
+    cache_size: u32,
+) {
+    check_indices(indices, vertex_count);
+    for range in ranges {
+        assert!(
+            range.start <= range.end && range.end <= indices.len() && range.len() % 3 == 0,
+            "range {:?} must consist of whole triangles within the {} indices",
+            range,
+            indices.len()
+        );
+    }
+
+    // maps vertices to their number within the current range, or u32::MAX if unreferenced
+    let mut local_vertices: Vec<u32> = vec![u32::MAX; vertex_count];
+    let mut range_vertices: Vec<u32> = Vec::new();
+    let mut local_indices: Vec<u32> = Vec::new();
+    for range in ranges {
+        let range_indices = &mut indices[range.clone()];
+        range_vertices.clear();
+        local_indices.clear();
+        for &index in range_indices.iter() {
+            let local = &mut local_vertices[index.to_u32() as usize];
+            if *local == u32::MAX {
+                *local = range_vertices.len() as u32;
+                range_vertices.push(index.to_u32());
+            }
+            local_indices.push(*local);
+        }
+
+        unsafe {
+            ffi::meshopt_optimizeVertexCacheFifo(
+                local_indices.as_mut_ptr(),
+                local_indices.as_ptr(),
+                local_indices.len(),
+                range_vertices.len(),
+                cache_size,
+            );
+        }
+
+        for (index, &local) in range_indices.iter_mut().zip(&local_indices) {
+            *index = T::from_u32(range_vertices[local as usize]);
+        }
+        for &vertex in &range_vertices {
+            local_vertices[vertex as usize] = u32::MAX;
+        }
+    }
+}
+
+/// Vertex transform cache optimizer for strip-like caches.
+///
+/// Produces inferior results to `optimize_vertex_cache` from the GPU vertex cache perspective.
+/// However, the resulting index order is more optimal if the goal is to reduce the triangle
+/// strip length or improve compression efficiency.
+pub fn optimize_vertex_cache_strip<T: Index>(indices: &[T], vertex_count: usize) -> Vec<T> {
+    let mut optimized = indices.to_vec();
+    optimize_vertex_cache_strip_in_place(&mut optimized, vertex_count);
+    optimized
+}
+
+/// Vertex transform cache optimizer for strip-like caches (in place).
+///
+/// Produces inferior results to `optimize_vertex_cache` from the GPU vertex cache perspective.
+/// However, the resulting index order is more optimal if the goal is to reduce the triangle
+/// strip length or improve compression efficiency.
+pub fn optimize_vertex_cache_strip_in_place<T: Index>(indices: &mut [T], vertex_count: usize) {
+    check_indices(indices, vertex_count);
+    T::with_u32_slice_mut(indices, |indices| unsafe {
+        ffi::meshopt_optimizeVertexCacheStrip(
+            indices.as_mut_ptr(),
+            indices.as_ptr(),
+            indices.len(),
+            vertex_count,
+        );
+    });
+}
+
+/// Reorders indices to reduce the number of GPU vertex shader invocations on the hardware
+/// described by `model`, selecting the optimizer that fits it best:
+///
+/// - models with warps or primitive groups (`Nvidia`, `Amd`) use `optimize_vertex_cache`
+/// - plain FIFO models (`Intel`, `VertexCacheModel::fifo`) use `optimize_vertex_cache_fifo`
+///   with the cache size of the model
+/// - models without a cache (a cache size of 0) use `optimize_vertex_cache_strip`, as only
+///   strip length and compression efficiency can be improved
+///
+/// If index buffer contains multiple ranges for multiple draw calls,
+/// this function needs to be called on each range individually.
+pub fn optimize_vertex_cache_for<T: Index>(
+    indices: &[T],
+    vertex_count: usize,
+    model: VertexCacheModel,
+) -> Vec<T> {
+    let mut optimized = indices.to_vec();
+    optimize_vertex_cache_for_in_place(&mut optimized, vertex_count, model);
+    optimized
+}
+
+/// Reorders indices in place to reduce the number of GPU vertex shader invocations on the
+/// hardware described by `model`, see `optimize_vertex_cache_for`.
+pub fn optimize_vertex_cache_for_in_place<T: Index>(
+    indices: &mut [T],
+    vertex_count: usize,
+    model: VertexCacheModel,
+) {
+    match model.parameters() {
+        (0, _, _) => optimize_vertex_cache_strip_in_place(indices, vertex_count),
+        (cache_size, 0, 0) => {
+            optimize_vertex_cache_fifo_in_place(indices, vertex_count, cache_size);
+        }
+        _ => optimize_vertex_cache_in_place(indices, vertex_count),
     }
 }
 
@@ -92,9 +239,13 @@ pub fn optimize_vertex_cache_fifo_in_place(
 /// use `optimize_vertex_fetch_remap` + `remap_vertex_buffer` for each stream.
 ///
 /// `indices` is used both as an input and as an output index buffer.
-pub fn optimize_vertex_fetch<T: Clone + Default>(indices: &mut [u32], vertices: &[T]) -> Vec<T> {
-    let mut result: Vec<T> = vec![T::default(); vertices.len()];
-    let next_vertex = unsafe {
+pub fn optimize_vertex_fetch<I: Index, T: Clone + Default>(
+    indices: &mut [I],
+    vertices: &[T],
+) -> Vec<T> {
+    check_indices(indices, vertices.len());
+    let mut result = UninitBuffer::<T>::new(vertices.len());
+    let next_vertex = I::with_u32_slice_mut(indices, |indices| unsafe {
         ffi::meshopt_optimizeVertexFetch(
             result.as_mut_ptr().cast(),
             indices.as_mut_ptr(),
@@ -103,9 +254,8 @@ pub fn optimize_vertex_fetch<T: Clone + Default>(indices: &mut [u32], vertices:
             vertices.len(),
             mem::size_of::<T>(),
         )
-    };
-    result.resize(next_vertex, T::default());
-    result
+    });
+    unsafe { result.assume_init(next_vertex) }
 }
 
 /// Vertex fetch cache optimizer (modifies in place)
@@ -116,8 +266,9 @@ pub fn optimize_vertex_fetch<T: Clone + Default>(indices: &mut [u32], vertices:
 /// use `optimize_vertex_fetch_remap` + `remap_vertex_buffer` for each stream.
 ///
 /// `indices` and `vertices` are used both as an input and as an output buffer.
-pub fn optimize_vertex_fetch_in_place<T>(indices: &mut [u32], vertices: &mut [T]) -> usize {
-    unsafe {
+pub fn optimize_vertex_fetch_in_place<I: Index, T>(indices: &mut [I], vertices: &mut [T]) -> usize {
+    check_indices(indices, vertices.len());
+    I::with_u32_slice_mut(indices, |indices| unsafe {
         ffi::meshopt_optimizeVertexFetch(
             vertices.as_mut_ptr().cast(),
             indices.as_mut_ptr(),
@@ -126,7 +277,7 @@ pub fn optimize_vertex_fetch_in_place<T>(indices: &mut [u32], vertices: &mut [T]
             vertices.len(),
             mem::size_of::<T>(),
         )
-    }
+    })
 }
 
 /// Generates vertex remap to reduce the amount of GPU memory fetches during
@@ -134,8 +285,10 @@ pub fn optimize_vertex_fetch_in_place<T>(indices: &mut [u32], vertices: &mut [T]
 ///
 /// The resulting remap table should be used to reorder vertex/index buffers
 /// using `optimize_remap_vertex_buffer`/`optimize_remap_index_buffer`.
-pub fn optimize_vertex_fetch_remap(indices: &[u32], vertex_count: usize) -> Vec<u32> {
-    let mut result: Vec<u32> = vec![0; vertex_count];
+pub fn optimize_vertex_fetch_remap<T: Index>(indices: &[T], vertex_count: usize) -> Vec<u32> {
+    check_indices(indices, vertex_count);
+    let indices = T::to_u32_slice(indices);
+    let mut result = UninitBuffer::new(vertex_count);
     let next_vertex = unsafe {
         ffi::meshopt_optimizeVertexFetchRemap(
             result.as_mut_ptr(),
@@ -144,8 +297,7 @@ pub fn optimize_vertex_fetch_remap(indices: &[u32], vertex_count: usize) -> Vec<
             vertex_count,
         )
     };
-    result.resize(next_vertex, 0u32);
-    result
+    unsafe { result.assume_init(next_vertex) }
 }
 
 /// Reorders indices to reduce the number of GPU vertex shader invocations
@@ -156,15 +308,16 @@ pub fn optimize_vertex_fetch_remap(indices: &[u32], vertex_count: usize) -> Vec<
 ///
 /// `threshold` indicates how much the overdraw optimizer can degrade vertex cache
 /// efficiency (1.05 = up to 5%) to reduce overdraw more efficiently.
-pub fn optimize_overdraw_in_place(
-    indices: &mut [u32],
+pub fn optimize_overdraw_in_place<T: Index>(
+    indices: &mut [T],
     vertices: &VertexDataAdapter<'_>,
     threshold: f32,
 ) {
+    check_indices(indices, vertices.vertex_count);
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    unsafe {
+    T::with_u32_slice_mut(indices, |indices| unsafe {
         ffi::meshopt_optimizeOverdraw(
             indices.as_mut_ptr(),
             indices.as_ptr(),
@@ -174,7 +327,7 @@ pub fn optimize_overdraw_in_place(
             vertices.vertex_stride,
             threshold,
         );
-    }
+    });
 }
 
 /// Reorders indices to reduce the number of GPU vertex shader invocations
@@ -185,24 +338,156 @@ pub fn optimize_overdraw_in_place(
 ///
 /// `threshold` indicates how much the overdraw optimizer can degrade vertex cache
 /// efficiency (1.05 = up to 5%) to reduce overdraw more efficiently.
-pub fn optimize_overdraw_in_place_decoder<T: DecodePosition>(
-    indices: &mut [u32],
+pub fn optimize_overdraw_in_place_decoder<I: Index, T: DecodePosition>(
+    indices: &mut [I],
     vertices: &[T],
     threshold: f32,
 ) {
-    let positions = vertices
+    vertices.with_positions(|positions| optimize_overdraw_in_place(indices, positions, threshold));
+}
+
+/// Reorders indices to reduce the number of GPU vertex shader invocations
+/// and the pixel overdraw, returning the reordered indices.
+///
+/// `indices` must contain index data that is the result of `optimize_vertex_cache`
+/// (*not* the original mesh indices!)
+///
+/// `threshold` indicates how much the overdraw optimizer can degrade vertex cache
+/// efficiency (1.05 = up to 5%) to reduce overdraw more efficiently.
+pub fn optimize_overdraw<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    threshold: f32,
+) -> Vec<T> {
+    let mut optimized = indices.to_vec();
+    optimize_overdraw_in_place(&mut optimized, vertices, threshold);
+    optimized
+}
+
+/// Reorders indices to reduce the number of GPU vertex shader invocations
+/// and the pixel overdraw, returning the reordered indices.
+///
+/// `indices` must contain index data that is the result of `optimize_vertex_cache`
+/// (*not* the original mesh indices!)
+///
+/// `threshold` indicates how much the overdraw optimizer can degrade vertex cache
+/// efficiency (1.05 = up to 5%) to reduce overdraw more efficiently.
+pub fn optimize_overdraw_decoder<I: Index, T: DecodePosition>(
+    indices: &[I],
+    vertices: &[T],
+    threshold: f32,
+) -> Vec<I> {
+    let mut optimized = indices.to_vec();
+    optimize_overdraw_in_place_decoder(&mut optimized, vertices, threshold);
+    optimized
+}
+
+/// A draw call within a shared index buffer, see `optimize_submesh_order`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submesh {
+    /// Range of the submesh in the index buffer.
+    pub range: Range<usize>,
+    /// Center of the bounds of the submesh.
+    pub center: [f32; 3],
+    /// Render state of the submesh, e.g. a material or pipeline id; submeshes with the same
+    /// state are drawn consecutively.
+    pub state: u64,
+}
+
+/// Returns an order to draw `submeshes` in that groups submeshes with the same render state,
+/// and orders submeshes spatially within each group so that consecutive draws are close to
+/// each other, which improves vertex locality.
+///
+/// Groups are ordered by where their first submesh appears in the spatial order. Use
+/// `reorder_submeshes` to rewrite the index buffer in this order.
+pub fn optimize_submesh_order(submeshes: &[Submesh]) -> Vec<usize> {
+    let centers = submeshes
         .iter()
-        .map(|vertex| vertex.decode_position())
+        .map(|submesh| submesh.center)
         .collect::<Vec<[f32; 3]>>();
+    let mut rank: Vec<u32> = vec![0; centers.len()];
     unsafe {
-        ffi::meshopt_optimizeOverdraw(
-            indices.as_mut_ptr(),
-            indices.as_ptr(),
-            indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
+        ffi::meshopt_spatialSortRemap(
+            rank.as_mut_ptr(),
+            centers.as_ptr().cast(),
+            centers.len(),
             mem::size_of::<f32>() * 3,
-            threshold,
         );
     }
+
+    let mut order = (0..submeshes.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&submesh| rank[submesh]);
+
+    let mut groups: HashMap<u64, usize> = HashMap::new();
+    for &submesh in &order {
+        let group_count = groups.len();
+        groups
+            .entry(submeshes[submesh].state)
+            .or_insert(group_count);
+    }
+    // stable, so the spatial order is kept within each group
+    order.sort_by_key(|&submesh| groups[&submeshes[submesh].state]);
+    order
+}
+
+/// Rewrites `indices` so that the submeshes are stored consecutively in `order` (as
+/// returned by `optimize_submesh_order`), returning the new index buffer and submeshes.
+///
+/// Indices that aren't part of any submesh are dropped. Run `optimize_vertex_fetch` on the
+/// result to reorder the vertices to match the new draw order.
+pub fn reorder_submeshes<T: Index>(
+    indices: &[T],
+    submeshes: &[Submesh],
+    order: &[usize],
+) -> (Vec<T>, Vec<Submesh>) {
+    let mut reordered: Vec<T> = Vec::with_capacity(indices.len());
+    let mut reordered_submeshes: Vec<Submesh> = Vec::with_capacity(order.len());
+    for &submesh in order {
+        let submesh = &submeshes[submesh];
+        let start = reordered.len();
+        reordered.extend_from_slice(&indices[submesh.range.clone()]);
+        reordered_submeshes.push(Submesh {
+            range: start..reordered.len(),
+            ..submesh.clone()
+        });
+    }
+    (reordered, reordered_submeshes)
+}
+
+/// Configuration for `optimize_complete`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OptimizeConfig {
+    /// How much the overdraw optimizer can degrade vertex cache efficiency
+    /// (1.05 = up to 5%) to reduce overdraw more efficiently, see `optimize_overdraw_in_place`.
+    pub overdraw_threshold: f32,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        Self {
+            overdraw_threshold: 1.05,
+        }
+    }
+}
+
+/// Optimizes a mesh for vertex cache, overdraw and vertex fetch, in that order.
+///
+/// `indices` and `vertices` are used both as an input and as an output buffer; returns the
+/// number of vertices that are still referenced, which `vertices` can be truncated to.
+///
+/// If index buffer contains multiple ranges for multiple draw calls,
+/// optimize each range for vertex cache and overdraw individually instead.
+pub fn optimize_complete<I: Index, T: DecodePosition>(
+    indices: &mut [I],
+    vertices: &mut [T],
+    config: &OptimizeConfig,
+) -> usize {
+    // vertex cache optimization should go first as it provides starting order for overdraw
+    optimize_vertex_cache_in_place(indices, vertices.len());
+
+    // reorder indices for overdraw, balancing overdraw and vertex cache efficiency
+    optimize_overdraw_in_place_decoder(indices, vertices, config.overdraw_threshold);
+
+    // vertex fetch optimization should go last as it depends on the final index order
+    optimize_vertex_fetch_in_place(indices, vertices)
 }
@@ -0,0 +1,457 @@
+use crate::{
+    utilities::check_indices, DecodePosition, Index, OverdrawStatistics, VertexDataAdapter,
+};
+
+/// Configuration for `analyze_overdraw_views`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverdrawConfig {
+    /// Width and height of the square viewport in pixels.
+    pub resolution: u32,
+    /// Directions the orthographic camera looks in, e.g. `[0.0, -1.0, 0.0]` for a top-down
+    /// camera. Directions don't need to be normalized.
+    pub views: Vec<[f32; 3]>,
+}
+
+impl OverdrawConfig {
+    /// Creates a configuration rendering the mesh from `views` at `resolution`.
+    pub fn new(resolution: u32, views: Vec<[f32; 3]>) -> Self {
+        Self { resolution, views }
+    }
+
+    /// Renders the mesh along both directions of each axis, like `analyze_overdraw` does.
+    pub fn axes(resolution: u32) -> Self {
+        Self::new(
+            resolution,
+            vec![
+                [1.0, 0.0, 0.0],
+                [-1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, -1.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, -1.0],
+            ],
+        )
+    }
+
+    /// Renders the mesh from directions spread over a cube around it, with
+    /// `subdivisions * subdivisions` directions through each face of the cube.
+    pub fn direction_cube(resolution: u32, subdivisions: u32) -> Self {
+        let mut views = Vec::with_capacity(6 * (subdivisions * subdivisions) as usize);
+        for axis in 0..3 {
+            for sign in [1f32, -1f32] {
+                for i in 0..subdivisions {
+                    for j in 0..subdivisions {
+                        // directions through the centers of the cells of the face
+                        let s = (i as f32 + 0.5) / subdivisions as f32 * 2.0 - 1.0;
+                        let t = (j as f32 + 0.5) / subdivisions as f32 * 2.0 - 1.0;
+                        let mut view = [0f32; 3];
+                        view[axis] = sign;
+                        view[(axis + 1) % 3] = s;
+                        view[(axis + 2) % 3] = t;
+                        views.push(normalize(view));
+                    }
+                }
+            }
+        }
+        Self::new(resolution, views)
+    }
+}
+
+impl Default for OverdrawConfig {
+    fn default() -> Self {
+        Self::axes(256)
+    }
+}
+
+/// Overdraw of a single view, see `analyze_overdraw_views`.
+#[derive(Debug, Copy, Clone)]
+pub struct ViewOverdraw {
+    /// Normalized direction the camera looks in.
+    pub direction: [f32; 3],
+    pub statistics: OverdrawStatistics,
+}
+
+/// Returns overdraw statistics for each view of `config`, in the same order, using a software
+/// rasterizer with an orthographic camera framing the whole mesh.
+///
+/// Triangles are rendered in index buffer order with back-face culling (counter-clockwise
+/// triangles are front-facing) and a depth test, so the statistics reflect the benefit of
+/// `optimize_overdraw` for the given views. Results may not match actual GPU performance.
+pub fn analyze_overdraw_views<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    config: &OverdrawConfig,
+) -> Vec<ViewOverdraw> {
+    let positions = vertices.iter_positions().collect::<Vec<[f32; 3]>>();
+    overdraw_views(&T::to_u32_slice(indices), &positions, config)
+}
+
+/// Returns overdraw statistics for each view of `config`, see `analyze_overdraw_views`.
+pub fn analyze_overdraw_views_decoder<I: Index, T: DecodePosition>(
+    indices: &[I],
+    vertices: &[T],
+    config: &OverdrawConfig,
+) -> Vec<ViewOverdraw> {
+    let positions = vertices
+        .iter()
+        .map(|vertex| vertex.decode_position())
+        .collect::<Vec<[f32; 3]>>();
+    overdraw_views(&I::to_u32_slice(indices), &positions, config)
+}
+
+fn overdraw_views(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    config: &OverdrawConfig,
+) -> Vec<ViewOverdraw> {
+    check_indices(indices, positions.len());
+    let frame = Frame::new(indices, positions);
+    config
+        .views
+        .iter()
+        .map(|&view| {
+            let direction = normalize(view);
+            let mut pixels_shaded = 0u32;
+            let depth = rasterize_view(
+                indices,
+                positions,
+                &frame,
+                direction,
+                config.resolution,
+                |_, _| pixels_shaded += 1,
+            );
+            let pixels_covered = depth.iter().filter(|depth| depth.is_finite()).count() as u32;
+            ViewOverdraw {
+                direction,
+                statistics: OverdrawStatistics {
+                    pixels_covered,
+                    pixels_shaded,
+                    overdraw: if pixels_covered == 0 {
+                        0f32
+                    } else {
+                        pixels_shaded as f32 / pixels_covered as f32
+                    },
+                },
+            }
+        })
+        .collect()
+}
+
+/// Per-pixel and per-triangle overdraw of a single view, see `analyze_overdraw_heatmap`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OverdrawHeatmap {
+    /// Normalized direction the camera looks in.
+    pub direction: [f32; 3],
+    /// Width and height of `pixels`.
+    pub resolution: u32,
+    /// Number of times each pixel was shaded, in rows of `resolution` pixels; 0 for pixels the
+    /// mesh doesn't cover, values above 1 are overdraw.
+    pub pixels: Vec<u32>,
+    /// For each triangle, the average number of times the pixels it shaded were shaded in
+    /// total, or 0 if the triangle shaded no pixels in this view.
+    pub triangles: Vec<f32>,
+}
+
+/// Returns per-pixel and per-triangle overdraw for each view of `config`, in the same order,
+/// so that overdraw hotspots can be visualized in the image or on the mesh.
+///
+/// Rendering matches `analyze_overdraw_views`.
+pub fn analyze_overdraw_heatmap<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    config: &OverdrawConfig,
+) -> Vec<OverdrawHeatmap> {
+    let positions = vertices.iter_positions().collect::<Vec<[f32; 3]>>();
+    overdraw_heatmaps(&T::to_u32_slice(indices), &positions, config)
+}
+
+/// Returns per-pixel and per-triangle overdraw for each view of `config`, see
+/// `analyze_overdraw_heatmap`.
+pub fn analyze_overdraw_heatmap_decoder<I: Index, T: DecodePosition>(
+    indices: &[I],
+    vertices: &[T],
+    config: &OverdrawConfig,
+) -> Vec<OverdrawHeatmap> {
+    let positions = vertices
+        .iter()
+        .map(|vertex| vertex.decode_position())
+        .collect::<Vec<[f32; 3]>>();
+    overdraw_heatmaps(&I::to_u32_slice(indices), &positions, config)
+}
+
+fn overdraw_heatmaps(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    config: &OverdrawConfig,
+) -> Vec<OverdrawHeatmap> {
+    check_indices(indices, positions.len());
+    let frame = Frame::new(indices, positions);
+    let size = config.resolution as usize;
+    config
+        .views
+        .iter()
+        .map(|&view| {
+            let direction = normalize(view);
+            let mut pixels = vec![0u32; size * size];
+            let mut fragments: Vec<(usize, usize)> = Vec::new();
+            rasterize_view(
+                indices,
+                positions,
+                &frame,
+                direction,
+                config.resolution,
+                |triangle, pixel| {
+                    pixels[pixel] += 1;
+                    fragments.push((triangle, pixel));
+                },
+            );
+
+            // average the final shading counts over the fragments of each triangle
+            let triangle_count = indices.len() / 3;
+            let mut totals = vec![0u64; triangle_count];
+            let mut counts = vec![0u32; triangle_count];
+            for (triangle, pixel) in fragments {
+                totals[triangle] += u64::from(pixels[pixel]);
+                counts[triangle] += 1;
+            }
+            let triangles = totals
+                .iter()
+                .zip(&counts)
+                .map(|(&total, &count)| {
+                    if count == 0 {
+                        0f32
+                    } else {
+                        total as f32 / count as f32
+                    }
+                })
+                .collect();
+
+            OverdrawHeatmap {
+                direction,
+                resolution: config.resolution,
+                pixels,
+                triangles,
+            }
+        })
+        .collect()
+}
+
+/// Bounding sphere of the referenced vertices, used to frame the mesh identically in all views.
+pub(crate) struct Frame {
+    center: [f32; 3],
+    radius: f32,
+}
+
+impl Frame {
+    pub(crate) fn new(indices: &[u32], positions: &[[f32; 3]]) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [-f32::MAX; 3];
+        for &index in indices {
+            let p = positions[index as usize];
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
+            }
+        }
+        if indices.is_empty() {
+            return Self {
+                center: [0f32; 3],
+                radius: 1f32,
+            };
+        }
+
+        let center = [0, 1, 2].map(|k| (min[k] + max[k]) * 0.5);
+        let extent = [0, 1, 2].map(|k| (max[k] - min[k]) * 0.5);
+        let radius = dot(extent, extent).sqrt();
+        Self {
+            center,
+            radius: if radius > 0f32 { radius } else { 1f32 },
+        }
+    }
+}
+
+/// Renders the triangles of `indices` looking along `direction`, calling `shade` with the
+/// triangle and pixel index of every fragment that passes the depth test.
+///
+/// Returns the resulting depth buffer; pixels that weren't covered are infinite.
+pub(crate) fn rasterize_view(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    frame: &Frame,
+    direction: [f32; 3],
+    resolution: u32,
+    mut shade: impl FnMut(usize, usize),
+) -> Vec<f32> {
+    let size = resolution as usize;
+    let mut depth = vec![f32::INFINITY; size * size];
+    if dot(direction, direction) == 0f32 {
+        return depth;
+    }
+
+    // orthonormal basis with cross(u, v) == direction
+    let up = if direction[1].abs() < 0.99 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let u = normalize(cross(up, direction));
+    let v = cross(direction, u);
+
+    let scale = resolution as f32 * 0.5;
+    let project = |index: u32| {
+        let p = positions[index as usize];
+        let q = [0, 1, 2].map(|k| (p[k] - frame.center[k]) / frame.radius);
+        [
+            (dot(q, u) + 1.0) * scale,
+            (dot(q, v) + 1.0) * scale,
+            dot(q, direction),
+        ]
+    };
+
+    for (triangle, t) in indices.chunks_exact(3).enumerate() {
+        let a = project(t[0]);
+        let mut b = project(t[1]);
+        let mut c = project(t[2]);
+
+        // with cross(u, v) == direction, front-facing triangles have a negative screen area
+        let area = edge(a, b, c);
+        if area >= 0f32 {
+            continue;
+        }
+        std::mem::swap(&mut b, &mut c);
+        let area = -area;
+
+        let x0 = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let y0 = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let x1 = (a[0].max(b[0]).max(c[0]).ceil() as usize).min(size);
+        let y1 = (a[1].max(b[1]).max(c[1]).ceil() as usize).min(size);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+                let wa = edge(b, c, p);
+                let wb = edge(c, a, p);
+                let wc = edge(a, b, p);
+                if !(covers(wa, b, c) && covers(wb, c, a) && covers(wc, a, b)) {
+                    continue;
+                }
+
+                let z = (wa * a[2] + wb * b[2] + wc * c[2]) / area;
+                let pixel = y * size + x;
+                if z < depth[pixel] {
+                    depth[pixel] = z;
+                    shade(triangle, pixel);
+                }
+            }
+        }
+    }
+    depth
+}
+
+fn edge(a: [f32; 3], b: [f32; 3], p: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Tie-breaking rule so that pixels exactly on an edge shared by two triangles are only
+/// rendered by one of them.
+fn covers(w: f32, a: [f32; 3], b: [f32; 3]) -> bool {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    w > 0f32 || (w == 0f32 && (dy > 0f32 || (dy == 0f32 && dx < 0f32)))
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    if length > 0f32 {
+        v.map(|x| x / length)
+    } else {
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analyze_overdraw, analyze_overdraw_heatmap, analyze_overdraw_views, OverdrawConfig,
+        VertexDataAdapter,
+    };
+
+    /// Two unit squares facing +z at z = 0 and z = 1, the farther one drawn first.
+    fn stacked_squares() -> (Vec<[f32; 3]>, Vec<u32>) {
+        let mut positions = Vec::new();
+        for z in [0f32, 1f32] {
+            positions.extend_from_slice(&[
+                [0f32, 0f32, z],
+                [1f32, 0f32, z],
+                [1f32, 1f32, z],
+                [0f32, 1f32, z],
+            ]);
+        }
+        (positions, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7])
+    }
+
+    #[test]
+    fn test_overdraw_views() {
+        let (positions, indices) = stacked_squares();
+        let vertices = VertexDataAdapter::from_slice(&positions, 0).unwrap();
+        let views = analyze_overdraw_views(&indices, &vertices, &OverdrawConfig::axes(64));
+        assert_eq!(views.len(), 6);
+
+        // only the camera looking down -z sees the front faces, and the nearer square is
+        // drawn last, so every covered pixel is shaded twice
+        let (mut covered, mut shaded) = (0, 0);
+        for view in &views {
+            let statistics = view.statistics;
+            if view.direction == [0f32, 0f32, -1f32] {
+                assert!(statistics.pixels_covered > 0);
+                assert_eq!(statistics.pixels_shaded, statistics.pixels_covered * 2);
+            } else {
+                assert_eq!(statistics.pixels_shaded, 0);
+            }
+            covered += statistics.pixels_covered;
+            shaded += statistics.pixels_shaded;
+        }
+
+        let native = analyze_overdraw(&indices, &vertices);
+        assert_eq!(shaded as f32 / covered as f32, native.overdraw);
+    }
+
+    #[test]
+    fn test_overdraw_heatmap() {
+        let (positions, indices) = stacked_squares();
+        let vertices = VertexDataAdapter::from_slice(&positions, 0).unwrap();
+        let config = OverdrawConfig::new(64, vec![[0f32, 0f32, -1f32], [0f32, 0f32, 1f32]]);
+        let heatmaps = analyze_overdraw_heatmap(&indices, &vertices, &config);
+        let views = analyze_overdraw_views(&indices, &vertices, &config);
+
+        let front = &heatmaps[0];
+        assert_eq!(front.pixels.len(), 64 * 64);
+        assert!(front.pixels.iter().all(|&count| count == 0 || count == 2));
+        assert_eq!(
+            front.pixels.iter().sum::<u32>(),
+            views[0].statistics.pixels_shaded
+        );
+        assert_eq!(front.triangles, [2f32; 4]);
+
+        // back faces are culled
+        assert!(heatmaps[1].pixels.iter().all(|&count| count == 0));
+        assert_eq!(heatmaps[1].triangles, [0f32; 4]);
+
+        let native = analyze_overdraw(&indices, &vertices);
+        let covered = front.pixels.iter().filter(|&&count| count > 0).count();
+        let shaded = front.pixels.iter().sum::<u32>();
+        assert_eq!(shaded as f32 / covered as f32, native.overdraw);
+    }
+}
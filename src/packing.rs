@@ -1,21 +1,70 @@
-use crate::{quantize_half, quantize_snorm};
+use crate::{
+    dequantize_half, dequantize_snorm, dequantize_unorm, quantize_half, quantize_snorm,
+    quantize_unorm, AttributeFormat, AttributeSemantic, HasVertexLayout, QuantizationGrid,
+    VertexDataAdapter, VertexLayout,
+};
 use float_cmp::ApproxEqUlps;
+use std::iter::Map;
+use std::mem;
 
 pub trait DecodePosition {
+    /// Byte offset of the position within `Self`, if it is stored as three consecutive
+    /// `f32` values; positions of such types are read in place by the `_decoder` functions
+    /// instead of being decoded into a temporary buffer.
+    const POSITION_OFFSET: Option<usize> = None;
+
     fn decode_position(&self) -> [f32; 3];
 }
 
 impl DecodePosition for [f32; 3] {
+    const POSITION_OFFSET: Option<usize> = Some(0);
+
     fn decode_position(&self) -> [f32; 3] {
         *self
     }
 }
 
-pub trait FromVertex {
-    fn fill_from_vertex(&mut self, vertex: &Vertex);
+/// A buffer of vertex positions that can be passed to the native functions, either in place
+/// or after decoding.
+pub trait PositionBuffer {
+    /// Calls `f` with an adapter over the positions of the buffer.
+    fn with_positions<R>(&self, f: impl FnOnce(&VertexDataAdapter<'_>) -> R) -> R;
+}
+
+impl PositionBuffer for VertexDataAdapter<'_> {
+    fn with_positions<R>(&self, f: impl FnOnce(&VertexDataAdapter<'_>) -> R) -> R {
+        f(self)
+    }
+}
+
+impl<T: DecodePosition> PositionBuffer for [T] {
+    /// Reads the positions in place if `T::POSITION_OFFSET` is set, and decodes them into a
+    /// temporary buffer otherwise.
+    fn with_positions<R>(&self, f: impl FnOnce(&VertexDataAdapter<'_>) -> R) -> R {
+        let in_place = T::POSITION_OFFSET.filter(|&offset| {
+            offset % 4 == 0
+                && offset + mem::size_of::<[f32; 3]>() <= mem::size_of::<T>()
+                && mem::align_of::<T>().is_multiple_of(4)
+        });
+        if let Some(offset) = in_place {
+            if let Ok(adapter) = VertexDataAdapter::from_slice(self, offset) {
+                return f(&adapter);
+            }
+        }
+        let positions: Vec<[f32; 3]> = self.iter().map(T::decode_position).collect();
+        let adapter =
+            VertexDataAdapter::from_slice(&positions, 0).expect("positions are tightly packed");
+        f(&adapter)
+    }
+}
+
+/// Fills a packed vertex from an unpacked vertex of type `V`, `Vertex` by default.
+pub trait FromVertex<V = Vertex> {
+    fn fill_from_vertex(&mut self, vertex: &V);
 }
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 #[repr(C)]
 pub struct PackedVertex {
     /// Unsigned 16-bit value, use `pos_offset/pos_scale` to unpack
@@ -46,6 +95,7 @@ impl FromVertex for PackedVertex {
 }
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 #[repr(C)]
 pub struct PackedVertexOct {
     pub p: [u16; 3],
@@ -59,32 +109,60 @@ impl FromVertex for PackedVertexOct {
         self.p[1] = quantize_half(vertex.p[1]);
         self.p[2] = quantize_half(vertex.p[2]);
 
-        let nsum = vertex.n[0].abs() + vertex.n[1].abs() + vertex.n[2].abs();
-        let nx = vertex.n[0] / nsum;
-        let ny = vertex.n[1] / nsum;
-        let nz = vertex.n[2];
+        let [nu, nv] = encode_oct_normal(vertex.n, 8);
+        self.n[0] = nu as u8;
+        self.n[1] = nv as u8;
 
-        let nu = if nz >= 0f32 {
-            nx
-        } else {
-            (1f32 - ny.abs()) * if nx >= 0f32 { 1f32 } else { -1f32 }
-        };
+        self.t[0] = quantize_half(vertex.t[0]);
+        self.t[1] = quantize_half(vertex.t[1]);
+    }
+}
 
-        let nv = if nz >= 0f32 {
-            ny
-        } else {
-            (1f32 - nx.abs()) * if ny >= 0f32 { 1f32 } else { -1f32 }
-        };
+/// Encodes a unit vector (e.g. a normal) using octahedron encoding, as two `bits`-bit
+/// snorm values (see `quantize_snorm`).
+///
+/// Decode with `decode_oct_normal` using the same number of bits.
+pub fn encode_oct_normal(n: [f32; 3], bits: u32) -> [i32; 2] {
+    let nsum = n[0].abs() + n[1].abs() + n[2].abs();
+    let nx = n[0] / nsum;
+    let ny = n[1] / nsum;
+    let nz = n[2];
 
-        self.n[0] = quantize_snorm(nu, 8) as u8;
-        self.n[1] = quantize_snorm(nv, 8) as u8;
+    let nu = if nz >= 0f32 {
+        nx
+    } else {
+        (1f32 - ny.abs()) * if nx >= 0f32 { 1f32 } else { -1f32 }
+    };
 
-        self.t[0] = quantize_half(vertex.t[0]);
-        self.t[1] = quantize_half(vertex.t[1]);
+    let nv = if nz >= 0f32 {
+        ny
+    } else {
+        (1f32 - nx.abs()) * if ny >= 0f32 { 1f32 } else { -1f32 }
+    };
+
+    [quantize_snorm(nu, bits), quantize_snorm(nv, bits)]
+}
+
+/// Decodes a unit vector encoded by `encode_oct_normal` with the same number of bits.
+pub fn decode_oct_normal(encoded: [i32; 2], bits: u32) -> [f32; 3] {
+    let scale = ((1i32 << (bits - 1)) - 1) as f32;
+    let mut x = (encoded[0] as f32 / scale).clamp(-1f32, 1f32);
+    let mut y = (encoded[1] as f32 / scale).clamp(-1f32, 1f32);
+    let z = 1f32 - x.abs() - y.abs();
+
+    // unfold the lower hemisphere
+    if z < 0f32 {
+        let (fx, fy) = (x, y);
+        x = (1f32 - fy.abs()) * if fx >= 0f32 { 1f32 } else { -1f32 };
+        y = (1f32 - fx.abs()) * if fy >= 0f32 { 1f32 } else { -1f32 };
     }
+
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
 }
 
 #[derive(Default, Debug, Copy, Clone, PartialOrd)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 #[repr(C)]
 /// A basic Vertex type that can be used with most mesh processing functions.
 ///
@@ -116,15 +194,337 @@ impl Eq for Vertex {}
 impl Vertex {}
 
 impl DecodePosition for Vertex {
+    const POSITION_OFFSET: Option<usize> = Some(0);
+
+    fn decode_position(&self) -> [f32; 3] {
+        self.p
+    }
+}
+
+/// A vertex with a tangent, for normal mapped meshes.
+#[derive(Default, Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct TangentVertex {
+    pub p: [f32; 3],
+    pub n: [f32; 3],
+    /// Tangent direction in `xyz`, with the bitangent sign (1 or -1) in `w`.
+    pub tangent: [f32; 4],
+    pub t: [f32; 2],
+}
+
+impl DecodePosition for TangentVertex {
+    const POSITION_OFFSET: Option<usize> = Some(0);
+
     fn decode_position(&self) -> [f32; 3] {
         self.p
     }
 }
 
+impl From<TangentVertex> for Vertex {
+    fn from(vertex: TangentVertex) -> Self {
+        Vertex {
+            p: vertex.p,
+            n: vertex.n,
+            t: vertex.t,
+        }
+    }
+}
+
+impl FromVertex<TangentVertex> for PackedVertex {
+    fn fill_from_vertex(&mut self, vertex: &TangentVertex) {
+        self.fill_from_vertex(&Vertex::from(*vertex));
+    }
+}
+
+impl FromVertex<TangentVertex> for PackedVertexOct {
+    fn fill_from_vertex(&mut self, vertex: &TangentVertex) {
+        self.fill_from_vertex(&Vertex::from(*vertex));
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PackedVertexTangentOct {
+    pub p: [u16; 3],
+    pub n: [u8; 2], // octahedron encoded normal, aliases .pw
+    /// Octahedron encoded tangent in `[0..2]`, normalized signed 8-bit bitangent sign in `[2]`.
+    pub tangent: [u8; 4],
+    pub t: [u16; 2],
+}
+
+impl FromVertex<TangentVertex> for PackedVertexTangentOct {
+    fn fill_from_vertex(&mut self, vertex: &TangentVertex) {
+        self.p[0] = quantize_half(vertex.p[0]);
+        self.p[1] = quantize_half(vertex.p[1]);
+        self.p[2] = quantize_half(vertex.p[2]);
+
+        let [nu, nv] = encode_oct_normal(vertex.n, 8);
+        self.n[0] = nu as u8;
+        self.n[1] = nv as u8;
+
+        let [tu, tv] =
+            encode_oct_normal([vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]], 8);
+        self.tangent[0] = tu as u8;
+        self.tangent[1] = tv as u8;
+        self.tangent[2] = quantize_snorm(
+            if vertex.tangent[3] < 0f32 {
+                -1f32
+            } else {
+                1f32
+            },
+            8,
+        ) as u8;
+        self.tangent[3] = 0u8;
+
+        self.t[0] = quantize_half(vertex.t[0]);
+        self.t[1] = quantize_half(vertex.t[1]);
+    }
+}
+
+/// Vertex with a 16-bit unorm position and a normal packed as 10:10:10:2 snorm, matching
+/// `R16G16B16A16_UNORM` and `R10G10B10A2_SNORM` formats.
+///
+/// `FromVertex` expects positions within the unit cube; use `pack_vertices_in_grid` to
+/// quantize arbitrary positions to their bounds instead.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PackedVertexSnorm10 {
+    /// Unsigned 16-bit position within the quantization grid, `p[3]` is unused
+    pub p: [u16; 4],
+
+    /// Normal in bits 0..30 (x in the lowest bits), bitangent sign (if any) in bits 30..32
+    pub n: u32,
+
+    /// Half-precision texture coordinates
+    pub t: [u16; 2],
+}
+
+impl PackedVertexSnorm10 {
+    /// Packs `vertex`, quantizing its position within `grid`.
+    pub fn from_vertex_in_grid(vertex: &Vertex, grid: &QuantizationGrid<3>) -> Self {
+        let mut result = Self::default();
+        result.fill_from_vertex(vertex);
+        result.set_position(grid.quantize(vertex.p, 16));
+        result
+    }
+
+    /// Packs `input` with positions quantized to a uniform grid covering their bounds, which
+    /// is returned along with the vertices to unpack them with `decode_position_in_grid`.
+    pub fn pack_vertices_in_grid(input: &[Vertex]) -> (Vec<Self>, QuantizationGrid<3>) {
+        let mut builder = QuantizationGrid::<3>::builder().uniform(true);
+        for vertex in input {
+            builder.add(vertex.p);
+        }
+        let grid = builder.build();
+        let vertices = input
+            .iter()
+            .map(|vertex| Self::from_vertex_in_grid(vertex, &grid))
+            .collect();
+        (vertices, grid)
+    }
+
+    /// Unpacks the position quantized within `grid`.
+    pub fn decode_position_in_grid(&self, grid: &QuantizationGrid<3>) -> [f32; 3] {
+        grid.dequantize(
+            [
+                u32::from(self.p[0]),
+                u32::from(self.p[1]),
+                u32::from(self.p[2]),
+            ],
+            16,
+        )
+    }
+
+    fn set_position(&mut self, q: [u32; 3]) {
+        self.p = [q[0] as u16, q[1] as u16, q[2] as u16, 0u16];
+    }
+
+    fn pack_normal(n: [f32; 3], w: f32) -> u32 {
+        let x = quantize_snorm(n[0], 10) as u32 & 0x3ff;
+        let y = quantize_snorm(n[1], 10) as u32 & 0x3ff;
+        let z = quantize_snorm(n[2], 10) as u32 & 0x3ff;
+        let w = quantize_snorm(w, 2) as u32 & 0x3;
+        x | (y << 10) | (z << 20) | (w << 30)
+    }
+
+    /// Unpacks the normal.
+    pub fn decode_normal(&self) -> [f32; 3] {
+        // shift each component to the top bits and back to sign extend it
+        let component = |shift: u32| dequantize_snorm(((self.n << (22 - shift)) as i32) >> 22, 10);
+        [component(0), component(10), component(20)]
+    }
+
+    /// Unpacks the bitangent sign stored by `FromVertex<TangentVertex>`, 0 if there is none.
+    pub fn decode_sign(&self) -> f32 {
+        dequantize_snorm((self.n as i32) >> 30, 2)
+    }
+
+    /// Unpacks the vertex, with the position within the unit cube.
+    pub fn decode(&self) -> Vertex {
+        Vertex {
+            p: self.decode_position(),
+            n: self.decode_normal(),
+            t: [dequantize_half(self.t[0]), dequantize_half(self.t[1])],
+        }
+    }
+}
+
+impl DecodePosition for PackedVertexSnorm10 {
+    /// Unpacks the position within the unit cube; positions packed with
+    /// `pack_vertices_in_grid` keep their proportions but not their scale.
+    fn decode_position(&self) -> [f32; 3] {
+        [
+            dequantize_unorm(i32::from(self.p[0]), 16),
+            dequantize_unorm(i32::from(self.p[1]), 16),
+            dequantize_unorm(i32::from(self.p[2]), 16),
+        ]
+    }
+}
+
+impl FromVertex for PackedVertexSnorm10 {
+    fn fill_from_vertex(&mut self, vertex: &Vertex) {
+        self.set_position([
+            quantize_unorm(vertex.p[0], 16) as u32,
+            quantize_unorm(vertex.p[1], 16) as u32,
+            quantize_unorm(vertex.p[2], 16) as u32,
+        ]);
+
+        self.n = Self::pack_normal(vertex.n, 0f32);
+
+        self.t[0] = quantize_half(vertex.t[0]);
+        self.t[1] = quantize_half(vertex.t[1]);
+    }
+}
+
+impl FromVertex<TangentVertex> for PackedVertexSnorm10 {
+    fn fill_from_vertex(&mut self, vertex: &TangentVertex) {
+        self.fill_from_vertex(&Vertex::from(*vertex));
+        self.n = Self::pack_normal(vertex.n, vertex.tangent[3]);
+    }
+}
+
+/// Packs vertices with tangents, like `pack_vertices` does for `Vertex`.
+pub fn pack_tangent_vertices<T: FromVertex<TangentVertex> + Default + Clone>(
+    input: &[TangentVertex],
+) -> Vec<T> {
+    input.iter().map_packed().collect()
+}
+
+impl HasVertexLayout for Vertex {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float32(3))
+            .with_attribute(AttributeSemantic::Normal, 12, AttributeFormat::Float32(3))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                24,
+                AttributeFormat::Float32(2),
+            )
+    }
+}
+
+impl HasVertexLayout for TangentVertex {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float32(3))
+            .with_attribute(AttributeSemantic::Normal, 12, AttributeFormat::Float32(3))
+            .with_attribute(AttributeSemantic::Tangent, 24, AttributeFormat::Float32(4))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                40,
+                AttributeFormat::Float32(2),
+            )
+    }
+}
+
+impl HasVertexLayout for PackedVertex {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float16(4))
+            .with_attribute(AttributeSemantic::Normal, 8, AttributeFormat::Snorm8(4))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                12,
+                AttributeFormat::Float16(2),
+            )
+    }
+}
+
+impl HasVertexLayout for PackedVertexOct {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float16(3))
+            .with_attribute(AttributeSemantic::Normal, 6, AttributeFormat::Snorm8(2))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                8,
+                AttributeFormat::Float16(2),
+            )
+    }
+}
+
+impl HasVertexLayout for PackedVertexTangentOct {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Float16(3))
+            .with_attribute(AttributeSemantic::Normal, 6, AttributeFormat::Snorm8(2))
+            .with_attribute(AttributeSemantic::Tangent, 8, AttributeFormat::Snorm8(3))
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                12,
+                AttributeFormat::Float16(2),
+            )
+    }
+}
+
+impl HasVertexLayout for PackedVertexSnorm10 {
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<Self>())
+            .with_attribute(AttributeSemantic::Position, 0, AttributeFormat::Unorm16(4))
+            .with_attribute(AttributeSemantic::Normal, 8, AttributeFormat::Snorm10x3)
+            .with_attribute(
+                AttributeSemantic::TexCoord(0),
+                12,
+                AttributeFormat::Float16(2),
+            )
+    }
+}
+
 pub fn pack_vertices<T: FromVertex + Default + Clone>(input: &[Vertex]) -> Vec<T> {
-    let mut vertices: Vec<T> = vec![T::default(); input.len()];
-    for i in 0..input.len() {
-        vertices[i].fill_from_vertex(&input[i]);
+    input.iter().map_packed().collect()
+}
+
+/// Packs `input` into `output`, e.g. mapped staging memory, without allocating.
+///
+/// Panics if `output` doesn't have the same length as `input`.
+pub fn pack_vertices_into<V, T: FromVertex<V>>(input: &[V], output: &mut [T]) {
+    assert_eq!(
+        input.len(),
+        output.len(),
+        "output must have room for exactly input.len() vertices"
+    );
+    for (vertex, input) in output.iter_mut().zip(input) {
+        vertex.fill_from_vertex(input);
+    }
+}
+
+/// Packs a single vertex.
+pub fn pack_vertex<V, T: FromVertex<V> + Default>(vertex: &V) -> T {
+    let mut packed = T::default();
+    packed.fill_from_vertex(vertex);
+    packed
+}
+
+/// Packing adapter for iterators over unpacked vertices.
+pub trait PackVerticesExt<'a, V: 'a>: Iterator<Item = &'a V> + Sized {
+    /// Packs the vertices lazily as the iterator is consumed, e.g.
+    /// `input.iter().map_packed::<PackedVertexOct>()`.
+    fn map_packed<T: FromVertex<V> + Default>(self) -> Map<Self, fn(&'a V) -> T> {
+        self.map(pack_vertex::<V, T>)
     }
-    vertices
 }
+
+impl<'a, V: 'a, I: Iterator<Item = &'a V>> PackVerticesExt<'a, V> for I {}
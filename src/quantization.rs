@@ -0,0 +1,163 @@
+use crate::{dequantize_unorm, quantize_unorm};
+
+/// Maps `N`-component attributes (e.g. positions or texture coordinates) to a grid of unorm
+/// values, as computed by `QuantizationGridBuilder`.
+///
+/// Values are reconstructed as `offset + q / (2^bits - 1) * scale`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct QuantizationGrid<const N: usize> {
+    pub offset: [f32; N],
+    pub scale: [f32; N],
+}
+
+impl<const N: usize> QuantizationGrid<N> {
+    /// Returns a builder computing the grid from attribute values.
+    pub fn builder() -> QuantizationGridBuilder<N> {
+        QuantizationGridBuilder::new()
+    }
+
+    /// Quantizes `value` to `bits`-bit unorm values, clamping values outside of the grid.
+    pub fn quantize(&self, value: [f32; N], bits: i32) -> [u32; N] {
+        let mut result = [0u32; N];
+        for (k, q) in result.iter_mut().enumerate() {
+            let v = if self.scale[k] > 0f32 {
+                (value[k] - self.offset[k]) / self.scale[k]
+            } else {
+                0f32
+            };
+            *q = quantize_unorm(v, bits) as u32;
+        }
+        result
+    }
+
+    /// Reconstructs a value quantized by `quantize` with the same number of bits.
+    pub fn dequantize(&self, quantized: [u32; N], bits: i32) -> [f32; N] {
+        let mut result = [0f32; N];
+        for (k, v) in result.iter_mut().enumerate() {
+            *v = self.offset[k] + dequantize_unorm(quantized[k] as i32, bits) * self.scale[k];
+        }
+        result
+    }
+}
+
+/// Computes a `QuantizationGrid` from the bounds of attribute values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct QuantizationGridBuilder<const N: usize> {
+    min: [f32; N],
+    max: [f32; N],
+    uniform: bool,
+    power_of_two: bool,
+}
+
+impl<const N: usize> Default for QuantizationGridBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> QuantizationGridBuilder<N> {
+    pub fn new() -> Self {
+        Self {
+            min: [f32::MAX; N],
+            max: [-f32::MAX; N],
+            uniform: false,
+            power_of_two: false,
+        }
+    }
+
+    /// Uses the same scale for all components, which preserves the aspect ratio; this is
+    /// what `calc_pos_offset_and_scale` does for positions.
+    pub fn uniform(mut self, uniform: bool) -> Self {
+        self.uniform = uniform;
+        self
+    }
+
+    /// Rounds the scale up to a power of two and aligns the offset to a multiple of it, so
+    /// that grids computed separately for neighboring tiles of the same size reconstruct
+    /// shared border values identically.
+    pub fn power_of_two(mut self, power_of_two: bool) -> Self {
+        self.power_of_two = power_of_two;
+        self
+    }
+
+    /// Extends the bounds to include `value`.
+    pub fn add(&mut self, value: [f32; N]) -> &mut Self {
+        for (k, &v) in value.iter().enumerate() {
+            self.min[k] = self.min[k].min(v);
+            self.max[k] = self.max[k].max(v);
+        }
+        self
+    }
+
+    /// Extends the bounds to include all `values`.
+    pub fn add_values(&mut self, values: &[[f32; N]]) -> &mut Self {
+        for &value in values {
+            self.add(value);
+        }
+        self
+    }
+
+    /// Extends the bounds to include the `N` floats located at byte `offset` within each
+    /// `stride` bytes of `data`, e.g. an attribute of interleaved vertex data.
+    ///
+    /// Panics if `stride` is 0 or the attribute doesn't fit within `stride`.
+    pub fn add_stream(&mut self, data: &[u8], stride: usize, offset: usize) -> &mut Self {
+        assert!(stride > 0 && offset + N * 4 <= stride);
+        for element in data.chunks_exact(stride) {
+            let mut value = [0f32; N];
+            for (k, bytes) in element[offset..offset + N * 4].chunks_exact(4).enumerate() {
+                value[k] = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            self.add(value);
+        }
+        self
+    }
+
+    /// Computes the grid; without any values, the grid has a zero offset and scale.
+    pub fn build(&self) -> QuantizationGrid<N> {
+        if (0..N).any(|k| self.min[k] > self.max[k]) {
+            return QuantizationGrid {
+                offset: [0f32; N],
+                scale: [0f32; N],
+            };
+        }
+
+        let mut offset = self.min;
+        let mut scale = [0f32; N];
+        for (k, s) in scale.iter_mut().enumerate() {
+            *s = if self.power_of_two {
+                self.power_of_two_scale(k)
+            } else {
+                self.max[k] - self.min[k]
+            };
+        }
+
+        if self.uniform {
+            // a power of two scale that covers one component covers it at larger powers too
+            let max_scale = scale.iter().fold(0f32, |result, &s| result.max(s));
+            scale = [max_scale; N];
+        }
+        if self.power_of_two {
+            for (k, offset) in offset.iter_mut().enumerate() {
+                *offset = (self.min[k] / scale[k]).floor() * scale[k];
+            }
+        }
+
+        QuantizationGrid { offset, scale }
+    }
+
+    /// Returns the smallest power of two scale that covers component `k` from an offset
+    /// aligned to a multiple of the scale.
+    fn power_of_two_scale(&self, k: usize) -> f32 {
+        let extent = self.max[k] - self.min[k];
+        let mut scale = if extent > 0f32 {
+            2f32.powi(extent.log2().ceil() as i32)
+        } else {
+            1f32
+        };
+        while (self.min[k] / scale).floor() * scale + scale < self.max[k] {
+            scale *= 2f32;
+        }
+        scale
+    }
+}
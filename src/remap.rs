@@ -1,4 +1,4 @@
-use crate::{ffi, VertexStream};
+use crate::{ffi, utilities::UninitBuffer, Index, VertexStream};
 use std::mem;
 
 /// Generates a vertex remap table from the vertex buffer and an optional index buffer and returns number of unique vertices.
@@ -8,7 +8,7 @@ use std::mem;
 ///
 /// The `indices` can be `None` if the input is unindexed.
 pub fn generate_vertex_remap<T>(vertices: &[T], indices: Option<&[u32]>) -> (usize, Vec<u32>) {
-    let mut remap: Vec<u32> = vec![0; vertices.len()];
+    let mut remap = UninitBuffer::<u32>::new(vertices.len());
     let vertex_count = unsafe {
         match indices {
             Some(indices) => ffi::meshopt_generateVertexRemap(
@@ -29,7 +29,7 @@ pub fn generate_vertex_remap<T>(vertices: &[T], indices: Option<&[u32]>) -> (usi
             ),
         }
     };
-    (vertex_count, remap)
+    (vertex_count, unsafe { remap.assume_init(vertices.len()) })
 }
 
 /// Generates a vertex remap table from multiple vertex streams and an optional index buffer and returns number of unique vertices.
@@ -39,12 +39,21 @@ pub fn generate_vertex_remap<T>(vertices: &[T], indices: Option<&[u32]>) -> (usi
 ///
 /// To remap vertex buffers, you will need to call `remap_vertex_buffer` for each vertex stream.
 ///
+/// Streams created with `VertexStream::from_slice` are checked to contain at least
+/// `vertex_count` vertices, so deinterleaved buffers can be deduplicated without unsafe code.
+///
 /// The `indices` can be `None` if the input is unindexed.
 pub fn generate_vertex_remap_multi(
     vertex_count: usize,
     streams: &[VertexStream<'_>],
     indices: Option<&[u32]>,
 ) -> (usize, Vec<u32>) {
+    for stream in streams {
+        stream.validate(vertex_count);
+    }
+    if let Some(indices) = indices {
+        assert!(indices.iter().all(|&index| (index as usize) < vertex_count));
+    }
     let streams: Vec<ffi::meshopt_Stream> = streams
         .iter()
         .map(|stream| ffi::meshopt_Stream {
@@ -53,8 +62,8 @@ pub fn generate_vertex_remap_multi(
             stride: stream.stride,
         })
         .collect();
-    let mut remap: Vec<u32> = vec![0; vertex_count];
-    let vertex_count = unsafe {
+    let mut remap = UninitBuffer::new(vertex_count);
+    let unique_count = unsafe {
         match indices {
             Some(indices) => ffi::meshopt_generateVertexRemapMulti(
                 remap.as_mut_ptr(),
@@ -74,15 +83,22 @@ pub fn generate_vertex_remap_multi(
             ),
         }
     };
-    (vertex_count, remap)
+    (unique_count, unsafe { remap.assume_init(vertex_count) })
 }
 
 /// Generate index buffer from the source index buffer and remap table generated by `generate_vertex_remap`.
 ///
+/// Accepts both `u16` and `u32` indices.
+///
 /// `indices` can be `None` if the input is unindexed.
-pub fn remap_index_buffer(indices: Option<&[u32]>, vertex_count: usize, remap: &[u32]) -> Vec<u32> {
+pub fn remap_index_buffer<T: Index>(
+    indices: Option<&[T]>,
+    vertex_count: usize,
+    remap: &[u32],
+) -> Vec<T> {
     let mut result: Vec<u32> = Vec::new();
     if let Some(indices) = indices {
+        let indices = T::to_u32_slice(indices);
         result.resize(indices.len(), 0u32);
         unsafe {
             ffi::meshopt_remapIndexBuffer(
@@ -104,7 +120,7 @@ pub fn remap_index_buffer(indices: Option<&[u32]>, vertex_count: usize, remap: &
         }
     }
 
-    result
+    result.into_iter().map(T::from_u32).collect()
 }
 
 /// Generates vertex buffer from the source vertex buffer and remap table generated by `generate_vertex_remap`.
@@ -125,3 +141,61 @@ pub fn remap_vertex_buffer<T: Clone + Default>(
     }
     result
 }
+
+/// A deinterleaved vertex stream stored as raw bytes, see `remap_vertex_buffers`.
+#[derive(Debug)]
+pub struct RemapStream<'a> {
+    /// Vertex data, replaced with the remapped vertex data by `remap_vertex_buffers`.
+    pub data: &'a mut Vec<u8>,
+    /// Size of a single vertex in this stream, in bytes.
+    pub vertex_size: usize,
+}
+
+/// Applies a remap table generated by `generate_vertex_remap` or `generate_vertex_remap_multi`
+/// to several vertex streams at once, leaving `vertex_count` vertices in each stream.
+///
+/// The remap table is walked once for all streams, and streams of any vertex size can be
+/// remapped without converting them to typed vectors first.
+pub fn remap_vertex_buffers(streams: &mut [RemapStream<'_>], remap: &[u32], vertex_count: usize) {
+    let mut remapped: Vec<Vec<u8>> = streams
+        .iter()
+        .map(|stream| {
+            assert_eq!(stream.data.len(), remap.len() * stream.vertex_size);
+            vec![0u8; vertex_count * stream.vertex_size]
+        })
+        .collect();
+
+    for (vertex, &target) in remap.iter().enumerate() {
+        // unreferenced vertices are marked with !0
+        if target == !0 {
+            continue;
+        }
+        let target = target as usize;
+        assert!(target < vertex_count);
+        for (stream, destination) in streams.iter().zip(&mut remapped) {
+            let size = stream.vertex_size;
+            destination[target * size..(target + 1) * size]
+                .copy_from_slice(&stream.data[vertex * size..(vertex + 1) * size]);
+        }
+    }
+
+    for (stream, remapped) in streams.iter_mut().zip(remapped) {
+        *stream.data = remapped;
+    }
+}
+
+/// Inverts a remap table generated by `generate_vertex_remap` (or another function returning
+/// an old to new vertex remap), returning a table with `vertex_count` entries that maps each new
+/// vertex to the first old vertex that was remapped to it.
+///
+/// New vertices that no old vertex maps to are marked with `!0`; old vertices marked with `!0`
+/// are ignored.
+pub fn invert_remap(remap: &[u32], vertex_count: usize) -> Vec<u32> {
+    let mut inverse: Vec<u32> = vec![!0; vertex_count];
+    for (vertex, &target) in remap.iter().enumerate() {
+        if target != !0 && inverse[target as usize] == !0 {
+            inverse[target as usize] = vertex as u32;
+        }
+    }
+    inverse
+}
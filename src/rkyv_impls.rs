@@ -0,0 +1,64 @@
+//! `rkyv` support for the generated FFI structs, and validation of archived meshlets.
+
+use crate::{ffi, ArchivedMeshlets, MeshletDef};
+use rkyv::bytecheck::Verify;
+use rkyv::rancor::{Fallible, Source};
+use std::fmt;
+
+impl From<MeshletDef> for ffi::meshopt_Meshlet {
+    fn from(meshlet: MeshletDef) -> Self {
+        Self {
+            vertex_offset: meshlet.vertex_offset,
+            triangle_offset: meshlet.triangle_offset,
+            vertex_count: meshlet.vertex_count,
+            triangle_count: meshlet.triangle_count,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MeshletRangeError(usize);
+
+impl fmt::Display for MeshletRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "meshlet {} references vertices or micro-indices out of range",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for MeshletRangeError {}
+
+// Safety: `verify` only reads the archived buffers, whose layout has been checked already.
+unsafe impl<C: Fallible + ?Sized> Verify<C> for ArchivedMeshlets
+where
+    C::Error: Source,
+{
+    /// Checks the same invariants as `Meshlets::validate`.
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        for (idx, meshlet) in self.meshlets.iter().enumerate() {
+            let vertex_offset = meshlet.vertex_offset.to_native() as usize;
+            let vertex_count = meshlet.vertex_count.to_native() as usize;
+            let triangle_offset = meshlet.triangle_offset.to_native() as usize;
+            let triangle_count = meshlet.triangle_count.to_native() as usize;
+
+            let triangles = triangle_count.checked_mul(3).and_then(|count| {
+                self.triangles
+                    .get(triangle_offset..triangle_offset.checked_add(count)?)
+            });
+            let valid = matches!(
+                vertex_offset.checked_add(vertex_count),
+                Some(end) if end <= self.vertices.len()
+            ) && matches!(
+                triangles,
+                Some(triangles) if triangles.iter().all(|&index| (index as usize) < vertex_count)
+            );
+            if !valid {
+                return Err(Source::new(MeshletRangeError(idx)));
+            }
+        }
+        Ok(())
+    }
+}
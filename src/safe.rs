@@ -0,0 +1,24 @@
+//! A facade over the crate where no safe public function takes or returns raw pointers.
+//!
+//! Everything except `ffi` is re-exported as is, so the full API can be used from crates
+//! built with `#![forbid(unsafe_code)]` without ever handing out a pointer; `VertexStream`
+//! can only be created from borrowed slices there, as its pointer constructors are `unsafe`.
+//!
+//! The byte reinterpretation helpers `typed_to_bytes`, `any_as_u8_slice`, `content_hash`
+//! and `ContentHasher` are left out, as they are only sound for types without padding; use
+//! `bytemuck` to view such types as bytes instead.
+
+#[cfg(feature = "rayon")]
+pub use crate::batch::*;
+pub use crate::utilities::{
+    convert_indices_16_to_32, convert_indices_32_to_16, dequantize_half, dequantize_snorm,
+    dequantize_unorm, quantize_float, quantize_float_slice, quantize_half, quantize_half_slice,
+    quantize_snorm, quantize_snorm_slice, quantize_unorm, quantize_unorm_slice, rcp_safe,
+    validate_indices, Index, IndexBuffer, VertexDataAdapter,
+};
+pub use crate::{
+    allocator::*, analyze::*, clusterize::*, compression::*, context::*, decoding::*, encoding::*,
+    error::*, export::*, hierarchy::*, layout::*, locks::*, lod::*, mesh_shader::*, optimize::*,
+    overdraw::*, packing::*, quantization::*, remap::*, shadow::*, simplify::*, source::*,
+    streaming::*, stripify::*, VertexStream,
+};
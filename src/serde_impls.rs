@@ -0,0 +1,90 @@
+//! `serde` implementations for the generated FFI structs, which can't carry derives, and
+//! for types whose deserialization needs validation.
+
+use crate::{ffi, Error, Meshlets};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Implements `Serialize` and `Deserialize` for an FFI struct through the remote
+/// definition mirroring its fields.
+macro_rules! impl_remote {
+    ($ty:ty, $def:ident) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $def::serialize(self, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $def::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ffi::meshopt_Bounds")]
+struct BoundsDef {
+    center: [f32; 3],
+    radius: f32,
+    cone_apex: [f32; 3],
+    cone_axis: [f32; 3],
+    cone_cutoff: f32,
+    cone_axis_s8: [i8; 3],
+    cone_cutoff_s8: i8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ffi::meshopt_Meshlet")]
+struct MeshletDef {
+    vertex_offset: u32,
+    triangle_offset: u32,
+    vertex_count: u32,
+    triangle_count: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ffi::meshopt_VertexCacheStatistics")]
+struct VertexCacheStatisticsDef {
+    vertices_transformed: u32,
+    warps_executed: u32,
+    acmr: f32,
+    atvr: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ffi::meshopt_VertexFetchStatistics")]
+struct VertexFetchStatisticsDef {
+    bytes_fetched: u32,
+    overfetch: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ffi::meshopt_OverdrawStatistics")]
+struct OverdrawStatisticsDef {
+    pixels_covered: u32,
+    pixels_shaded: u32,
+    overdraw: f32,
+}
+
+impl_remote!(ffi::meshopt_Bounds, BoundsDef);
+impl_remote!(ffi::meshopt_Meshlet, MeshletDef);
+impl_remote!(ffi::meshopt_VertexCacheStatistics, VertexCacheStatisticsDef);
+impl_remote!(ffi::meshopt_VertexFetchStatistics, VertexFetchStatisticsDef);
+impl_remote!(ffi::meshopt_OverdrawStatistics, OverdrawStatisticsDef);
+
+/// The fields of `Meshlets`, checked by `Meshlets::from_parts` after deserialization.
+#[derive(Deserialize)]
+pub(crate) struct MeshletsParts {
+    meshlets: Vec<ffi::meshopt_Meshlet>,
+    vertices: Vec<u32>,
+    triangles: Vec<u8>,
+}
+
+impl TryFrom<MeshletsParts> for Meshlets {
+    type Error = Error;
+
+    fn try_from(parts: MeshletsParts) -> Result<Self, Error> {
+        Self::from_parts(parts.meshlets, parts.vertices, parts.triangles)
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{ffi, DecodePosition, VertexDataAdapter, VertexStream};
+use crate::{
+    ffi,
+    utilities::{validate_indices, UninitBuffer},
+    AttributeSemantic, DecodePosition, Error, Index, PositionBuffer, Result, VertexDataAdapter,
+    VertexLayout, VertexStream,
+};
+use std::mem;
 
 /// Generate index buffer that can be used for more efficient rendering when only a subset of the vertex
 /// attributes is necessary.
@@ -8,12 +14,18 @@ use crate::{ffi, DecodePosition, VertexDataAdapter, VertexStream};
 ///
 /// This makes it possible to use the index buffer for Z pre-pass or shadowmap rendering, while using
 /// the original index buffer for regular rendering.
-pub fn generate_shadow_indices(indices: &[u32], vertices: &VertexDataAdapter<'_>) -> Vec<u32> {
+///
+/// Accepts both `u16` and `u32` indices, as do the other shadow index functions.
+pub fn generate_shadow_indices<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+) -> Vec<T> {
+    let indices = T::to_u32_slice(indices);
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    let mut shadow_indices: Vec<u32> = vec![0; indices.len()];
-    unsafe {
+    let mut shadow_indices = UninitBuffer::new(indices.len());
+    let shadow_indices = unsafe {
         ffi::meshopt_generateShadowIndexBuffer(
             shadow_indices.as_mut_ptr(),
             indices.as_ptr(),
@@ -23,8 +35,51 @@ pub fn generate_shadow_indices(indices: &[u32], vertices: &VertexDataAdapter<'_>
             std::mem::size_of::<f32>() * 3,
             vertices.vertex_stride,
         );
+        shadow_indices.assume_init(indices.len())
+    };
+    shadow_indices.into_iter().map(Index::from_u32).collect()
+}
+
+/// Generate index buffer that can be used for more efficient rendering when only a subset of the vertex
+/// attributes is necessary.
+///
+/// All vertices that are binary equivalent wrt the first `vertex_size` bytes starting at the position
+/// map to the first vertex in the original vertex buffer. Unlike `generate_shadow_indices`, which only
+/// compares positions, this can include attributes stored after the position, e.g. texture coordinates
+/// for alpha-tested shadows.
+///
+/// Returns an error if `vertex_size` bytes starting at the position don't fit within the vertex stride.
+pub fn generate_shadow_indices_with_size<T: Index>(
+    indices: &[T],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_size: usize,
+) -> Result<Vec<T>> {
+    let end = vertices.position_offset.checked_add(vertex_size);
+    if vertex_size == 0 || !matches!(end, Some(end) if end <= vertices.vertex_stride) {
+        return Err(Error::Config(format!(
+            "vertex_size ({}) must be non-zero and fit within vertex_stride ({}) after position_offset ({})",
+            vertex_size, vertices.vertex_stride, vertices.position_offset
+        )));
     }
-    shadow_indices
+
+    let indices = T::to_u32_slice(indices);
+    let vertex_data = vertices.reader.get_ref();
+    let vertex_data = vertex_data.as_ptr().cast::<u8>();
+    let positions = unsafe { vertex_data.add(vertices.position_offset) };
+    let mut shadow_indices = UninitBuffer::new(indices.len());
+    let shadow_indices = unsafe {
+        ffi::meshopt_generateShadowIndexBuffer(
+            shadow_indices.as_mut_ptr(),
+            indices.as_ptr(),
+            indices.len(),
+            positions.cast(),
+            vertices.vertex_count,
+            vertex_size,
+            vertices.vertex_stride,
+        );
+        shadow_indices.assume_init(indices.len())
+    };
+    Ok(shadow_indices.into_iter().map(Index::from_u32).collect())
 }
 
 /// Generate index buffer that can be used for more efficient rendering when only a subset of the vertex
@@ -35,28 +90,14 @@ pub fn generate_shadow_indices(indices: &[u32], vertices: &VertexDataAdapter<'_>
 ///
 /// This makes it possible to use the index buffer for Z pre-pass or shadowmap rendering, while using
 /// the original index buffer for regular rendering.
-pub fn generate_shadow_indices_decoder<T: DecodePosition>(
-    indices: &[u32],
+pub fn generate_shadow_indices_decoder<I: Index, T: DecodePosition>(
+    indices: &[I],
     vertices: &[T],
-) -> Vec<u32> {
-    let vertices = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    let positions = vertices.as_ptr().cast();
-    let mut shadow_indices: Vec<u32> = vec![0; indices.len()];
-    unsafe {
-        ffi::meshopt_generateShadowIndexBuffer(
-            shadow_indices.as_mut_ptr(),
-            indices.as_ptr(),
-            indices.len(),
-            positions,
-            vertices.len() * 3,
-            std::mem::size_of::<f32>() * 3,
-            std::mem::size_of::<f32>() * 3,
-        );
-    }
-    shadow_indices
+) -> Vec<I> {
+    vertices.with_positions(|positions| {
+        generate_shadow_indices_with_size(indices, positions, mem::size_of::<[f32; 3]>())
+            .expect("positions fit within a vertex")
+    })
 }
 
 /// Generate index buffer that can be used for more efficient rendering when only a subset of the vertex
@@ -67,11 +108,21 @@ pub fn generate_shadow_indices_decoder<T: DecodePosition>(
 ///
 /// This makes it possible to use the index buffer for Z pre-pass or shadowmap rendering, while using
 /// the original index buffer for regular rendering.
-pub fn generate_shadow_indices_multi(
-    indices: &[u32],
+///
+/// # Panics
+///
+/// Panics if an index is out of range, or a stream created from a slice contains fewer than
+/// `vertex_count` vertices.
+pub fn generate_shadow_indices_multi<T: Index>(
+    indices: &[T],
     vertex_count: usize,
     streams: &[VertexStream<'_>],
-) -> Vec<u32> {
+) -> Vec<T> {
+    let indices = T::to_u32_slice(indices);
+    for stream in streams {
+        stream.validate(vertex_count);
+    }
+    assert!(indices.iter().all(|&index| (index as usize) < vertex_count));
     let streams: Vec<ffi::meshopt_Stream> = streams
         .iter()
         .map(|stream| ffi::meshopt_Stream {
@@ -80,8 +131,8 @@ pub fn generate_shadow_indices_multi(
             stride: stream.stride,
         })
         .collect();
-    let mut shadow_indices: Vec<u32> = vec![0; indices.len()];
-    unsafe {
+    let mut shadow_indices = UninitBuffer::new(indices.len());
+    let shadow_indices = unsafe {
         ffi::meshopt_generateShadowIndexBufferMulti(
             shadow_indices.as_mut_ptr(),
             indices.as_ptr(),
@@ -90,6 +141,60 @@ pub fn generate_shadow_indices_multi(
             streams.as_ptr(),
             streams.len(),
         );
+        shadow_indices.assume_init(indices.len())
+    };
+    shadow_indices.into_iter().map(Index::from_u32).collect()
+}
+
+/// Generate index buffer that can be used for more efficient rendering when only a subset of the vertex
+/// attributes is necessary.
+///
+/// All vertices that are binary equivalent wrt the attributes of `layout` with the given `semantics`
+/// (e.g. position and texture coordinates for alpha-tested shadows) map to the first vertex in the
+/// original vertex buffer.
+///
+/// Returns an error if an attribute is missing, the layout doesn't match `vertices` or an index is
+/// out of range.
+pub fn generate_shadow_indices_layout<T: Index>(
+    indices: &[T],
+    vertices: &[u8],
+    layout: &VertexLayout,
+    semantics: &[AttributeSemantic],
+) -> Result<Vec<T>> {
+    layout.validate()?;
+    if layout.stride == 0 || !vertices.len().is_multiple_of(layout.stride) {
+        return Err(Error::Config(format!(
+            "vertex data length ({}) must be a multiple of the layout stride ({})",
+            vertices.len(),
+            layout.stride
+        )));
     }
-    shadow_indices
+    let vertex_count = vertices.len() / layout.stride;
+    let streams = semantics
+        .iter()
+        .map(|&semantic| {
+            let attribute = layout.require(semantic)?;
+            Ok(ffi::meshopt_Stream {
+                data: vertices.as_ptr().wrapping_add(attribute.offset).cast(),
+                size: attribute.format.size(),
+                stride: layout.stride,
+            })
+        })
+        .collect::<Result<Vec<ffi::meshopt_Stream>>>()?;
+
+    validate_indices(indices, vertex_count)?;
+    let indices = T::to_u32_slice(indices);
+    let mut shadow_indices = UninitBuffer::new(indices.len());
+    let shadow_indices = unsafe {
+        ffi::meshopt_generateShadowIndexBufferMulti(
+            shadow_indices.as_mut_ptr(),
+            indices.as_ptr(),
+            indices.len(),
+            vertex_count,
+            streams.as_ptr(),
+            streams.len(),
+        );
+        shadow_indices.assume_init(indices.len())
+    };
+    Ok(shadow_indices.into_iter().map(Index::from_u32).collect())
 }
@@ -1,5 +1,11 @@
-use crate::{ffi, DecodePosition, VertexDataAdapter};
+use crate::{
+    ffi,
+    utilities::{check_indices, validate_indices, UninitBuffer},
+    AttributeFormat, AttributeSemantic, DecodePosition, Error, PositionBuffer, Result,
+    VertexDataAdapter, VertexLayout,
+};
 use bitflags::bitflags;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 
 bitflags! {
@@ -19,6 +25,33 @@ bitflags! {
     }
 }
 
+/// Result of a simplification, as returned by the `*_result` simplify functions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimplifyResult {
+    /// The simplified index buffer, referencing vertices from the original vertex buffer.
+    pub indices: Vec<u32>,
+    /// The error after simplification; relative to mesh extents unless
+    /// `SimplifyOptions::ErrorAbsolute` was used.
+    pub error: f32,
+    /// The ratio of the resulting index count to the source index count (1.0 for empty input).
+    pub achieved_ratio: f32,
+}
+
+impl SimplifyResult {
+    fn new(indices: Vec<u32>, source_index_count: usize, error: f32) -> Self {
+        let achieved_ratio = if source_index_count == 0 {
+            1f32
+        } else {
+            indices.len() as f32 / source_index_count as f32
+        };
+        Self {
+            indices,
+            error,
+            achieved_ratio,
+        }
+    }
+}
+
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
 /// appearance as much as possible.
 ///
@@ -34,13 +67,78 @@ pub fn simplify(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
+    let mut result = UninitBuffer::new(indices.len());
+    unsafe {
+        let index_count = simplify_raw(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            options,
+            result.as_mut_ptr(),
+            result.len(),
+            result_error,
+        );
+        result.assume_init(index_count)
+    }
+}
+
+/// Same as `simplify`, writing the resulting index buffer into `destination` and returning
+/// the number of indices written.
+///
+/// # Panics
+///
+/// Panics if `destination` has room for fewer than `indices.len()` elements.
+pub fn simplify_into(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    unsafe {
+        simplify_raw(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            options,
+            destination.as_mut_ptr(),
+            destination.len(),
+            result_error,
+        )
+    }
+}
+
+/// Same as `simplify_into`, writing to `capacity` elements at `destination`.
+///
+/// # Safety
+///
+/// `destination` must be valid for writes of `capacity` elements.
+#[allow(clippy::too_many_arguments)]
+unsafe fn simplify_raw(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: *mut u32,
+    capacity: usize,
+    result_error: Option<&mut f32>,
+) -> usize {
+    check_indices(indices, vertices.vertex_count);
+    assert!(
+        capacity >= indices.len(),
+        "destination must have room for at least indices.len() elements"
+    );
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
+    unsafe {
         ffi::meshopt_simplify(
-            result.as_mut_ptr().cast(),
+            destination.cast(),
             indices.as_ptr().cast(),
             indices.len(),
             positions.cast::<f32>(),
@@ -51,9 +149,60 @@ pub fn simplify(
             options.bits(),
             result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
         )
+    }
+}
+
+/// Reduces the number of triangles in the mesh like `simplify`, writing the resulting
+/// index buffer into `indices` instead of a new buffer; the result never has more indices
+/// than the source, so no second index buffer is allocated.
+pub fn simplify_in_place(
+    indices: &mut Vec<u32>,
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    result_error: Option<&mut f32>,
+) {
+    check_indices(indices, vertices.vertex_count);
+    let vertex_data = vertices.reader.get_ref();
+    let positions = unsafe { vertex_data.as_ptr().add(vertices.position_offset) };
+    let indices_ptr = indices.as_mut_ptr();
+    let index_count = unsafe {
+        ffi::meshopt_simplify(
+            indices_ptr,
+            indices_ptr,
+            indices.len(),
+            positions.cast::<f32>(),
+            vertices.vertex_count,
+            vertices.vertex_stride,
+            target_count,
+            target_error,
+            options.bits(),
+            result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
+        )
     };
-    result.resize(index_count, 0u32);
-    result
+    indices.truncate(index_count);
+}
+
+/// Same as `simplify`, returning the resulting index buffer together with the achieved
+/// error and reduction ratio.
+pub fn simplify_result(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify(
+        indices,
+        vertices,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
@@ -71,27 +220,63 @@ pub fn simplify_decoder<T: DecodePosition>(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
-        ffi::meshopt_simplify(
-            result.as_mut_ptr().cast(),
-            indices.as_ptr().cast(),
-            indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
+    vertices.with_positions(|positions| {
+        simplify(
+            indices,
+            positions,
             target_count,
             target_error,
-            options.bits(),
-            result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
+            options,
+            result_error,
         )
-    };
-    result.resize(index_count, 0u32);
-    result
+    })
+}
+
+/// Same as `simplify_into`, reading the positions of `vertices` through `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `destination` has room for fewer than `indices.len()` elements.
+pub fn simplify_decoder_into<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    vertices.with_positions(|positions| {
+        simplify_into(
+            indices,
+            positions,
+            target_count,
+            target_error,
+            options,
+            destination,
+            result_error,
+        )
+    })
+}
+
+/// Same as `simplify_result`, reading the positions of `vertices` through `DecodePosition`.
+pub fn simplify_decoder_result<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_decoder(
+        indices,
+        vertices,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
@@ -101,6 +286,12 @@ pub fn simplify_decoder<T: DecodePosition>(
 ///
 /// If the original vertex data isn't required, creating a compact vertex buffer
 /// using `optimize_vertex_fetch` is recommended.
+///
+/// `vertex_lock` may be empty, in which case no vertices are locked.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex.
 pub fn simplify_with_locks(
     indices: &[u32],
     vertices: &VertexDataAdapter<'_>,
@@ -110,13 +301,85 @@ pub fn simplify_with_locks(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
+    let mut result = UninitBuffer::new(indices.len());
+    unsafe {
+        let index_count = simplify_with_locks_raw(
+            indices,
+            vertices,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            result.as_mut_ptr(),
+            result.len(),
+            result_error,
+        );
+        result.assume_init(index_count)
+    }
+}
+
+/// Same as `simplify_with_locks`, writing the resulting index buffer into `destination` and
+/// returning the number of indices written.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, or if
+/// `destination` has room for fewer than `indices.len()` elements.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_locks_into(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    unsafe {
+        simplify_with_locks_raw(
+            indices,
+            vertices,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            destination.as_mut_ptr(),
+            destination.len(),
+            result_error,
+        )
+    }
+}
+
+/// Same as `simplify_with_locks_into`, writing to `capacity` elements at `destination`.
+///
+/// # Safety
+///
+/// `destination` must be valid for writes of `capacity` elements.
+#[allow(clippy::too_many_arguments)]
+unsafe fn simplify_with_locks_raw(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: *mut u32,
+    capacity: usize,
+    result_error: Option<&mut f32>,
+) -> usize {
+    check_indices(indices, vertices.vertex_count);
+    assert!(
+        capacity >= indices.len(),
+        "destination must have room for at least indices.len() elements"
+    );
+    let vertex_lock = lock_ptr(vertex_lock, vertices.vertex_count);
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
+    unsafe {
         ffi::meshopt_simplifyWithAttributes(
-            result.as_mut_ptr().cast(),
+            destination.cast(),
             indices.as_ptr().cast(),
             indices.len(),
             positions.cast::<f32>(),
@@ -126,15 +389,40 @@ pub fn simplify_with_locks(
             0,
             std::ptr::null(),
             0,
-            vertex_lock.as_ptr().cast(),
+            vertex_lock,
             target_count,
             target_error,
             options.bits(),
             result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
         )
-    };
-    result.resize(index_count, 0u32);
-    result
+    }
+}
+
+/// Same as `simplify_with_locks`, returning the resulting index buffer together with the
+/// achieved error and reduction ratio.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex.
+pub fn simplify_with_locks_result(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_with_locks(
+        indices,
+        vertices,
+        vertex_lock,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
@@ -144,6 +432,12 @@ pub fn simplify_with_locks(
 ///
 /// If the original vertex data isn't required, creating a compact vertex buffer
 /// using `optimize_vertex_fetch` is recommended.
+///
+/// `vertex_lock` may be empty, in which case no vertices are locked.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex.
 pub fn simplify_with_locks_decoder<T: DecodePosition>(
     indices: &[u32],
     vertices: &[T],
@@ -153,32 +447,76 @@ pub fn simplify_with_locks_decoder<T: DecodePosition>(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
-        ffi::meshopt_simplifyWithAttributes(
-            result.as_mut_ptr().cast(),
-            indices.as_ptr().cast(),
-            indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
-            std::ptr::null(),
-            0,
-            std::ptr::null(),
-            0,
-            vertex_lock.as_ptr().cast(),
+    vertices.with_positions(|positions| {
+        simplify_with_locks(
+            indices,
+            positions,
+            vertex_lock,
             target_count,
             target_error,
-            options.bits(),
-            result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
+            options,
+            result_error,
         )
-    };
-    result.resize(index_count, 0u32);
-    result
+    })
+}
+
+/// Same as `simplify_with_locks_into`, reading the positions of `vertices` through
+/// `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, or if
+/// `destination` has room for fewer than `indices.len()` elements.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_locks_decoder_into<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    vertices.with_positions(|positions| {
+        simplify_with_locks_into(
+            indices,
+            positions,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            destination,
+            result_error,
+        )
+    })
+}
+
+/// Same as `simplify_with_locks_result`, reading the positions of `vertices` through
+/// `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex.
+pub fn simplify_with_locks_decoder_result<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_with_locks_decoder(
+        indices,
+        vertices,
+        vertex_lock,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
@@ -189,6 +527,14 @@ pub fn simplify_with_locks_decoder<T: DecodePosition>(
 ///
 /// If the original vertex data isn't required, creating a compact vertex buffer
 /// using `optimize_vertex_fetch` is recommended.
+///
+/// `vertex_lock` may be empty, in which case no vertices are locked.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, or if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`.
 #[allow(clippy::too_many_arguments)]
 pub fn simplify_with_attributes_and_locks(
     indices: &[u32],
@@ -202,13 +548,106 @@ pub fn simplify_with_attributes_and_locks(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
+    let mut result = UninitBuffer::new(indices.len());
+    unsafe {
+        let index_count = simplify_with_attributes_and_locks_raw(
+            indices,
+            vertices,
+            vertex_attributes,
+            vertex_attribute_weights,
+            vertex_attributes_stride,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            result.as_mut_ptr(),
+            result.len(),
+            result_error,
+        );
+        result.assume_init(index_count)
+    }
+}
+
+/// Same as `simplify_with_attributes_and_locks`, writing the resulting index buffer into
+/// `destination` and returning the number of indices written.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`, or
+/// if `destination` has room for fewer than `indices.len()` elements.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_attributes_and_locks_into(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    unsafe {
+        simplify_with_attributes_and_locks_raw(
+            indices,
+            vertices,
+            vertex_attributes,
+            vertex_attribute_weights,
+            vertex_attributes_stride,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            destination.as_mut_ptr(),
+            destination.len(),
+            result_error,
+        )
+    }
+}
+
+/// Same as `simplify_with_attributes_and_locks_into`, writing to `capacity` elements at
+/// `destination`.
+///
+/// # Safety
+///
+/// `destination` must be valid for writes of `capacity` elements.
+#[allow(clippy::too_many_arguments)]
+unsafe fn simplify_with_attributes_and_locks_raw(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: *mut u32,
+    capacity: usize,
+    result_error: Option<&mut f32>,
+) -> usize {
+    check_indices(indices, vertices.vertex_count);
+    assert!(
+        capacity >= indices.len(),
+        "destination must have room for at least indices.len() elements"
+    );
+    validate_attributes(
+        vertex_attributes,
+        vertex_attribute_weights,
+        vertex_attributes_stride,
+        vertices.vertex_count,
+    );
+    let vertex_lock = lock_ptr(vertex_lock, vertices.vertex_count);
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
+    unsafe {
         ffi::meshopt_simplifyWithAttributes(
-            result.as_mut_ptr().cast(),
+            destination.cast(),
             indices.as_ptr().cast(),
             indices.len(),
             positions.cast::<f32>(),
@@ -218,15 +657,49 @@ pub fn simplify_with_attributes_and_locks(
             vertex_attributes_stride,
             vertex_attribute_weights.as_ptr(),
             vertex_attribute_weights.len(),
-            vertex_lock.as_ptr().cast(),
+            vertex_lock,
             target_count,
             target_error,
             options.bits(),
             result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
         )
-    };
-    result.resize(index_count, 0u32);
-    result
+    }
+}
+
+/// Same as `simplify_with_attributes_and_locks`, returning the resulting index buffer
+/// together with the achieved error and reduction ratio.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, or if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_attributes_and_locks_result(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_with_attributes_and_locks(
+        indices,
+        vertices,
+        vertex_attributes,
+        vertex_attribute_weights,
+        vertex_attributes_stride,
+        vertex_lock,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, attempting to preserve mesh
@@ -237,6 +710,14 @@ pub fn simplify_with_attributes_and_locks(
 ///
 /// If the original vertex data isn't required, creating a compact vertex buffer
 /// using `optimize_vertex_fetch` is recommended.
+///
+/// `vertex_lock` may be empty, in which case no vertices are locked.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, or if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`.
 #[allow(clippy::too_many_arguments)]
 pub fn simplify_with_attributes_and_locks_decoder<T: DecodePosition>(
     indices: &[u32],
@@ -250,32 +731,337 @@ pub fn simplify_with_attributes_and_locks_decoder<T: DecodePosition>(
     options: SimplifyOptions,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    let mut result: Vec<u32> = vec![0; indices.len()];
+    vertices.with_positions(|positions| {
+        simplify_with_attributes_and_locks(
+            indices,
+            positions,
+            vertex_attributes,
+            vertex_attribute_weights,
+            vertex_attributes_stride,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            result_error,
+        )
+    })
+}
+
+/// Same as `simplify_with_attributes_and_locks_into`, reading the positions of `vertices`
+/// through `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`, or
+/// if `destination` has room for fewer than `indices.len()` elements.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_attributes_and_locks_decoder_into<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    vertices.with_positions(|positions| {
+        simplify_with_attributes_and_locks_into(
+            indices,
+            positions,
+            vertex_attributes,
+            vertex_attribute_weights,
+            vertex_attributes_stride,
+            vertex_lock,
+            target_count,
+            target_error,
+            options,
+            destination,
+            result_error,
+        )
+    })
+}
+
+/// Same as `simplify_with_attributes_and_locks_result`, reading the positions of `vertices`
+/// through `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `vertex_lock` is neither empty nor has an entry for every vertex, if there are
+/// more than `AttributeLayout::MAX_COMPONENTS` attribute weights, or if `vertex_attributes`
+/// doesn't hold the weighted attributes of every vertex at `vertex_attributes_stride`.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_attributes_and_locks_decoder_result<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_with_attributes_and_locks_decoder(
+        indices,
+        vertices,
+        vertex_attributes,
+        vertex_attribute_weights,
+        vertex_attributes_stride,
+        vertex_lock,
+        target_count,
+        target_error,
+        options,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
+}
+
+/// A single vertex attribute consumed by `simplify_with_attribute_layout`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VertexAttribute {
+    /// Byte offset of the attribute within a vertex.
+    pub offset: usize,
+    /// Number of `f32` components of the attribute.
+    pub count: usize,
+    /// Weight applied to every component of the attribute.
+    pub weight: f32,
+}
+
+/// Checks that `vertex_lock` is either empty (no vertices locked) or has an entry for every
+/// vertex; extra entries are ignored. All simplify entry points follow this rule.
+fn validate_lock(vertex_lock: &[bool], vertex_count: usize) -> Result<()> {
+    if !vertex_lock.is_empty() && vertex_lock.len() < vertex_count {
+        return Err(Error::Config(format!(
+            "vertex_lock contains {} entries, expected 0 or at least {}",
+            vertex_lock.len(),
+            vertex_count
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the lock pointer passed to meshoptimizer, panicking if `vertex_lock` fails
+/// `validate_lock`.
+fn lock_ptr(vertex_lock: &[bool], vertex_count: usize) -> *const u8 {
+    if let Err(err) = validate_lock(vertex_lock, vertex_count) {
+        panic!("{}", err);
+    }
+    if vertex_lock.is_empty() {
+        std::ptr::null()
+    } else {
+        vertex_lock.as_ptr().cast()
+    }
+}
+
+/// Checks that `vertex_attributes` holds `vertex_count` vertices of `vertex_attribute_weights.len()`
+/// attributes each, spaced `vertex_attributes_stride` bytes apart.
+fn validate_attributes(
+    vertex_attributes: &[f32],
+    vertex_attribute_weights: &[f32],
+    vertex_attributes_stride: usize,
+    vertex_count: usize,
+) {
+    let attribute_count = vertex_attribute_weights.len();
+    assert!(
+        attribute_count <= AttributeLayout::MAX_COMPONENTS,
+        "at most {} attributes are supported, got {}",
+        AttributeLayout::MAX_COMPONENTS,
+        attribute_count
+    );
+    if attribute_count == 0 || vertex_count == 0 {
+        return;
+    }
+    assert!(
+        vertex_attributes_stride.is_multiple_of(mem::size_of::<f32>())
+            && vertex_attributes_stride / mem::size_of::<f32>() >= attribute_count,
+        "vertex_attributes_stride ({}) must be a multiple of 4 and hold {} attributes",
+        vertex_attributes_stride,
+        attribute_count
+    );
+    let required =
+        (vertex_count - 1) * (vertex_attributes_stride / mem::size_of::<f32>()) + attribute_count;
+    assert!(
+        vertex_attributes.len() >= required,
+        "vertex_attributes contains {} elements, expected at least {}",
+        vertex_attributes.len(),
+        required
+    );
+}
+
+/// Describes the attributes stored in interleaved vertex data, so that they can be used for
+/// attribute-aware simplification without deinterleaving them into a separate buffer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributeLayout {
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl AttributeLayout {
+    /// Maximum number of attribute components supported by the simplifier.
+    pub const MAX_COMPONENTS: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an attribute of `count` floats at byte `offset`, weighted by `weight`.
+    pub fn with_attribute(mut self, offset: usize, count: usize, weight: f32) -> Self {
+        self.attributes.push(VertexAttribute {
+            offset,
+            count,
+            weight,
+        });
+        self
+    }
+
+    /// Creates a layout from the attributes of `layout` with the given semantics and weights,
+    /// which must be stored as `Float32`.
+    pub fn from_vertex_layout(
+        layout: &VertexLayout,
+        weights: &[(AttributeSemantic, f32)],
+    ) -> Result<Self> {
+        let mut result = Self::new();
+        for &(semantic, weight) in weights {
+            let attribute = layout.require(semantic)?;
+            match attribute.format {
+                AttributeFormat::Float32(count) => {
+                    result = result.with_attribute(attribute.offset, count, weight);
+                }
+                format => {
+                    return Err(Error::Config(format!(
+                        "{:?} attribute format ({:?}) must be Float32 for simplification",
+                        semantic, format
+                    )))
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Total number of `f32` components over all attributes.
+    pub fn component_count(&self) -> usize {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.count)
+            .sum()
+    }
+
+    fn weights(&self) -> Vec<f32> {
+        self.attributes
+            .iter()
+            .flat_map(|attribute| (0..attribute.count).map(move |_| attribute.weight))
+            .collect()
+    }
+
+    /// Whether the attributes are stored back to back, in order, so the interleaved data can be used as is.
+    fn is_contiguous(&self) -> bool {
+        self.attributes
+            .windows(2)
+            .all(|pair| pair[0].offset + pair[0].count * 4 == pair[1].offset)
+    }
+
+    fn validate(&self, vertex_stride: usize) -> Result<()> {
+        for attribute in &self.attributes {
+            let end = attribute
+                .count
+                .checked_mul(4)
+                .and_then(|size| attribute.offset.checked_add(size));
+            if attribute.offset % 4 != 0 || !matches!(end, Some(end) if end <= vertex_stride) {
+                return Err(Error::Config(format!(
+                    "attribute at offset {} with {} components must be 4-byte aligned and fit within vertex_stride ({})",
+                    attribute.offset, attribute.count, vertex_stride
+                )));
+            }
+        }
+        // every attribute fits within the stride, so the sum can't overflow
+        let component_count = self.component_count();
+        if component_count == 0 || component_count > Self::MAX_COMPONENTS {
+            return Err(Error::Config(format!(
+                "attribute component count ({}) must be in 1..={}",
+                component_count,
+                Self::MAX_COMPONENTS
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reduces the number of triangles in the mesh, attempting to preserve mesh
+/// appearance as much as possible, weighing the vertex attributes described by `layout`,
+/// while respecting the given vertex locks
+///
+/// Unlike `simplify_with_attributes_and_locks`, the attributes are read from the same
+/// interleaved vertex data as the positions. When the attributes are stored back to back
+/// in `layout` order, the vertex data is used directly without copying.
+///
+/// `vertex_lock` may be empty, in which case no vertices are locked. Fails if it is shorter
+/// than the vertex count.
+///
+/// The resulting index buffer references vertices from the original vertex buffer.
+///
+/// If the original vertex data isn't required, creating a compact vertex buffer
+/// using `optimize_vertex_fetch` is recommended.
+#[allow(clippy::too_many_arguments)]
+pub fn simplify_with_attribute_layout(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    layout: &AttributeLayout,
+    vertex_lock: &[bool],
+    target_count: usize,
+    target_error: f32,
+    options: SimplifyOptions,
+    result_error: Option<&mut f32>,
+) -> Result<Vec<u32>> {
+    check_indices(indices, vertices.vertex_count);
+    layout.validate(vertices.vertex_stride)?;
+    validate_lock(vertex_lock, vertices.vertex_count)?;
+
+    let weights = layout.weights();
+    let vertex_data = vertices.reader.get_ref();
+    let gathered: Vec<f32>;
+    let (attributes, attributes_stride) = if layout.is_contiguous() {
+        let attributes = unsafe { vertex_data.as_ptr().add(layout.attributes[0].offset) };
+        (attributes.cast::<f32>(), vertices.vertex_stride)
+    } else {
+        gathered = (0..vertices.vertex_count)
+            .flat_map(|vertex| {
+                layout.attributes.iter().flat_map(move |attribute| {
+                    (0..attribute.count)
+                        .map(move |i| vertices.attribute::<1>(vertex, attribute.offset + i * 4)[0])
+                })
+            })
+            .collect();
+        (gathered.as_ptr(), weights.len() * mem::size_of::<f32>())
+    };
+
+    let positions = unsafe { vertex_data.as_ptr().add(vertices.position_offset) };
+    let mut result = UninitBuffer::new(indices.len());
     let index_count = unsafe {
         ffi::meshopt_simplifyWithAttributes(
-            result.as_mut_ptr().cast(),
+            result.as_mut_ptr(),
             indices.as_ptr().cast(),
             indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
-            vertex_attributes.as_ptr(),
-            vertex_attributes_stride,
-            vertex_attribute_weights.as_ptr(),
-            vertex_attribute_weights.len(),
-            vertex_lock.as_ptr().cast(),
+            positions.cast::<f32>(),
+            vertices.vertex_count,
+            vertices.vertex_stride,
+            attributes,
+            attributes_stride,
+            weights.as_ptr(),
+            weights.len(),
+            lock_ptr(vertex_lock, vertices.vertex_count),
             target_count,
             target_error,
             options.bits(),
             result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
         )
     };
-    result.resize(index_count, 0u32);
-    result
+    Ok(unsafe { result.assume_init(index_count) })
 }
 
 /// Reduces the number of triangles in the mesh, sacrificing mesh appearance for simplification performance.
@@ -293,13 +1079,73 @@ pub fn simplify_sloppy(
     target_error: f32,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
+    let mut result = UninitBuffer::new(indices.len());
+    unsafe {
+        let index_count = simplify_sloppy_raw(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            result.as_mut_ptr(),
+            result.len(),
+            result_error,
+        );
+        result.assume_init(index_count)
+    }
+}
+
+/// Same as `simplify_sloppy`, writing the resulting index buffer into `destination` and
+/// returning the number of indices written.
+///
+/// # Panics
+///
+/// Panics if `destination` has room for fewer than `indices.len()` elements.
+pub fn simplify_sloppy_into(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    unsafe {
+        simplify_sloppy_raw(
+            indices,
+            vertices,
+            target_count,
+            target_error,
+            destination.as_mut_ptr(),
+            destination.len(),
+            result_error,
+        )
+    }
+}
+
+/// Same as `simplify_sloppy_into`, writing to `capacity` elements at `destination`.
+///
+/// # Safety
+///
+/// `destination` must be valid for writes of `capacity` elements.
+unsafe fn simplify_sloppy_raw(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    destination: *mut u32,
+    capacity: usize,
+    result_error: Option<&mut f32>,
+) -> usize {
+    check_indices(indices, vertices.vertex_count);
+    assert!(
+        capacity >= indices.len(),
+        "destination must have room for at least indices.len() elements"
+    );
     let vertex_data = vertices.reader.get_ref();
     let vertex_data = vertex_data.as_ptr().cast::<u8>();
     let positions = unsafe { vertex_data.add(vertices.position_offset) };
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
+    unsafe {
         ffi::meshopt_simplifySloppy(
-            result.as_mut_ptr().cast(),
+            destination.cast(),
             indices.as_ptr().cast(),
             indices.len(),
             positions.cast(),
@@ -309,9 +1155,55 @@ pub fn simplify_sloppy(
             target_error,
             result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
         )
+    }
+}
+
+/// Reduces the number of triangles in the mesh like `simplify_sloppy`, writing the
+/// resulting index buffer into `indices` instead of a new buffer.
+pub fn simplify_sloppy_in_place(
+    indices: &mut Vec<u32>,
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+    result_error: Option<&mut f32>,
+) {
+    check_indices(indices, vertices.vertex_count);
+    let vertex_data = vertices.reader.get_ref();
+    let positions = unsafe { vertex_data.as_ptr().add(vertices.position_offset) };
+    let indices_ptr = indices.as_mut_ptr();
+    let index_count = unsafe {
+        ffi::meshopt_simplifySloppy(
+            indices_ptr,
+            indices_ptr,
+            indices.len(),
+            positions.cast(),
+            vertices.vertex_count,
+            vertices.vertex_stride,
+            target_count,
+            target_error,
+            result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
+        )
     };
-    result.resize(index_count, 0u32);
-    result
+    indices.truncate(index_count);
+}
+
+/// Same as `simplify_sloppy`, returning the resulting index buffer together with the
+/// achieved error and reduction ratio.
+pub fn simplify_sloppy_result(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: usize,
+    target_error: f32,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_sloppy(
+        indices,
+        vertices,
+        target_count,
+        target_error,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Reduces the number of triangles in the mesh, sacrificing mesh appearance for simplification performance.
@@ -329,26 +1221,54 @@ pub fn simplify_sloppy_decoder<T: DecodePosition>(
     target_error: f32,
     result_error: Option<&mut f32>,
 ) -> Vec<u32> {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
-    let mut result: Vec<u32> = vec![0; indices.len()];
-    let index_count = unsafe {
-        ffi::meshopt_simplifySloppy(
-            result.as_mut_ptr().cast(),
-            indices.as_ptr().cast(),
-            indices.len(),
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
+    vertices.with_positions(|positions| {
+        simplify_sloppy(indices, positions, target_count, target_error, result_error)
+    })
+}
+
+/// Same as `simplify_sloppy_into`, reading the positions of `vertices` through
+/// `DecodePosition`.
+///
+/// # Panics
+///
+/// Panics if `destination` has room for fewer than `indices.len()` elements.
+pub fn simplify_sloppy_decoder_into<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    target_count: usize,
+    target_error: f32,
+    destination: &mut [u32],
+    result_error: Option<&mut f32>,
+) -> usize {
+    vertices.with_positions(|positions| {
+        simplify_sloppy_into(
+            indices,
+            positions,
             target_count,
             target_error,
-            result_error.map_or_else(std::ptr::null_mut, |v| v as *mut _),
+            destination,
+            result_error,
         )
-    };
-    result.resize(index_count, 0u32);
-    result
+    })
+}
+
+/// Same as `simplify_sloppy_result`, reading the positions of `vertices` through
+/// `DecodePosition`.
+pub fn simplify_sloppy_decoder_result<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    target_count: usize,
+    target_error: f32,
+) -> SimplifyResult {
+    let mut error = 0f32;
+    let result = simplify_sloppy_decoder(
+        indices,
+        vertices,
+        target_count,
+        target_error,
+        Some(&mut error),
+    );
+    SimplifyResult::new(result, indices.len(), error)
 }
 
 /// Returns the error scaling factor used by the simplifier to convert between absolute and relative extents
@@ -370,16 +1290,348 @@ pub fn simplify_scale(vertices: &VertexDataAdapter<'_>) -> f32 {
 /// Absolute error must be *divided* by the scaling factor before passing it to `simplify` as `target_error`
 /// Relative error returned by `simplify` via `result_error` must be *multiplied* by the scaling factor to get absolute error.
 pub fn simplify_scale_decoder<T: DecodePosition>(vertices: &[T]) -> f32 {
-    let positions = vertices
-        .iter()
-        .map(|vertex| vertex.decode_position())
-        .collect::<Vec<[f32; 3]>>();
+    vertices.with_positions(simplify_scale)
+}
 
-    unsafe {
-        ffi::meshopt_simplifyScale(
-            positions.as_ptr().cast(),
-            positions.len(),
-            mem::size_of::<f32>() * 3,
+/// Target size of a simplified mesh.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TargetCount {
+    /// Fraction of the source index count to keep, e.g. 0.5 for half of the triangles.
+    Ratio(f32),
+    /// Absolute number of indices to keep.
+    Indices(usize),
+}
+
+impl TargetCount {
+    /// Returns the target index count for a source mesh with `source_index_count` indices,
+    /// rounded down to whole triangles and clamped to the source index count.
+    pub fn index_count(self, source_index_count: usize) -> usize {
+        let count = match self {
+            TargetCount::Ratio(ratio) => {
+                (source_index_count as f64 * f64::from(ratio.clamp(0f32, 1f32))).round() as usize
+            }
+            TargetCount::Indices(count) => count,
+        };
+        count.min(source_index_count) / 3 * 3
+    }
+}
+
+/// Error that can be tolerated by a simplification.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TargetError {
+    /// Error relative to mesh extents, e.g. 0.01 = 1% deformation; value range [0..1].
+    Relative(f32),
+    /// Error in the same units as the vertex positions.
+    Absolute(f32),
+}
+
+/// Reduces the number of triangles in the mesh, attempting to preserve mesh
+/// appearance as much as possible.
+///
+/// Unlike `simplify`, the target can be expressed as a ratio of the source mesh, and the
+/// error limit can be given in absolute units, in which case it is converted using
+/// `simplify_scale`. The error in the returned `SimplifyResult` uses the same units
+/// as `target_error`.
+///
+/// `SimplifyOptions::ErrorAbsolute` is controlled by `target_error` and is ignored in `options`.
+pub fn simplify_with_target(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: TargetCount,
+    target_error: TargetError,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let (target_error, options, scale) =
+        resolve_target_error(target_error, options, || simplify_scale(vertices));
+    let mut result = simplify_result(
+        indices,
+        vertices,
+        target_count.index_count(indices.len()),
+        target_error,
+        options,
+    );
+    result.error *= scale;
+    result
+}
+
+/// Reduces the number of triangles in the mesh, attempting to preserve mesh
+/// appearance as much as possible.
+///
+/// Unlike `simplify_decoder`, the target can be expressed as a ratio of the source mesh, and
+/// the error limit can be given in absolute units, in which case it is converted using
+/// `simplify_scale_decoder`. The error in the returned `SimplifyResult` uses the same units
+/// as `target_error`.
+///
+/// `SimplifyOptions::ErrorAbsolute` is controlled by `target_error` and is ignored in `options`.
+pub fn simplify_with_target_decoder<T: DecodePosition>(
+    indices: &[u32],
+    vertices: &[T],
+    target_count: TargetCount,
+    target_error: TargetError,
+    options: SimplifyOptions,
+) -> SimplifyResult {
+    let (target_error, options, scale) =
+        resolve_target_error(target_error, options, || simplify_scale_decoder(vertices));
+    let mut result = simplify_decoder_result(
+        indices,
+        vertices,
+        target_count.index_count(indices.len()),
+        target_error,
+        options,
+    );
+    result.error *= scale;
+    result
+}
+
+/// Simplification path used by `simplify_to_target`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SimplifyMethod {
+    /// The target was reached by the regular, topology preserving simplifier.
+    Regular,
+    /// The regular simplifier fell short of the target, and its result was further reduced
+    /// by `simplify_sloppy`.
+    Sloppy,
+}
+
+/// Reduces the number of triangles in the mesh until the target index count is reached.
+///
+/// This first runs `simplify_with_target`; if that can't reach the target index count,
+/// e.g. because of topology constraints or `SimplifyOptions::LockBorder`, the remainder is
+/// reduced with `simplify_sloppy`, which doesn't preserve topology and isn't limited by
+/// `target_error`. The returned method reports which path produced the result, and the
+/// error is the larger of the two passes, in the same units as `target_error`.
+pub fn simplify_to_target(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    target_count: TargetCount,
+    target_error: TargetError,
+    options: SimplifyOptions,
+) -> (SimplifyResult, SimplifyMethod) {
+    let target_index_count = target_count.index_count(indices.len());
+    let result = simplify_with_target(
+        indices,
+        vertices,
+        TargetCount::Indices(target_index_count),
+        target_error,
+        options,
+    );
+    if result.indices.len() <= target_index_count {
+        return (result, SimplifyMethod::Regular);
+    }
+
+    let sloppy = simplify_sloppy_result(&result.indices, vertices, target_index_count, f32::MAX);
+    let sloppy_error = match target_error {
+        TargetError::Relative(_) => sloppy.error,
+        TargetError::Absolute(_) => sloppy.error * simplify_scale(vertices),
+    };
+    (
+        SimplifyResult::new(
+            sloppy.indices,
+            indices.len(),
+            result.error.max(sloppy_error),
+        ),
+        SimplifyMethod::Sloppy,
+    )
+}
+
+/// Converts `target_error` into the error passed to the simplifier, returning the adjusted
+/// options and the factor converting the resulting error back into the requested units.
+fn resolve_target_error(
+    target_error: TargetError,
+    options: SimplifyOptions,
+    scale: impl FnOnce() -> f32,
+) -> (f32, SimplifyOptions, f32) {
+    let options = options - SimplifyOptions::ErrorAbsolute;
+    match target_error {
+        TargetError::Relative(error) => (error, options, 1f32),
+        // with sparse input, relative error is measured against the subset extents,
+        // so let the simplifier handle absolute errors natively
+        TargetError::Absolute(error) if options.contains(SimplifyOptions::Sparse) => {
+            (error, options | SimplifyOptions::ErrorAbsolute, 1f32)
+        }
+        TargetError::Absolute(error) => {
+            let scale = scale();
+            let relative = if scale > 0f32 { error / scale } else { 0f32 };
+            (relative, options, scale)
+        }
+    }
+}
+
+/// Classification of a vertex by the simplifier, see `classify_vertices`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VertexKind {
+    /// Interior vertex of a manifold surface; can be collapsed freely.
+    Manifold,
+    /// Vertex on the topological border of the mesh; can only be collapsed along the border.
+    Border,
+    /// Vertex on an attribute seam (two vertices sharing a position); can only be collapsed along the seam.
+    Seam,
+    /// Vertex with non-manifold topology or more than two attribute wedges; never collapsed.
+    Complex,
+    /// Vertex locked through `vertex_lock` or `SimplifyOptions::LockBorder`; never collapsed.
+    Locked,
+}
+
+/// Classifies every vertex the way the simplifier does, which explains why some regions of a
+/// mesh refuse to simplify, e.g. for visualization in tools.
+///
+/// Vertices with bitwise equal positions are treated as attribute wedges of the same
+/// position. `vertex_lock` may be empty; otherwise it must have an entry for every vertex.
+/// Only `SimplifyOptions::LockBorder` affects the result.
+///
+/// meshoptimizer doesn't expose its classification, so this is a reimplementation of the same
+/// rules in Rust and is meant as a diagnostic: the result can diverge from what `simplify`
+/// actually does. For example, positions of `0.0` and `-0.0` are treated as equal here but
+/// not by the native simplifier.
+///
+/// Fails if an index is out of range or `vertex_lock` is too short.
+pub fn classify_vertices(
+    indices: &[u32],
+    vertices: &VertexDataAdapter<'_>,
+    vertex_lock: &[bool],
+    options: SimplifyOptions,
+) -> Result<Vec<VertexKind>> {
+    let vertex_count = vertices.vertex_count;
+    validate_indices(indices, vertex_count)?;
+    validate_lock(vertex_lock, vertex_count)?;
+
+    // map every vertex to the first vertex with the same position
+    let mut first: HashMap<[u32; 3], u32> = HashMap::new();
+    let remap: Vec<u32> = (0..vertex_count)
+        .map(|vertex| {
+            let key = vertices
+                .position(vertex)
+                .map(|v| if v == 0f32 { 0 } else { v.to_bits() });
+            *first.entry(key).or_insert(vertex as u32)
+        })
+        .collect();
+
+    let mut referenced = vec![false; vertex_count];
+    let mut wedges = vec![0u32; vertex_count];
+    for &index in indices {
+        let index = index as usize;
+        if !referenced[index] {
+            referenced[index] = true;
+            wedges[remap[index] as usize] += 1;
+        }
+    }
+
+    let half_edges = |map: &dyn Fn(u32) -> u32| -> Vec<(u32, u32)> {
+        indices
+            .chunks_exact(3)
+            .flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .map(|(a, b)| (map(a), map(b)))
+            .collect()
+    };
+    let count_open = |edges: &[(u32, u32)]| -> (Vec<u32>, Vec<u32>) {
+        let lookup: HashSet<(u32, u32)> = edges.iter().copied().collect();
+        let mut open_out = vec![0u32; vertex_count];
+        let mut open_in = vec![0u32; vertex_count];
+        for &(a, b) in edges {
+            if !lookup.contains(&(b, a)) {
+                open_out[a as usize] += 1;
+                open_in[b as usize] += 1;
+            }
+        }
+        (open_out, open_in)
+    };
+
+    let (open_out, open_in) = count_open(&half_edges(&|v| v));
+    let (position_open_out, position_open_in) = count_open(&half_edges(&|v| remap[v as usize]));
+
+    Ok((0..vertex_count)
+        .map(|vertex| {
+            let position = remap[vertex] as usize;
+            let single_open = |out: &[u32], inc: &[u32], v: usize| out[v] == 1 && inc[v] == 1;
+
+            let kind = if !vertex_lock.is_empty() && vertex_lock[vertex] {
+                VertexKind::Locked
+            } else if !referenced[vertex] || wedges[position] == 1 {
+                if open_out[vertex] == 0 && open_in[vertex] == 0 {
+                    VertexKind::Manifold
+                } else if single_open(&open_out, &open_in, vertex) {
+                    VertexKind::Border
+                } else {
+                    VertexKind::Complex
+                }
+            } else if wedges[position] == 2
+                && position_open_out[position] == 0
+                && position_open_in[position] == 0
+                && single_open(&open_out, &open_in, vertex)
+            {
+                VertexKind::Seam
+            } else {
+                VertexKind::Complex
+            };
+
+            if kind == VertexKind::Border && options.contains(SimplifyOptions::LockBorder) {
+                VertexKind::Locked
+            } else {
+                kind
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        classify_vertices, typed_to_bytes, SimplifyOptions, TargetCount, VertexDataAdapter,
+        VertexKind,
+    };
+
+    #[test]
+    fn test_target_count_index_count() {
+        assert_eq!(TargetCount::Ratio(0.5).index_count(300), 150);
+        assert_eq!(TargetCount::Ratio(0.7).index_count(30), 21);
+        assert_eq!(TargetCount::Ratio(0.1).index_count(20), 0);
+        assert_eq!(TargetCount::Ratio(2.0).index_count(30), 30);
+        assert_eq!(TargetCount::Indices(100).index_count(300), 99);
+        assert_eq!(TargetCount::Indices(1000).index_count(300), 300);
+    }
+
+    #[test]
+    fn test_classify_vertices() {
+        // 3x3 grid of quads, with the right column split off by a UV seam at x = 1
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                positions.push([x as f32, y as f32, 0.0]);
+            }
+        }
+        for y in 0..3 {
+            positions.push([1.0, y as f32, 0.0]);
+        }
+        let mut indices: Vec<u32> = Vec::new();
+        for y in 0..2u32 {
+            for x in 0..2u32 {
+                let i = y * 3 + x;
+                let (a, c) = if x == 1 {
+                    (9 + y, 9 + y + 1)
+                } else {
+                    (i, i + 3)
+                };
+                indices.extend_from_slice(&[a, i + 1, c, c, i + 1, i + 4]);
+            }
+        }
+
+        let vertices = VertexDataAdapter::new(
+            typed_to_bytes(&positions),
+            std::mem::size_of::<[f32; 3]>(),
+            0,
         )
+        .unwrap();
+        let kinds = classify_vertices(&indices, &vertices, &[], SimplifyOptions::None).unwrap();
+        assert_eq!(kinds[0], VertexKind::Border);
+        assert_eq!(kinds[4], VertexKind::Seam);
+        assert_eq!(kinds[10], VertexKind::Seam);
+        assert_eq!(kinds[2], VertexKind::Border);
+        // the seam meets the border
+        assert_eq!(kinds[1], VertexKind::Complex);
+
+        let kinds =
+            classify_vertices(&indices, &vertices, &[], SimplifyOptions::LockBorder).unwrap();
+        assert_eq!(kinds[0], VertexKind::Locked);
+        assert_eq!(kinds[4], VertexKind::Seam);
     }
 }
@@ -0,0 +1,193 @@
+//! A common interface for mesh data coming from loaders, so that gltf, obj, ply and
+//! engine-native meshes can be fed to the higher-level pipelines the same way.
+
+use crate::{
+    build_meshlets, generate_lod_chain, generate_vertex_remap_multi, optimize_overdraw_in_place,
+    optimize_vertex_cache_in_place, optimize_vertex_fetch_remap, remap_index_buffer,
+    remap_vertex_buffer, typed_to_bytes, Error, LodChain, LodConfig, Meshlets, OptimizeConfig,
+    Result, VertexDataAdapter, VertexStream,
+};
+use std::borrow::Cow;
+use std::mem;
+use std::ops::Range;
+
+/// Triangle list mesh data consumed by `optimize_source`, `generate_lod_chain_source` and
+/// `build_meshlets_source`.
+pub trait MeshSource {
+    /// Triangle list indices of all submeshes.
+    fn indices(&self) -> Cow<'_, [u32]>;
+
+    /// Returns an adapter reading the vertex positions.
+    fn positions(&self) -> VertexDataAdapter<'_>;
+
+    /// Returns a stream for every vertex attribute besides the position, which are compared
+    /// when removing duplicate vertices. Defaults to none, i.e. vertices only have positions.
+    fn attribute_streams(&self) -> Vec<VertexStream<'_>> {
+        Vec::new()
+    }
+
+    /// Returns the ranges of the index buffer drawn as separate submeshes. Defaults to a
+    /// single range covering all indices.
+    #[allow(clippy::single_range_in_vec_init)]
+    fn submeshes(&self) -> Vec<Range<usize>> {
+        vec![0..self.indices().len()]
+    }
+}
+
+/// Result of `optimize_source`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceOptimization {
+    /// Optimized indices, with the same submesh ranges as the source.
+    pub indices: Vec<u32>,
+    /// Maps source vertices to optimized vertices, for `remap_vertex_buffer` or
+    /// `remap_vertex_buffers`; unreferenced vertices are marked with `!0`.
+    pub vertex_remap: Vec<u32>,
+    /// Number of vertices after optimization.
+    pub vertex_count: usize,
+}
+
+/// Runs the complete optimization pipeline on `source`: removes duplicate and unreferenced
+/// vertices (comparing positions and attribute streams), optimizes each submesh for vertex
+/// cache and overdraw, then optimizes the whole vertex buffer for vertex fetch.
+///
+/// The vertex buffers of the source are left untouched; apply `vertex_remap` to them with
+/// `vertex_count` as the new vertex count.
+///
+/// Fails if an index or submesh range is out of bounds, or a submesh isn't made of whole
+/// triangles.
+pub fn optimize_source<S: MeshSource + ?Sized>(
+    source: &S,
+    config: &OptimizeConfig,
+) -> Result<SourceOptimization> {
+    let indices = source.indices();
+    let positions = source.positions();
+    let submeshes = source.submeshes();
+    validate_source(&indices, positions.vertex_count, &submeshes)?;
+
+    let vertex_count = positions.vertex_count;
+    let position_data = &positions.reader.get_ref()[positions.position_offset..];
+    let mut streams = vec![VertexStream::from_bytes(
+        position_data,
+        positions.vertex_stride,
+        mem::size_of::<[f32; 3]>(),
+    )];
+    streams.extend(source.attribute_streams());
+
+    let (unique_count, unique_remap) =
+        generate_vertex_remap_multi(vertex_count, &streams, Some(&indices));
+    let mut unique_indices = remap_index_buffer(Some(&indices), vertex_count, &unique_remap);
+    let unique_positions = remap_vertex_buffer(
+        &positions.iter_positions().collect::<Vec<_>>(),
+        unique_count,
+        &unique_remap,
+    );
+    let unique_adapter = VertexDataAdapter::new(
+        typed_to_bytes(&unique_positions),
+        mem::size_of::<[f32; 3]>(),
+        0,
+    )?;
+
+    // vertex cache optimization should go first as it provides starting order for overdraw
+    for range in submeshes {
+        let submesh = &mut unique_indices[range];
+        optimize_vertex_cache_in_place(submesh, unique_count);
+        optimize_overdraw_in_place(submesh, &unique_adapter, config.overdraw_threshold);
+    }
+
+    // vertex fetch optimization should go last as it depends on the final index order;
+    // every unique vertex is referenced, so the remap covers all of them
+    let fetch_remap = optimize_vertex_fetch_remap(&unique_indices, unique_count);
+    let indices = remap_index_buffer(Some(&unique_indices), unique_count, &fetch_remap);
+    let vertex_remap = unique_remap
+        .iter()
+        .map(|&vertex| {
+            if vertex == !0 {
+                !0
+            } else {
+                fetch_remap[vertex as usize]
+            }
+        })
+        .collect();
+
+    Ok(SourceOptimization {
+        indices,
+        vertex_remap,
+        vertex_count: unique_count,
+    })
+}
+
+/// Generates a LOD chain for every submesh of `source`, see `generate_lod_chain`.
+///
+/// Fails if an index or submesh range is out of bounds, or a submesh isn't made of whole
+/// triangles.
+pub fn generate_lod_chain_source<S: MeshSource + ?Sized>(
+    source: &S,
+    config: &LodConfig,
+) -> Result<Vec<LodChain>> {
+    let indices = source.indices();
+    let positions = source.positions();
+    let submeshes = source.submeshes();
+    validate_source(&indices, positions.vertex_count, &submeshes)?;
+
+    Ok(submeshes
+        .into_iter()
+        .map(|range| generate_lod_chain(&indices[range], &positions, config))
+        .collect())
+}
+
+/// Splits every submesh of `source` into meshlets, see `build_meshlets`.
+///
+/// Fails if an index or submesh range is out of bounds, a submesh isn't made of whole
+/// triangles, or the meshlet parameters are invalid.
+pub fn build_meshlets_source<S: MeshSource + ?Sized>(
+    source: &S,
+    max_vertices: usize,
+    max_triangles: usize,
+    cone_weight: f32,
+) -> Result<Vec<Meshlets>> {
+    let indices = source.indices();
+    let positions = source.positions();
+    let submeshes = source.submeshes();
+    validate_source(&indices, positions.vertex_count, &submeshes)?;
+
+    submeshes
+        .into_iter()
+        .map(|range| {
+            build_meshlets(
+                &indices[range],
+                &positions,
+                max_vertices,
+                max_triangles,
+                cone_weight,
+            )
+        })
+        .collect()
+}
+
+fn validate_source(indices: &[u32], vertex_count: usize, submeshes: &[Range<usize>]) -> Result<()> {
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(Error::Parse(format!(
+            "index {} is out of range for {} vertices",
+            index, vertex_count
+        )));
+    }
+    for range in submeshes {
+        if range.start > range.end || range.end > indices.len() {
+            return Err(Error::Config(format!(
+                "submesh range {:?} is out of bounds for {} indices",
+                range,
+                indices.len()
+            )));
+        }
+        if range.start % 3 != 0 || range.len() % 3 != 0 {
+            return Err(Error::Config(format!(
+                "submesh range {:?} must contain whole triangles",
+                range
+            )));
+        }
+    }
+    Ok(())
+}
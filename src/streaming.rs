@@ -0,0 +1,260 @@
+//! Streaming encoding and decoding of index and vertex buffers through `io::Write` and
+//! `io::Read`.
+//!
+//! The codecs can't decode a buffer before all of it is available, so streams consist of
+//! independently encoded chunks instead. Each chunk is framed by its element count and
+//! encoded size (little-endian `u32` values), after a header holding the element size.
+//! Readers decode every chunk as soon as it has been read, which lets loaders overlap
+//! downloading and decoding.
+
+use crate::{
+    decode_index_buffer_checked, decode_vertex_buffer_checked, encode_index_buffer,
+    encode_vertex_buffer, Error, Result,
+};
+use std::io::{self, Read, Write};
+use std::mem;
+
+/// Writes a vertex buffer as a stream of independently encoded chunks, see
+/// `ChunkedVertexReader`.
+#[derive(Debug)]
+pub struct ChunkedVertexWriter<W: Write> {
+    writer: W,
+    vertex_size: usize,
+}
+
+impl<W: Write> ChunkedVertexWriter<W> {
+    /// Starts a stream of vertices of `vertex_size` bytes, writing its header.
+    pub fn new(mut writer: W, vertex_size: usize) -> Result<Self> {
+        write_u32(&mut writer, vertex_size)?;
+        Ok(Self {
+            writer,
+            vertex_size,
+        })
+    }
+
+    /// Encodes `vertices` as the next chunk of the stream.
+    pub fn write_chunk<T>(&mut self, vertices: &[T]) -> Result<()> {
+        check_element_size::<T>(self.vertex_size)?;
+        let encoded = encode_vertex_buffer(vertices)?;
+        write_frame(&mut self.writer, vertices.len(), &encoded)
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads and decodes a vertex buffer written by `ChunkedVertexWriter` chunk by chunk.
+///
+/// The decoder is safe to use for untrusted input; chunks claiming more vertices than
+/// their encoded size can hold are rejected before allocating, see
+/// `decode_vertex_buffer_checked`.
+#[derive(Debug)]
+pub struct ChunkedVertexReader<R: Read> {
+    reader: R,
+    vertex_size: usize,
+    encoded: Vec<u8>,
+}
+
+impl<R: Read> ChunkedVertexReader<R> {
+    /// Starts reading a stream, reading its header.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let vertex_size = read_header(&mut reader)?;
+        Ok(Self {
+            reader,
+            vertex_size,
+            encoded: Vec::new(),
+        })
+    }
+
+    /// Size of the vertices of the stream in bytes.
+    pub fn vertex_size(&self) -> usize {
+        self.vertex_size
+    }
+
+    /// Reads and decodes the next chunk, returning `None` at the end of the stream.
+    pub fn read_chunk<T: Clone + Default>(&mut self) -> Result<Option<Vec<T>>> {
+        check_element_size::<T>(self.vertex_size)?;
+        match read_frame(&mut self.reader, &mut self.encoded)? {
+            Some(vertex_count) => {
+                decode_vertex_buffer_checked(&self.encoded, vertex_count).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Writes an index buffer as a stream of independently encoded chunks, see
+/// `ChunkedIndexReader`.
+///
+/// Chunks consist of whole triangles, and every chunk is encoded with `encode_index_buffer`.
+#[derive(Debug)]
+pub struct ChunkedIndexWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ChunkedIndexWriter<W> {
+    /// Starts a stream of indices, writing its header.
+    pub fn new(mut writer: W) -> Result<Self> {
+        write_u32(&mut writer, mem::size_of::<u32>())?;
+        Ok(Self { writer })
+    }
+
+    /// Encodes `indices`, referencing `vertex_count` vertices, as the next chunk of the
+    /// stream.
+    pub fn write_chunk(&mut self, indices: &[u32], vertex_count: usize) -> Result<()> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(Error::Config(format!(
+                "index count ({}) must be a multiple of 3",
+                indices.len()
+            )));
+        }
+        let encoded = encode_index_buffer(indices, vertex_count)?;
+        write_frame(&mut self.writer, indices.len(), &encoded)
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads and decodes an index buffer written by `ChunkedIndexWriter` chunk by chunk.
+///
+/// The decoder is safe to use for untrusted input, see `decode_index_buffer_checked`.
+#[derive(Debug)]
+pub struct ChunkedIndexReader<R: Read> {
+    reader: R,
+    encoded: Vec<u8>,
+}
+
+impl<R: Read> ChunkedIndexReader<R> {
+    /// Starts reading a stream, reading its header.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let index_size = read_header(&mut reader)?;
+        if index_size != mem::size_of::<u32>() {
+            return Err(Error::Parse(format!(
+                "index stream has indices of {} bytes, expected 4",
+                index_size
+            )));
+        }
+        Ok(Self {
+            reader,
+            encoded: Vec::new(),
+        })
+    }
+
+    /// Reads and decodes the next chunk, returning `None` at the end of the stream.
+    ///
+    /// Indices are always decoded as `u32`, like they are written; use
+    /// `convert_indices_32_to_16` to narrow them.
+    pub fn read_chunk(&mut self) -> Result<Option<Vec<u32>>> {
+        match read_frame(&mut self.reader, &mut self.encoded)? {
+            Some(index_count) => decode_index_buffer_checked(&self.encoded, index_count).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+fn check_element_size<T>(element_size: usize) -> Result<()> {
+    if mem::size_of::<T>() != element_size {
+        return Err(Error::Config(format!(
+            "element size of the stream ({}) doesn't match the size of the type ({})",
+            element_size,
+            mem::size_of::<T>()
+        )));
+    }
+    Ok(())
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: usize) -> Result<()> {
+    let value = u32::try_from(value)
+        .map_err(|err| Error::Config(format!("{} doesn't fit in a frame: {}", value, err)))?;
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_frame<W: Write>(writer: &mut W, count: usize, encoded: &[u8]) -> Result<()> {
+    write_u32(writer, count)?;
+    write_u32(writer, encoded.len())?;
+    writer.write_all(encoded)?;
+    Ok(())
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<usize> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+    Ok(u32::from_le_bytes(header) as usize)
+}
+
+/// Reads the next frame into `encoded`, returning its element count, or `None` if the
+/// stream ends before the frame.
+fn read_frame<R: Read>(reader: &mut R, encoded: &mut Vec<u8>) -> Result<Option<usize>> {
+    let mut frame = [0u8; 8];
+    let mut filled = 0;
+    while filled < frame.len() {
+        match reader.read(&mut frame[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let count = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+    let size = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]) as usize;
+
+    // grow the buffer as data arrives rather than trusting the size up front
+    encoded.clear();
+    reader.take(size as u64).read_to_end(encoded)?;
+    if encoded.len() != size {
+        return Err(Error::Parse(format!(
+            "chunk of {} bytes ends after {} bytes",
+            size,
+            encoded.len()
+        )));
+    }
+    Ok(Some(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChunkedIndexReader, ChunkedIndexWriter, ChunkedVertexReader, ChunkedVertexWriter};
+
+    #[test]
+    fn test_chunked_round_trip() {
+        let vertices: Vec<[f32; 3]> = (0..64).map(|i| [i as f32, 0f32, 1f32]).collect();
+        let indices: Vec<u32> = (0..62).flat_map(|i| [i, i + 1, i + 2]).collect();
+
+        let mut writer = ChunkedVertexWriter::new(Vec::new(), 12).unwrap();
+        writer.write_chunk(&vertices[..40]).unwrap();
+        writer.write_chunk(&vertices[40..]).unwrap();
+        let vertex_stream = writer.into_inner();
+
+        let mut reader = ChunkedVertexReader::new(vertex_stream.as_slice()).unwrap();
+        let mut decoded: Vec<[f32; 3]> = Vec::new();
+        while let Some(chunk) = reader.read_chunk::<[f32; 3]>().unwrap() {
+            decoded.extend(chunk);
+        }
+        assert_eq!(decoded, vertices);
+
+        let mut writer = ChunkedIndexWriter::new(Vec::new()).unwrap();
+        writer.write_chunk(&indices[..90], 64).unwrap();
+        writer.write_chunk(&indices[90..], 64).unwrap();
+        let index_stream = writer.into_inner();
+
+        let mut reader = ChunkedIndexReader::new(index_stream.as_slice()).unwrap();
+        let mut decoded: Vec<u32> = Vec::new();
+        while let Some(chunk) = reader.read_chunk().unwrap() {
+            decoded.extend(chunk);
+        }
+        assert_eq!(decoded, indices);
+
+        // a stream cut off in the middle of a chunk is an error, not the end of the stream
+        let truncated = &index_stream[..index_stream.len() - 1];
+        let mut reader = ChunkedIndexReader::new(truncated).unwrap();
+        assert!(reader.read_chunk().is_ok());
+        assert!(reader.read_chunk().is_err());
+    }
+}
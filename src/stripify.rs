@@ -1,4 +1,14 @@
-use crate::{ffi, Error, Result};
+use crate::{ffi, Error, Index, Result};
+
+/// Returns the worst case number of indices `stripify` can produce for `index_count` indices.
+pub fn stripify_bound(index_count: usize) -> usize {
+    unsafe { ffi::meshopt_stripifyBound(index_count) }
+}
+
+/// Returns the worst case number of indices `unstripify` can produce for `index_count` indices.
+pub fn unstripify_bound(index_count: usize) -> usize {
+    unsafe { ffi::meshopt_unstripifyBound(index_count) }
+}
 
 /// Converts a previously vertex cache optimized triangle list to triangle
 /// strip, stitching strips using restart index.
@@ -8,40 +18,194 @@ use crate::{ffi, Error, Result};
 ///
 /// The `restart_index` should be 0xffff or 0xffffffff depending on index size,
 /// or 0 to use degenerate triangles.
-pub fn stripify(indices: &[u32], vertex_count: usize, restart_index: u32) -> Result<Vec<u32>> {
-    let mut result: Vec<u32> = vec![0; indices.len() / 3 * 4];
-    let index_count = unsafe {
+///
+/// Accepts both `u16` and `u32` indices, as does `unstripify`.
+pub fn stripify<T: Index>(indices: &[T], vertex_count: usize, restart_index: T) -> Result<Vec<T>> {
+    let mut result: Vec<T> = vec![T::default(); stripify_bound(indices.len())];
+    let index_count = stripify_into(indices, vertex_count, restart_index, &mut result)?;
+    result.truncate(index_count);
+    Ok(result)
+}
+
+/// Converts a previously vertex cache optimized triangle list to triangle strip like `stripify`,
+/// writing the strip to `destination` and returning its length.
+///
+/// Returns an error if `destination` is smaller than `stripify_bound(indices.len())`.
+pub fn stripify_into<T: Index>(
+    indices: &[T],
+    vertex_count: usize,
+    restart_index: T,
+    destination: &mut [T],
+) -> Result<usize> {
+    let bound = stripify_bound(indices.len());
+    if destination.len() < bound {
+        return Err(Error::memory_dynamic(format!(
+            "destination holds {} indices, stripify may produce up to {}",
+            destination.len(),
+            bound
+        )));
+    }
+    let indices = T::to_u32_slice(indices);
+    let restart_index = restart_index.to_u32();
+    let index_count = T::with_u32_slice_mut(destination, |destination| unsafe {
         ffi::meshopt_stripify(
-            result.as_mut_ptr().cast(),
-            indices.as_ptr().cast(),
+            destination.as_mut_ptr(),
+            indices.as_ptr(),
             indices.len(),
             vertex_count,
             restart_index,
         )
-    };
-    if index_count <= result.len() {
-        result.resize(index_count, 0u32);
-        Ok(result)
-    } else {
-        Err(Error::memory("index count is larger than result"))
-    }
+    });
+    Ok(index_count)
 }
 
 /// Converts a triangle strip to a triangle list
-pub fn unstripify(indices: &[u32], restart_index: u32) -> Result<Vec<u32>> {
-    let mut result: Vec<u32> = vec![0; (indices.len() - 2) * 3];
-    let index_count = unsafe {
+pub fn unstripify<T: Index>(indices: &[T], restart_index: T) -> Result<Vec<T>> {
+    let mut result: Vec<T> = vec![T::default(); unstripify_bound(indices.len())];
+    let index_count = unstripify_into(indices, restart_index, &mut result)?;
+    result.truncate(index_count);
+    Ok(result)
+}
+
+/// Converts a triangle strip to a triangle list like `unstripify`, writing the list to
+/// `destination` and returning its length.
+///
+/// Returns an error if `destination` is smaller than `unstripify_bound(indices.len())`.
+pub fn unstripify_into<T: Index>(
+    indices: &[T],
+    restart_index: T,
+    destination: &mut [T],
+) -> Result<usize> {
+    let bound = unstripify_bound(indices.len());
+    if destination.len() < bound {
+        return Err(Error::memory_dynamic(format!(
+            "destination holds {} indices, unstripify may produce up to {}",
+            destination.len(),
+            bound
+        )));
+    }
+    let indices = T::to_u32_slice(indices);
+    let restart_index = restart_index.to_u32();
+    let index_count = T::with_u32_slice_mut(destination, |destination| unsafe {
         ffi::meshopt_unstripify(
-            result.as_mut_ptr().cast(),
-            indices.as_ptr().cast(),
+            destination.as_mut_ptr(),
+            indices.as_ptr(),
             indices.len(),
             restart_index,
         )
+    });
+    Ok(index_count)
+}
+
+/// How strips are stitched together by `stripify_with_restart`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RestartMode {
+    /// Stitch strips using degenerate triangles.
+    Degenerate,
+    /// Stitch strips using a primitive restart index, typically 0xffff or 0xffffffff
+    /// depending on index size.
+    Index(u32),
+}
+
+impl RestartMode {
+    /// Returns the restart index passed to meshoptimizer, 0 for degenerate triangles.
+    fn restart_index<T: Index>(self) -> Result<u32> {
+        match self {
+            RestartMode::Degenerate => Ok(0),
+            RestartMode::Index(0) => Err(Error::Config(
+                "restart index 0 is reserved, use RestartMode::Degenerate instead".to_string(),
+            )),
+            RestartMode::Index(index) => {
+                if std::mem::size_of::<T>() == 2 && index > u32::from(u16::MAX) {
+                    Err(Error::Config(format!(
+                        "restart index ({:#x}) doesn't fit in 16-bit indices",
+                        index
+                    )))
+                } else {
+                    Ok(index)
+                }
+            }
+        }
+    }
+}
+
+/// Converts a previously vertex cache optimized triangle list to triangle
+/// strip, stitching strips as specified by `mode`.
+///
+/// For maximum efficiency the index buffer being converted has to be
+/// optimized for vertex cache first.
+///
+/// Returns an error if the restart index of `mode` is 0 or doesn't fit in `T`.
+pub fn stripify_with_restart<T: Index>(
+    indices: &[T],
+    vertex_count: usize,
+    mode: RestartMode,
+) -> Result<Vec<T>> {
+    let restart_index = mode.restart_index::<T>()?;
+    stripify(indices, vertex_count, T::from_u32(restart_index))
+}
+
+/// Converts a triangle strip produced by `stripify_with_restart` with the same `mode` to a
+/// triangle list.
+pub fn unstripify_with_restart<T: Index>(indices: &[T], mode: RestartMode) -> Result<Vec<T>> {
+    let restart_index = mode.restart_index::<T>()?;
+    unstripify(indices, T::from_u32(restart_index))
+}
+
+/// Aggregate statistics of the strips returned by `stripify_strips`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct StripStatistics {
+    pub strip_count: usize,
+    /// Average number of indices per strip.
+    pub average_length: f32,
+    /// Fraction of the indices that would be restart indices if the strips were stitched
+    /// with primitive restart.
+    pub restart_overhead: f32,
+}
+
+/// Individual triangle strips, as returned by `stripify_strips`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Strips<T> {
+    /// Indices of all strips, without restart indices between them.
+    pub indices: Vec<T>,
+    /// Range of each strip in `indices`.
+    pub strips: Vec<std::ops::Range<usize>>,
+    pub statistics: StripStatistics,
+}
+
+/// Converts a previously vertex cache optimized triangle list to individual triangle strips,
+/// for renderers that issue one draw per strip.
+///
+/// For maximum efficiency the index buffer being converted has to be
+/// optimized for vertex cache first.
+pub fn stripify_strips<T: Index>(indices: &[T], vertex_count: usize) -> Result<Strips<T>> {
+    const RESTART_INDEX: u32 = !0;
+
+    let stitched = stripify(&T::to_u32_slice(indices), vertex_count, RESTART_INDEX)?;
+    let mut result = Strips {
+        indices: Vec::with_capacity(stitched.len()),
+        strips: Vec::new(),
+        statistics: StripStatistics::default(),
     };
-    if index_count <= result.len() {
-        result.resize(index_count, 0u32);
-        Ok(result)
-    } else {
-        Err(Error::memory("index count is larger than result"))
+    for strip in stitched.split(|&index| index == RESTART_INDEX) {
+        if strip.is_empty() {
+            continue;
+        }
+        let start = result.indices.len();
+        result
+            .indices
+            .extend(strip.iter().map(|&index| T::from_u32(index)));
+        result.strips.push(start..result.indices.len());
+    }
+
+    let strip_count = result.strips.len();
+    if strip_count > 0 {
+        let restart_count = strip_count - 1;
+        result.statistics = StripStatistics {
+            strip_count,
+            average_length: result.indices.len() as f32 / strip_count as f32,
+            restart_overhead: restart_count as f32 / (result.indices.len() + restart_count) as f32,
+        };
     }
+    Ok(result)
 }
@@ -1,16 +1,146 @@
 use crate::{Error, Result};
+use std::borrow::Cow;
 use std::io::{Cursor, Read};
+use std::mem::MaybeUninit;
 
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// Index buffer element type, implemented for `u16` and `u32`.
+///
+/// meshoptimizer works on 32-bit indices; 16-bit index buffers are widened where needed,
+/// while 32-bit index buffers are passed through without copying.
+pub trait Index: Copy + Default + Eq + std::fmt::Debug + private::Sealed + 'static {
+    /// Converts a 32-bit index to this type; the value must be in range.
+    fn from_u32(index: u32) -> Self;
+
+    fn to_u32(self) -> u32;
+
+    /// Returns the indices as 32-bit indices, borrowing them if they already are.
+    fn to_u32_slice(indices: &[Self]) -> Cow<'_, [u32]>;
+
+    /// Runs `f` on the indices as 32-bit indices and stores the modified indices back,
+    /// converting them if needed.
+    fn with_u32_slice_mut<R>(indices: &mut [Self], f: impl FnOnce(&mut [u32]) -> R) -> R;
+}
+
+impl Index for u16 {
+    #[inline(always)]
+    fn from_u32(index: u32) -> Self {
+        debug_assert!(u16::try_from(index).is_ok());
+        index as u16
+    }
+
+    #[inline(always)]
+    fn to_u32(self) -> u32 {
+        u32::from(self)
+    }
+
+    fn to_u32_slice(indices: &[Self]) -> Cow<'_, [u32]> {
+        Cow::Owned(indices.iter().map(|&index| u32::from(index)).collect())
+    }
+
+    fn with_u32_slice_mut<R>(indices: &mut [Self], f: impl FnOnce(&mut [u32]) -> R) -> R {
+        let mut wide: Vec<u32> = indices.iter().map(|&index| u32::from(index)).collect();
+        let result = f(&mut wide);
+        for (index, &wide) in indices.iter_mut().zip(&wide) {
+            *index = Self::from_u32(wide);
+        }
+        result
+    }
+}
+
+impl Index for u32 {
+    #[inline(always)]
+    fn from_u32(index: u32) -> Self {
+        index
+    }
+
+    #[inline(always)]
+    fn to_u32(self) -> u32 {
+        self
+    }
+
+    fn to_u32_slice(indices: &[Self]) -> Cow<'_, [u32]> {
+        Cow::Borrowed(indices)
+    }
+
+    fn with_u32_slice_mut<R>(indices: &mut [Self], f: impl FnOnce(&mut [u32]) -> R) -> R {
+        f(indices)
+    }
+}
+
+/// Reinterprets a value as bytes.
+///
+/// The value must not contain padding bytes; with the `bytemuck` feature, prefer
+/// `bytemuck::bytes_of` for the vertex and header types of this crate, which checks this.
 #[inline(always)]
 pub fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     typed_to_bytes(std::slice::from_ref(p))
 }
 
+/// Reinterprets a slice as bytes.
+///
+/// The elements must not contain padding bytes; with the `bytemuck` feature, prefer
+/// `bytemuck::cast_slice` for the vertex and header types of this crate, which checks this.
 #[inline(always)]
 pub fn typed_to_bytes<T: Sized>(typed: &[T]) -> &[u8] {
     unsafe { std::slice::from_raw_parts(typed.as_ptr().cast(), std::mem::size_of_val(typed)) }
 }
 
+/// Incremental 64-bit FNV-1a hash over the bytes of mesh buffers, see `content_hash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentHasher {
+    state: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub fn new() -> Self {
+        Self {
+            state: Self::OFFSET_BASIS,
+        }
+    }
+
+    /// Adds the bytes of `data` to the hash.
+    pub fn update<T>(&mut self, data: &[T]) {
+        for &byte in typed_to_bytes(data) {
+            self.state = (self.state ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+/// Returns a hash of the bytes of `data` that is stable across platforms, compilers and
+/// releases of this crate, suitable as a cache key for cooked meshes.
+///
+/// The optimizers, simplifiers and encoders of this crate are deterministic: given
+/// byte-identical inputs and parameters, they produce byte-identical output on every
+/// platform, as meshoptimizer only uses IEEE 754 arithmetic and is built without
+/// floating point contraction. The hash covers the in-memory representation of `T`, which
+/// is the same on all supported (little-endian) targets as long as `T` has no padding.
+///
+/// Use `ContentHasher` to hash several buffers, e.g. indices and vertices, together.
+pub fn content_hash<T>(data: &[T]) -> u64 {
+    let mut hasher = ContentHasher::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
 pub fn convert_indices_32_to_16(indices: &[u32]) -> Result<Vec<u16>> {
     let mut result: Vec<u16> = Vec::with_capacity(indices.len());
     for index in indices {
@@ -32,6 +162,220 @@ pub fn convert_indices_16_to_32(indices: &[u16]) -> Result<Vec<u32>> {
     Ok(result)
 }
 
+/// Output buffer of `len` elements for native functions, allocated without zero-filling
+/// since the native function overwrites the elements it reports as written.
+pub(crate) struct UninitBuffer<T> {
+    vec: Vec<T>,
+    len: usize,
+}
+
+impl<T> UninitBuffer<T> {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            vec: Vec::with_capacity(len),
+            len,
+        }
+    }
+
+    /// Same as `new`, but returns `Error::Memory` instead of aborting if the buffer can't
+    /// be allocated.
+    pub(crate) fn try_new(len: usize) -> Result<Self> {
+        let mut vec = Vec::new();
+        try_reserve(&mut vec, len)?;
+        Ok(Self { vec, len })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Pointer to the (uninitialized) storage of the `len` elements.
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
+        self.vec.spare_capacity_mut().as_mut_ptr().cast()
+    }
+
+    /// The (uninitialized) storage of the `len` elements, e.g. to be split across threads.
+    pub(crate) fn as_uninit_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        &mut self.vec.spare_capacity_mut()[..self.len]
+    }
+
+    /// Returns the first `count` elements as a vector.
+    ///
+    /// # Safety
+    ///
+    /// The first `count` elements must have been written through `as_mut_ptr`.
+    pub(crate) unsafe fn assume_init(mut self, count: usize) -> Vec<T> {
+        assert!(
+            count <= self.len,
+            "{} elements written to a buffer of {}",
+            count,
+            self.len
+        );
+        self.vec.set_len(count);
+        self.vec
+    }
+}
+
+/// Makes room for `len` elements in the empty vector `vec`, returning `Error::Memory`
+/// instead of aborting if the allocation fails.
+pub(crate) fn try_reserve<T>(vec: &mut Vec<T>, len: usize) -> Result<()> {
+    vec.try_reserve_exact(len).map_err(|err| {
+        Error::memory_dynamic(format!(
+            "failed to allocate {} elements of {} bytes: {}",
+            len,
+            std::mem::size_of::<T>(),
+            err
+        ))
+    })
+}
+
+/// Checks that every index in `indices` references one of `vertex_count` vertices.
+///
+/// Out-of-range indices are undefined behavior for the native functions of this crate;
+/// enabling the `validate_indices` feature runs this check before every optimize, simplify
+/// and clusterize call, panicking with the offending index instead.
+pub fn validate_indices<T: Index>(indices: &[T], vertex_count: usize) -> Result<()> {
+    match indices
+        .iter()
+        .position(|index| index.to_u32() as usize >= vertex_count)
+    {
+        Some(position) => Err(Error::Config(format!(
+            "index {} at position {} is out of range for {} vertices",
+            indices[position].to_u32(),
+            position,
+            vertex_count
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Runs `validate_indices` if the `validate_indices` feature is enabled.
+#[inline]
+pub(crate) fn check_indices<T: Index>(indices: &[T], vertex_count: usize) {
+    if cfg!(feature = "validate_indices") {
+        if let Err(err) = validate_indices(indices, vertex_count) {
+            panic!("{}", err);
+        }
+    }
+}
+
+/// Index buffer with either 16-bit or 32-bit indices.
+///
+/// The functions of this crate accept both index types, so the variants can be passed on
+/// directly after matching on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Default for IndexBuffer {
+    fn default() -> Self {
+        IndexBuffer::U16(Vec::new())
+    }
+}
+
+impl IndexBuffer {
+    /// Creates an index buffer using 16-bit indices if all indices fit, and 32-bit indices
+    /// otherwise.
+    ///
+    /// Occurrences of `restart_index` (typically 0xffffffff) are converted to 0xffff when
+    /// narrowing; since 0xffff is then reserved for restarts, meshes using vertex 0xffff keep
+    /// 32-bit indices.
+    pub fn from_u32_auto(indices: Vec<u32>, restart_index: Option<u32>) -> Self {
+        let limit = if restart_index.is_some() {
+            u32::from(u16::MAX)
+        } else {
+            u32::from(u16::MAX) + 1
+        };
+        let fits = indices
+            .iter()
+            .all(|&index| Some(index) == restart_index || index < limit);
+        if !fits {
+            return IndexBuffer::U32(indices);
+        }
+        IndexBuffer::U16(
+            indices
+                .iter()
+                .map(|&index| {
+                    if Some(index) == restart_index {
+                        u16::MAX
+                    } else {
+                        index as u16
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexBuffer::U16(indices) => indices.len(),
+            IndexBuffer::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Size of a single index in bytes.
+    pub fn index_size(&self) -> usize {
+        match self {
+            IndexBuffer::U16(_) => 2,
+            IndexBuffer::U32(_) => 4,
+        }
+    }
+
+    /// Returns the indices as 32-bit indices, borrowing them if they already are.
+    ///
+    /// Restart indices aren't converted; use `into_u32` for that.
+    pub fn as_u32(&self) -> Cow<'_, [u32]> {
+        match self {
+            IndexBuffer::U16(indices) => u16::to_u32_slice(indices),
+            IndexBuffer::U32(indices) => Cow::Borrowed(indices),
+        }
+    }
+
+    /// Converts the indices to 32-bit indices, converting 16-bit `restart_index`
+    /// values (typically 0xffff) to 0xffffffff.
+    pub fn into_u32(self, restart_index: Option<u16>) -> Vec<u32> {
+        match self {
+            IndexBuffer::U16(indices) => indices
+                .iter()
+                .map(|&index| {
+                    if Some(index) == restart_index {
+                        u32::MAX
+                    } else {
+                        u32::from(index)
+                    }
+                })
+                .collect(),
+            IndexBuffer::U32(indices) => indices,
+        }
+    }
+
+    /// Returns the raw bytes of the indices, e.g. for uploading to a GPU buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            IndexBuffer::U16(indices) => typed_to_bytes(indices),
+            IndexBuffer::U32(indices) => typed_to_bytes(indices),
+        }
+    }
+}
+
+impl From<Vec<u16>> for IndexBuffer {
+    fn from(indices: Vec<u16>) -> Self {
+        IndexBuffer::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for IndexBuffer {
+    fn from(indices: Vec<u32>) -> Self {
+        IndexBuffer::U32(indices)
+    }
+}
+
 /// Quantize a float in [0..1] range into an N-bit fixed point unorm value.
 ///
 /// Assumes reconstruction function (q / (2^N-1)), which is the case for
@@ -52,15 +396,43 @@ pub fn quantize_unorm(v: f32, n: i32) -> i32 {
 /// fixed-function normalized fixed point conversion (except early OpenGL versions).
 ///
 /// Maximum reconstruction error: 1/2^N.
+///
+/// `n` must be in 2..=32; with a single bit there is no room for a non-zero magnitude.
 #[inline(always)]
 pub fn quantize_snorm(v: f32, n: u32) -> i32 {
-    let scale = ((1 << (n - 1)) - 1) as f32;
+    debug_assert!((2..=32).contains(&n), "n ({}) must be in 2..=32", n);
+    let scale = ((1i64 << (n - 1)) - 1) as f32;
     let round = if v >= 0f32 { 0.5f32 } else { -0.5f32 };
     let v = if v >= -1f32 { v } else { -1f32 };
     let v = if v <= 1f32 { v } else { 1f32 };
     (v * scale + round) as i32
 }
 
+/// Reconstructs a float in [0..1] range from an N-bit fixed point unorm value produced by
+/// `quantize_unorm`, using the reconstruction function (q / (2^N-1)).
+#[inline(always)]
+pub fn dequantize_unorm(q: i32, n: i32) -> f32 {
+    let scale = ((1i32 << n) - 1i32) as f32;
+    q as f32 / scale
+}
+
+/// Reconstructs a float in [-1..1] range from an N-bit fixed point snorm value produced by
+/// `quantize_snorm`, using the reconstruction function (q / (2^(N-1)-1)).
+///
+/// The most negative value (-2^(N-1)) is clamped to -1. `n` must be in 2..=32, like for
+/// `quantize_snorm`.
+#[inline(always)]
+pub fn dequantize_snorm(q: i32, n: u32) -> f32 {
+    debug_assert!((2..=32).contains(&n), "n ({}) must be in 2..=32", n);
+    let scale = ((1i64 << (n - 1)) - 1) as f32;
+    let v = q as f32 / scale;
+    if v >= -1f32 {
+        v
+    } else {
+        -1f32
+    }
+}
+
 #[repr(C)]
 union FloatUInt {
     fl: f32,
@@ -94,6 +466,30 @@ pub fn quantize_half(v: f32) -> u16 {
     (s | h) as u16
 }
 
+/// Reconstructs a float from a half-precision floating point value produced by `quantize_half`.
+///
+/// Denormals are flushed to zero.
+#[inline(always)]
+pub fn dequantize_half(h: u16) -> f32 {
+    let s = u32::from(h & 0x8000) << 16;
+    let em = u32::from(h & 0x7fff);
+
+    // bias exponent; 112 is relative exponent bias (127-15)
+    let mut ui = (em << 13) + (112 << 23);
+
+    // denormal: flush to zero
+    ui = if em < (1 << 10) { 0 } else { ui };
+
+    // infinity or NaN: keep exponent at 255
+    ui = if em >= (31 << 10) {
+        ui + (112 << 23)
+    } else {
+        ui
+    };
+
+    f32::from_bits(s | ui)
+}
+
 /// Quantize a float into a floating point value with a limited number of significant mantissa bits.
 ///
 /// Generates +-inf for overflow, preserves NaN, flushes denormals to zero, rounds to nearest.
@@ -120,6 +516,26 @@ pub fn quantize_float(v: f32, n: i32) -> f32 {
     unsafe { u.fl }
 }
 
+/// Quantizes each value of `values` with `quantize_unorm`.
+pub fn quantize_unorm_slice(values: &[f32], n: i32) -> Vec<i32> {
+    values.iter().map(|&v| quantize_unorm(v, n)).collect()
+}
+
+/// Quantizes each value of `values` with `quantize_snorm`.
+pub fn quantize_snorm_slice(values: &[f32], n: u32) -> Vec<i32> {
+    values.iter().map(|&v| quantize_snorm(v, n)).collect()
+}
+
+/// Quantizes each value of `values` with `quantize_half`.
+pub fn quantize_half_slice(values: &[f32]) -> Vec<u16> {
+    values.iter().map(|&v| quantize_half(v)).collect()
+}
+
+/// Quantizes each value of `values` with `quantize_float`.
+pub fn quantize_float_slice(values: &[f32], n: i32) -> Vec<f32> {
+    values.iter().map(|&v| quantize_float(v, n)).collect()
+}
+
 #[inline(always)]
 pub fn rcp_safe(v: f32) -> f32 {
     if v.abs() as u32 == 0 {
@@ -129,6 +545,7 @@ pub fn rcp_safe(v: f32) -> f32 {
     }
 }
 
+#[derive(Debug)]
 pub struct VertexDataAdapter<'a> {
     pub reader: Cursor<&'a [u8]>,
     pub vertex_count: usize,
@@ -143,7 +560,7 @@ impl<'a> VertexDataAdapter<'a> {
         position_offset: usize,
     ) -> Result<VertexDataAdapter<'a>> {
         let vertex_count = data.len() / vertex_stride;
-        if data.len() % vertex_stride != 0 {
+        if !data.len().is_multiple_of(vertex_stride) {
             Err(Error::memory_dynamic(format!(
                 "vertex data length ({}) must be evenly divisible by vertex_stride ({})",
                 data.len(),
@@ -164,6 +581,24 @@ impl<'a> VertexDataAdapter<'a> {
         }
     }
 
+    /// Creates an adapter for a slice of vertices of type `T`, with the position (3 x `f32`)
+    /// located at byte `position_offset` within `T`, e.g. as computed by `offset_of!`.
+    pub fn from_slice<T>(
+        vertices: &'a [T],
+        position_offset: usize,
+    ) -> Result<VertexDataAdapter<'a>> {
+        Self::new(
+            typed_to_bytes(vertices),
+            std::mem::size_of::<T>(),
+            position_offset,
+        )
+    }
+
+    /// Returns an iterator over the positions of all vertices.
+    pub fn iter_positions(&self) -> impl ExactSizeIterator<Item = [f32; 3]> + '_ {
+        (0..self.vertex_count).map(move |vertex| self.position(vertex))
+    }
+
     pub fn xyz_f32_at(&mut self, vertex: usize) -> Result<[f32; 3]> {
         if vertex >= self.vertex_count {
             return Err(Error::memory_dynamic(format!(
@@ -184,7 +619,27 @@ impl<'a> VertexDataAdapter<'a> {
         Ok(position)
     }
 
-    pub fn pos_ptr(&self) -> *const f32 {
+    /// Reads the position of `vertex` without moving the reader.
+    ///
+    /// Panics if the vertex is out of range.
+    pub(crate) fn position(&self, vertex: usize) -> [f32; 3] {
+        self.attribute(vertex, self.position_offset)
+    }
+
+    /// Reads `N` floats located at byte `offset` within `vertex` without moving the reader.
+    ///
+    /// Panics if the vertex is out of range.
+    pub(crate) fn attribute<const N: usize>(&self, vertex: usize, offset: usize) -> [f32; N] {
+        let offset = vertex * self.vertex_stride + offset;
+        let bytes = &self.reader.get_ref()[offset..offset + N * 4];
+        let mut result = [0f32; N];
+        for (value, bytes) in result.iter_mut().zip(bytes.chunks_exact(4)) {
+            *value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        result
+    }
+
+    pub(crate) fn pos_ptr(&self) -> *const f32 {
         let vertex_data = self.reader.get_ref();
         let vertex_data = vertex_data.as_ptr().cast::<u8>();
         let positions = unsafe { vertex_data.add(self.position_offset) };
@@ -200,8 +655,13 @@ impl Read for VertexDataAdapter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{typed_to_bytes, Vertex, VertexDataAdapter};
+    use crate::{
+        content_hash, dequantize_half, dequantize_snorm, dequantize_unorm, optimize_vertex_fetch,
+        quantize_half, quantize_snorm, quantize_unorm, typed_to_bytes, validate_indices,
+        ContentHasher, Index, IndexBuffer, Vertex, VertexDataAdapter,
+    };
     use memoffset::offset_of;
+    use std::borrow::Cow;
 
     #[test]
     fn test_xyz_f32_at() {
@@ -232,4 +692,80 @@ mod tests {
 
         adapter.xyz_f32_at(2).expect_err("should fail");
     }
+
+    #[test]
+    fn test_index_to_u32_slice() {
+        let indices: Vec<u32> = vec![0, 1, 2];
+        assert!(matches!(u32::to_u32_slice(&indices), Cow::Borrowed(_)));
+
+        let indices: Vec<u16> = vec![0, 1, 65535];
+        assert_eq!(&*u16::to_u32_slice(&indices), &[0, 1, 65535]);
+    }
+
+    #[test]
+    fn test_dequantize() {
+        assert_eq!(dequantize_unorm(quantize_unorm(0.5, 8), 8), 128.0 / 255.0);
+        assert_eq!(dequantize_unorm(quantize_unorm(1.0, 10), 10), 1.0);
+        assert_eq!(dequantize_snorm(quantize_snorm(-1.0, 8), 8), -1.0);
+        assert_eq!(dequantize_snorm(-128, 8), -1.0);
+        assert_eq!(dequantize_snorm(quantize_snorm(-1.0, 2), 2), -1.0);
+        assert_eq!(dequantize_snorm(quantize_snorm(1.0, 32), 32), 1.0);
+        for v in [0.0f32, 1.0, -2.5, 65504.0, 0.333] {
+            assert!((dequantize_half(quantize_half(v)) - v).abs() <= v.abs() * 5e-4);
+        }
+        assert!(dequantize_half(quantize_half(f32::INFINITY)).is_infinite());
+    }
+
+    #[test]
+    fn test_index_buffer_from_u32_auto() {
+        let indices = IndexBuffer::from_u32_auto(vec![0, 1, 2, u32::MAX, 3, 4], Some(u32::MAX));
+        assert_eq!(indices, IndexBuffer::U16(vec![0, 1, 2, 0xffff, 3, 4]));
+        assert_eq!(
+            indices.into_u32(Some(0xffff)),
+            vec![0, 1, 2, u32::MAX, 3, 4]
+        );
+
+        // 0xffff is reserved for restarts
+        let indices = IndexBuffer::from_u32_auto(vec![0, 0xffff, u32::MAX], Some(u32::MAX));
+        assert_eq!(indices.index_size(), 4);
+        let indices = IndexBuffer::from_u32_auto(vec![0, 0xffff], None);
+        assert_eq!(indices.index_size(), 2);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        // 64-bit FNV-1a reference values
+        assert_eq!(content_hash::<u8>(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let mut hasher = ContentHasher::new();
+        hasher.update(&[1u32, 2]);
+        hasher.update(&[3u32]);
+        assert_eq!(hasher.finish(), content_hash(&[1u32, 2, 3]));
+    }
+
+    #[test]
+    fn test_content_hash_optimized() {
+        let vertices = [
+            [0f32, 0f32, 0f32],
+            [1f32, 0f32, 0f32],
+            [0f32, 1f32, 0f32],
+            [1f32, 1f32, 0f32],
+        ];
+        let mut indices = [3u32, 1, 2, 2, 1, 0];
+        let vertices = optimize_vertex_fetch(&mut indices, &vertices);
+
+        // pinned so that changes to the output on any platform are caught
+        let mut hasher = ContentHasher::new();
+        hasher.update(&indices);
+        hasher.update(&vertices);
+        assert_eq!(hasher.finish(), 0xa0fd_0f94_21b9_3e76);
+    }
+
+    #[test]
+    fn test_validate_indices() {
+        assert!(validate_indices(&[0u16, 1, 2], 3).is_ok());
+        assert!(validate_indices(&[0u32, 3, 2], 3).is_err());
+        assert!(validate_indices::<u32>(&[], 0).is_ok());
+    }
 }
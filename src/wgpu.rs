@@ -0,0 +1,144 @@
+//! Helpers producing buffers and layouts that can be uploaded to `wgpu` as is.
+//!
+//! Buffer contents are padded to `wgpu::COPY_BUFFER_ALIGNMENT`, so they can be written with
+//! `Queue::write_buffer` as well as `DeviceExt::create_buffer_init`.
+
+use crate::{GpuMeshlets, PackedVertex, PackedVertexOct, Vertex};
+use std::mem;
+use wgpu::{
+    BufferAddress, IndexFormat, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode,
+};
+
+/// Vertex types with a matching `wgpu` vertex buffer layout.
+///
+/// Attributes use shader locations 0 (position), 1 (normal) and 2 (texture coordinates).
+pub trait WgpuVertex: Sized {
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    /// Returns the layout of a vertex buffer holding tightly packed values of this type.
+    fn vertex_buffer_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl WgpuVertex for Vertex {
+    const ATTRIBUTES: &'static [VertexAttribute] =
+        &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+}
+
+impl WgpuVertex for PackedVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] =
+        &wgpu::vertex_attr_array![0 => Float16x4, 1 => Snorm8x4, 2 => Float16x2];
+}
+
+impl WgpuVertex for PackedVertexOct {
+    /// The position is read as `Float16x4`, whose last component aliases the octahedron
+    /// encoded normal and should be ignored by the shader.
+    const ATTRIBUTES: &'static [VertexAttribute] = &[
+        VertexAttribute {
+            format: VertexFormat::Float16x4,
+            offset: 0,
+            shader_location: 0,
+        },
+        VertexAttribute {
+            format: VertexFormat::Snorm8x2,
+            offset: 6,
+            shader_location: 1,
+        },
+        VertexAttribute {
+            format: VertexFormat::Float16x2,
+            offset: 8,
+            shader_location: 2,
+        },
+    ];
+}
+
+/// Returns `IndexFormat::Uint16` if every index of a mesh with `vertex_count` vertices fits
+/// in 16 bits, and `IndexFormat::Uint32` otherwise.
+///
+/// Strip topologies reserve the largest index for primitive restart, so `Uint16` is only
+/// chosen for strips with fewer than 65535 vertices.
+pub fn index_format(vertex_count: usize, strip: bool) -> IndexFormat {
+    let limit = if strip { 0xffff } else { 0x10000 };
+    if vertex_count <= limit {
+        IndexFormat::Uint16
+    } else {
+        IndexFormat::Uint32
+    }
+}
+
+/// Converts `indices` to the format chosen by `index_format` for a triangle list, returning
+/// the format and the contents of the index buffer.
+///
+/// # Panics
+///
+/// Panics if an index is out of range for `vertex_count` vertices.
+pub fn index_buffer(indices: &[u32], vertex_count: usize) -> (IndexFormat, Vec<u8>) {
+    assert!(indices.iter().all(|&index| (index as usize) < vertex_count));
+
+    let format = index_format(vertex_count, false);
+    let mut data = match format {
+        IndexFormat::Uint16 => indices
+            .iter()
+            .flat_map(|&index| (index as u16).to_le_bytes())
+            .collect(),
+        IndexFormat::Uint32 => indices
+            .iter()
+            .flat_map(|&index| index.to_le_bytes())
+            .collect(),
+    };
+    pad_to_copy_alignment(&mut data);
+    (format, data)
+}
+
+/// Contents of the storage buffers holding `GpuMeshlets`, as returned by `meshlet_buffers`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshletBuffers {
+    /// `GpuMeshlet` descriptors, 16 bytes each.
+    pub meshlets: Vec<u8>,
+    /// Meshlet vertex indices as `u32` values.
+    pub vertices: Vec<u8>,
+    /// Micro-indices, packed four to a `u32` word.
+    pub triangles: Vec<u8>,
+}
+
+/// Serializes `meshlets` into storage buffer contents.
+///
+/// Every buffer holds at least one word, as empty buffers can't be bound, and all sizes are
+/// multiples of 4 bytes, as required for `array<u32>` storage bindings.
+pub fn meshlet_buffers(meshlets: &GpuMeshlets) -> MeshletBuffers {
+    let descriptors = meshlets
+        .meshlets
+        .iter()
+        .flat_map(|meshlet| {
+            [
+                meshlet.vertex_offset,
+                meshlet.triangle_offset,
+                meshlet.vertex_count,
+                meshlet.triangle_count,
+            ]
+        })
+        .collect::<Vec<u32>>();
+    MeshletBuffers {
+        meshlets: storage_buffer(&descriptors),
+        vertices: storage_buffer(&meshlets.vertices),
+        triangles: storage_buffer(&meshlets.triangles),
+    }
+}
+
+fn storage_buffer(words: &[u32]) -> Vec<u8> {
+    let mut data: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    if data.is_empty() {
+        data.resize(mem::size_of::<u32>(), 0);
+    }
+    data
+}
+
+fn pad_to_copy_alignment(data: &mut Vec<u8>) {
+    let padded = wgpu::util::align_to(data.len() as BufferAddress, wgpu::COPY_BUFFER_ALIGNMENT);
+    data.resize(padded as usize, 0);
+}